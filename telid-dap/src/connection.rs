@@ -0,0 +1,129 @@
+use serde_json::{json, Value};
+use std::{
+  io::{stdin, stdout, BufRead, BufReader, Stdout, Write},
+  sync::mpsc::{channel, Receiver, TryRecvError},
+  thread,
+};
+
+// A request from the client. `arguments` is `null` when there aren't any
+pub struct Request {
+  pub seq: i64,
+  pub command: String,
+  pub arguments: Value,
+}
+
+// Content-Length framed DAP messages over stdio. Requests are read on their
+// own thread, so that they can be checked for while the program runs
+#[derive(Debug)]
+pub struct Connection {
+  requests: Receiver<Request>,
+  writer: Stdout,
+  seq: i64,
+}
+
+impl Connection {
+  pub fn stdio() -> Self {
+    let (sender, requests) = channel();
+    thread::spawn(move || {
+      let mut reader = BufReader::new(stdin());
+      while let Some(request) = read_request(&mut reader) {
+        if sender.send(request).is_err() {
+          return;
+        }
+      }
+    });
+
+    Connection {
+      requests,
+      writer: stdout(),
+      seq: 0,
+    }
+  }
+
+  // The next request. `None` once the client has gone away
+  pub fn receive(&mut self) -> Option<Request> {
+    self.requests.recv().ok()
+  }
+
+  // The next request if there's one already, without waiting for it. `Err`
+  // once the client has gone away
+  pub fn try_receive(&mut self) -> Result<Option<Request>, ()> {
+    match self.requests.try_recv() {
+      Ok(request) => Ok(Some(request)),
+      Err(TryRecvError::Empty) => Ok(None),
+      Err(TryRecvError::Disconnected) => Err(()),
+    }
+  }
+
+  pub fn respond(&mut self, request: &Request, result: Result<Value, String>) {
+    let mut response = json!({
+      "type": "response",
+      "request_seq": request.seq,
+      "command": request.command,
+      "success": result.is_ok(),
+    });
+    match result {
+      Ok(body) => response["body"] = body,
+      Err(message) => response["message"] = json!(message),
+    }
+    self.send(response);
+  }
+
+  pub fn event(&mut self, event: &str, body: Value) {
+    self.send(json!({ "type": "event", "event": event, "body": body }));
+  }
+
+  fn send(&mut self, mut message: Value) {
+    self.seq += 1;
+    message["seq"] = json!(self.seq);
+
+    let body = message.to_string();
+    write!(
+      self.writer,
+      "Content-Length: {}\r\n\r\n{}",
+      body.len(),
+      body
+    )
+    .unwrap();
+    self.writer.flush().unwrap();
+  }
+}
+
+// The next request, skipping anything else the client sends. `None` once the
+// client has gone away
+fn read_request(reader: &mut impl BufRead) -> Option<Request> {
+  loop {
+    let message = read(reader)?;
+    if message["type"] != json!("request") {
+      continue;
+    }
+
+    return Some(Request {
+      seq: message["seq"].as_i64().unwrap_or_default(),
+      command: message["command"].as_str().unwrap_or_default().to_string(),
+      arguments: message["arguments"].clone(),
+    });
+  }
+}
+
+fn read(reader: &mut impl BufRead) -> Option<Value> {
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+      return None;
+    }
+
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some(value) = line.strip_prefix("Content-Length: ") {
+      length = value.parse::<usize>().ok();
+    }
+  }
+
+  let mut body = vec![0; length?];
+  reader.read_exact(&mut body).ok()?;
+  serde_json::from_slice(&body).ok()
+}
@@ -0,0 +1,64 @@
+use connection::Connection;
+use serde_json::{json, Value};
+use session::{exited, run, set_breakpoints, threads, Launch};
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+
+mod connection;
+mod session;
+
+// Configures a debugging session, and once the client is done setting it up,
+// runs the program. While it runs, only `pause`, `disconnect`, `threads` and
+// `setBreakpoints` are answered, and the rest fail until it pauses
+fn main() {
+  let connection = Rc::new(RefCell::new(Connection::stdio()));
+  let mut breakpoints = BTreeSet::new();
+  let mut launch = None;
+  let mut configured = false;
+
+  loop {
+    let Some(request) = connection.borrow_mut().receive() else {
+      return;
+    };
+    let arguments = &request.arguments;
+
+    let result = match request.command.as_str() {
+      "initialize" => Ok(json!({ "supportsConfigurationDoneRequest": true })),
+      "setBreakpoints" => {
+        let (lines, body) = set_breakpoints(arguments);
+        breakpoints = lines;
+        Ok(body)
+      }
+      "launch" => match arguments["program"].as_str() {
+        Some(program) => {
+          launch = Some(Launch {
+            program: program.to_string(),
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+          });
+          Ok(Value::Null)
+        }
+        None => Err(String::from("Expected a program to launch")),
+      },
+      "configurationDone" => {
+        configured = true;
+        Ok(Value::Null)
+      }
+      "threads" => Ok(threads()),
+      "disconnect" => Ok(Value::Null),
+      command => Err(format!("Unsupported request: {}", command)),
+    };
+    connection.borrow_mut().respond(&request, result);
+
+    match request.command.as_str() {
+      "initialize" => connection.borrow_mut().event("initialized", json!({})),
+      "disconnect" => return,
+      _ => {}
+    }
+
+    if configured {
+      if let Some(launch) = launch.take() {
+        let code = run(&connection, launch, std::mem::take(&mut breakpoints));
+        exited(&connection, code);
+      }
+    }
+  }
+}
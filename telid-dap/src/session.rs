@@ -0,0 +1,346 @@
+use crate::connection::{Connection, Request};
+use serde_json::{json, Value};
+use std::{cell::RefCell, collections::BTreeSet, rc::Rc};
+use telid_lang::{
+  debugger::{Debugger, Frontend, Paused, Reason, Step},
+  error::source_span,
+  evaluator::{
+    context::Context,
+    evaluate_with_context,
+    io::Io,
+    scope,
+    value::{Value as TelidValue, Variable},
+  },
+  position::LineIndex,
+  program::compile,
+  resolver::resolve,
+};
+
+pub type Shared = Rc<RefCell<Connection>>;
+
+// Telid programs have a single thread
+const THREAD: i64 = 1;
+
+// What the client asked to debug
+pub struct Launch {
+  pub program: String,
+  pub stop_on_entry: bool,
+}
+
+// Runs the program until it ends, and returns its exit code. Problems that
+// stop it from running are sent to the client as output, like its own output
+pub fn run(connection: &Shared, launch: Launch, breakpoints: BTreeSet<usize>) -> i32 {
+  let Launch {
+    program: path,
+    stop_on_entry,
+  } = launch;
+  let error = |message: String| {
+    connection.borrow_mut().event(
+      "output",
+      json!({ "category": "stderr", "output": format!("{}\n", message) }),
+    );
+    1
+  };
+
+  let source = match std::fs::read_to_string(&path) {
+    Ok(source) => source,
+    Err(err) => return error(format!("{}: {}", path, err)),
+  };
+  let lines = LineIndex::new(&source);
+  let location = |offset: usize| {
+    let position = lines.position(offset);
+    format!("{}:{}:{}", path, position.line + 1, position.column + 1)
+  };
+
+  let scope = scope::default();
+  let program = compile(&source, &scope, resolve);
+  if let Some(err) = program.errors.first() {
+    let span = err.span(&program.tokens);
+    return error(format!("{}: {}", location(span.start), err.message()));
+  }
+
+  let mut context = Context::new();
+  context.io = Box::new(Output(connection.clone()));
+  context.hook = Some(Box::new(Debugger::new(
+    &source,
+    Session {
+      connection: connection.clone(),
+      path: path.clone(),
+      breakpoints,
+      stop_on_entry,
+    },
+  )));
+
+  match evaluate_with_context(program.ast, scope, &mut context) {
+    Ok(_) => 0,
+    Err(err) => {
+      let at =
+        |span: &std::ops::Range<usize>| location(source_span(&program.tokens, span.clone()).start);
+
+      let mut message = format!("{}: {}", at(&err.span), err.kind);
+      for frame in &err.stack {
+        message += &format!("\n  in call to {} at {}", frame.name, at(&frame.span));
+      }
+      error(message)
+    }
+  }
+}
+
+// Tells the client the program has ended
+pub fn exited(connection: &Shared, code: i32) {
+  let mut connection = connection.borrow_mut();
+  connection.event("exited", json!({ "exitCode": code }));
+  connection.event("terminated", json!({}));
+}
+
+// The lines to break on, counting from 0, and the response to send
+pub fn set_breakpoints(arguments: &Value) -> (BTreeSet<usize>, Value) {
+  let lines = arguments["breakpoints"]
+    .as_array()
+    .into_iter()
+    .flatten()
+    .filter_map(|breakpoint| breakpoint["line"].as_u64())
+    .filter(|line| *line > 0)
+    .collect::<Vec<_>>();
+
+  let body = json!({
+    "breakpoints": lines
+      .iter()
+      .map(|line| json!({ "verified": true, "line": line }))
+      .collect::<Vec<_>>(),
+  });
+  (lines.iter().map(|line| *line as usize - 1).collect(), body)
+}
+
+pub fn threads() -> Value {
+  json!({ "threads": [{ "id": THREAD, "name": "main" }] })
+}
+
+// The program's output goes to the client as output events, since stdout
+// belongs to the protocol. There's no input to read
+#[derive(Debug)]
+struct Output(Shared);
+
+impl Io for Output {
+  fn write(&mut self, text: &str) {
+    self
+      .0
+      .borrow_mut()
+      .event("output", json!({ "category": "stdout", "output": text }));
+  }
+
+  fn read_line(&mut self) -> Option<String> {
+    None
+  }
+
+  // The client still expects an answer to `disconnect` once it's told
+  fn exit(&mut self, code: i32) -> ! {
+    exited(&self.0, code);
+    loop {
+      let Some(request) = self.0.borrow_mut().receive() else {
+        std::process::exit(code);
+      };
+      let result = match request.command.as_str() {
+        "disconnect" => Ok(Value::Null),
+        _ => Err(String::from("The program has exited")),
+      };
+      self.0.borrow_mut().respond(&request, result);
+      if request.command == "disconnect" {
+        std::process::exit(code);
+      }
+    }
+  }
+}
+
+// Answers the client's questions while the program is paused
+#[derive(Debug)]
+struct Session {
+  connection: Shared,
+  path: String,
+  // Set before the program started
+  breakpoints: BTreeSet<usize>,
+  stop_on_entry: bool,
+}
+
+impl Frontend for Session {
+  fn paused(&mut self, paused: &mut Paused) -> Step {
+    let reason = match paused.reason {
+      Reason::Entry => {
+        paused.breakpoints.append(&mut self.breakpoints);
+        if !self.stop_on_entry {
+          return Step::Continue;
+        }
+        "entry"
+      }
+      Reason::Breakpoint => "breakpoint",
+      Reason::Step => "step",
+      Reason::Pause => "pause",
+    };
+    self.connection.borrow_mut().event(
+      "stopped",
+      json!({ "reason": reason, "threadId": THREAD, "allThreadsStopped": true }),
+    );
+
+    loop {
+      let Some(request) = self.connection.borrow_mut().receive() else {
+        std::process::exit(0);
+      };
+
+      let step = match request.command.as_str() {
+        "continue" => Some(Step::Continue),
+        "next" => Some(Step::Over),
+        "stepIn" => Some(Step::Into),
+        "stepOut" => Some(Step::Out),
+        _ => None,
+      };
+      if let Some(step) = step {
+        let body = json!({ "allThreadsContinued": true });
+        self.connection.borrow_mut().respond(&request, Ok(body));
+        return step;
+      }
+
+      let result = self.request(&request, paused);
+      self.connection.borrow_mut().respond(&request, result);
+      if request.command == "disconnect" {
+        std::process::exit(0);
+      }
+    }
+  }
+
+  // Answers what can be answered without the program paused, until there's
+  // nothing left to answer or the client wants it paused
+  fn running(&mut self, breakpoints: &mut BTreeSet<usize>) -> bool {
+    loop {
+      let request = match self.connection.borrow_mut().try_receive() {
+        Ok(Some(request)) => request,
+        Ok(None) => return false,
+        Err(()) => std::process::exit(0),
+      };
+
+      let result = match request.command.as_str() {
+        "pause" | "disconnect" => Ok(Value::Null),
+        "threads" => Ok(threads()),
+        "setBreakpoints" => {
+          let (lines, body) = set_breakpoints(&request.arguments);
+          *breakpoints = lines;
+          Ok(body)
+        }
+        _ => Err(String::from("The program is running")),
+      };
+      self.connection.borrow_mut().respond(&request, result);
+
+      match request.command.as_str() {
+        "pause" => return true,
+        "disconnect" => std::process::exit(0),
+        _ => {}
+      }
+    }
+  }
+}
+
+impl Session {
+  fn request(&self, request: &Request, paused: &mut Paused) -> Result<Value, String> {
+    let arguments = &request.arguments;
+
+    match request.command.as_str() {
+      "threads" => Ok(threads()),
+      "setBreakpoints" => {
+        let (lines, body) = set_breakpoints(arguments);
+        *paused.breakpoints = lines;
+        Ok(body)
+      }
+      "stackTrace" => Ok(self.stack_trace(paused)),
+      // Functions run in their caller's scope, so every stack frame sees the
+      // same variables
+      "scopes" => {
+        let frames = paused.frames().count();
+        let scopes = paused
+          .frames()
+          .enumerate()
+          .filter(|(_, frame)| frame.iter().any(visible))
+          .map(|(depth, _)| {
+            let name = match depth {
+              0 => String::from("Locals"),
+              _ if depth == frames - 1 => String::from("Globals"),
+              _ => format!("Frame {}", depth),
+            };
+            json!({ "name": name, "variablesReference": depth + 1, "expensive": false })
+          })
+          .collect::<Vec<_>>();
+        Ok(json!({ "scopes": scopes }))
+      }
+      "variables" => {
+        let depth = arguments["variablesReference"].as_u64().unwrap_or_default() as usize;
+        let variables = paused
+          .frames()
+          .nth(depth.wrapping_sub(1))
+          .ok_or_else(|| String::from("Unknown variables reference"))?
+          .iter()
+          .filter(|variable| visible(variable))
+          .map(|(name, variable)| {
+            json!({
+              "name": name,
+              "value": describe(&variable.value),
+              "type": variable.value.as_ref(),
+              "variablesReference": 0,
+            })
+          })
+          .collect::<Vec<_>>();
+        Ok(json!({ "variables": variables }))
+      }
+      "evaluate" => {
+        let expression = arguments["expression"].as_str().unwrap_or_default();
+        let value = paused.evaluate(expression)?;
+        Ok(json!({
+          "result": describe(&value),
+          "type": value.as_ref(),
+          "variablesReference": 0,
+        }))
+      }
+      "disconnect" => Ok(Value::Null),
+      command => Err(format!("Unsupported request: {}", command)),
+    }
+  }
+
+  // The paused function first, then each function that called it, down to
+  // the top of the program
+  fn stack_trace(&self, paused: &Paused) -> Value {
+    let calls = paused.call_stack();
+    let names = calls
+      .iter()
+      .map(|(name, _)| name.clone())
+      .chain([String::from("<main>")]);
+    let positions = [paused.position]
+      .into_iter()
+      .chain(calls.iter().map(|(_, position)| *position));
+
+    let frames = names
+      .zip(positions)
+      .enumerate()
+      .map(|(id, (name, position))| {
+        json!({
+          "id": id,
+          "name": name,
+          "line": position.line + 1,
+          "column": position.column + 1,
+          "source": { "path": self.path },
+        })
+      })
+      .collect::<Vec<_>>();
+
+    json!({ "totalFrames": frames.len(), "stackFrames": frames })
+  }
+}
+
+// Builtins would drown out the program's own globals
+fn visible((_, variable): &(String, Variable)) -> bool {
+  !matches!(variable.value, TelidValue::RustFunction { .. })
+}
+
+// Strings are quoted so they can be told apart from other values
+fn describe(value: &TelidValue) -> String {
+  match value {
+    TelidValue::String(string) => format!("{:?}", string),
+    value => value.to_string(),
+  }
+}
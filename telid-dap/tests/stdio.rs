@@ -0,0 +1,193 @@
+use serde_json::{json, Value};
+use std::{
+  io::{BufRead, BufReader, Read, Write},
+  process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+const SOURCE: &str = "let fn double x =
+  * x 2;
+let a = 'hi';
+let b = double(2);
+println(b);
+";
+
+// A minimal client speaking Content-Length framed DAP to the adapter
+struct Client {
+  child: Child,
+  stdin: ChildStdin,
+  stdout: BufReader<ChildStdout>,
+  seq: i64,
+}
+
+impl Client {
+  fn spawn() -> Self {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_telid-dap"))
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .spawn()
+      .unwrap();
+
+    Client {
+      stdin: child.stdin.take().unwrap(),
+      stdout: BufReader::new(child.stdout.take().unwrap()),
+      child,
+      seq: 0,
+    }
+  }
+
+  fn receive(&mut self) -> Value {
+    let mut length = None;
+    loop {
+      let mut line = String::new();
+      self.stdout.read_line(&mut line).unwrap();
+      let line = line.trim_end();
+      if line.is_empty() {
+        break;
+      }
+      if let Some(value) = line.strip_prefix("Content-Length: ") {
+        length = Some(value.parse::<usize>().unwrap());
+      }
+    }
+
+    let mut body = vec![0; length.unwrap()];
+    self.stdout.read_exact(&mut body).unwrap();
+    serde_json::from_slice(&body).unwrap()
+  }
+
+  // Sends a request without waiting for the response
+  fn send(&mut self, command: &str, arguments: Value) -> i64 {
+    self.seq += 1;
+    let body = json!({
+      "seq": self.seq,
+      "type": "request",
+      "command": command,
+      "arguments": arguments,
+    })
+    .to_string();
+    write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    self.stdin.flush().unwrap();
+    self.seq
+  }
+
+  fn request(&mut self, command: &str, arguments: Value) -> Value {
+    let seq = self.send(command, arguments);
+    loop {
+      let message = self.receive();
+      if message["type"] == json!("response") && message["request_seq"] == json!(seq) {
+        assert_eq!(message["success"], json!(true), "{}", message);
+        return message["body"].clone();
+      }
+    }
+  }
+
+  fn wait_for(&mut self, event: &str) -> Value {
+    loop {
+      let message = self.receive();
+      if message["type"] == json!("event") && message["event"] == json!(event) {
+        return message["body"].clone();
+      }
+    }
+  }
+}
+
+#[test]
+fn test_session() {
+  let path = std::env::temp_dir().join(format!("telid-dap-{}.tl", std::process::id()));
+  std::fs::write(&path, SOURCE).unwrap();
+  let path = path.to_str().unwrap().to_string();
+
+  let mut client = Client::spawn();
+
+  client.request("initialize", json!({ "adapterID": "telid" }));
+  client.wait_for("initialized");
+
+  let breakpoints = client.request(
+    "setBreakpoints",
+    json!({ "source": { "path": path }, "breakpoints": [{ "line": 2 }] }),
+  );
+  assert_eq!(breakpoints["breakpoints"][0]["verified"], json!(true));
+
+  client.request("launch", json!({ "program": path }));
+  client.send("configurationDone", json!({}));
+
+  let stopped = client.wait_for("stopped");
+  assert_eq!(stopped["reason"], json!("breakpoint"));
+
+  let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+  let frames = trace["stackFrames"].as_array().unwrap();
+  assert_eq!(frames.len(), 2);
+  assert_eq!(frames[0]["name"], json!("double"));
+  assert_eq!(frames[0]["line"], json!(2));
+  assert_eq!(frames[0]["column"], json!(3));
+  assert_eq!(frames[1]["line"], json!(4));
+  assert_eq!(frames[1]["column"], json!(9));
+
+  let scopes = client.request("scopes", json!({ "frameId": 0 }));
+  let scopes = scopes["scopes"].as_array().unwrap();
+  assert_eq!(scopes[0]["name"], json!("Locals"));
+  let locals = client.request(
+    "variables",
+    json!({ "variablesReference": scopes[0]["variablesReference"] }),
+  );
+  assert_eq!(locals["variables"][0]["name"], json!("x"));
+  assert_eq!(locals["variables"][0]["value"], json!("2"));
+  let globals = client.request(
+    "variables",
+    json!({ "variablesReference": scopes.last().unwrap()["variablesReference"] }),
+  );
+  assert!(globals["variables"]
+    .as_array()
+    .unwrap()
+    .iter()
+    .any(|variable| variable["name"] == json!("a") && variable["value"] == json!("\"hi\"")));
+
+  let evaluated = client.request("evaluate", json!({ "expression": "+ x 1", "frameId": 0 }));
+  assert_eq!(evaluated["result"], json!("3"));
+
+  client.request("next", json!({ "threadId": 1 }));
+  let stopped = client.wait_for("stopped");
+  assert_eq!(stopped["reason"], json!("step"));
+  let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+  assert_eq!(trace["stackFrames"][0]["line"], json!(5));
+
+  client.request("continue", json!({ "threadId": 1 }));
+  let output = client.wait_for("output");
+  assert_eq!(output["output"], json!("4\n"));
+  let exited = client.wait_for("exited");
+  assert_eq!(exited["exitCode"], json!(0));
+  client.wait_for("terminated");
+
+  client.request("disconnect", json!({}));
+  assert!(client.child.wait().unwrap().success());
+
+  std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_pause() {
+  let path = std::env::temp_dir().join(format!("telid-dap-pause-{}.tl", std::process::id()));
+  std::fs::write(&path, "println('started');\nwhile true {}\n").unwrap();
+  let path = path.to_str().unwrap().to_string();
+
+  let mut client = Client::spawn();
+
+  client.request("initialize", json!({ "adapterID": "telid" }));
+  client.wait_for("initialized");
+  client.request("launch", json!({ "program": path }));
+  client.send("configurationDone", json!({}));
+  client.wait_for("output");
+
+  // The loop never ends on its own, but stops when asked
+  client.request("pause", json!({ "threadId": 1 }));
+  let stopped = client.wait_for("stopped");
+  assert_eq!(stopped["reason"], json!("pause"));
+  let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+  assert_eq!(trace["stackFrames"][0]["line"], json!(2));
+
+  // And can be left while it's running
+  client.request("continue", json!({ "threadId": 1 }));
+  client.request("disconnect", json!({}));
+  assert!(client.child.wait().unwrap().success());
+
+  std::fs::remove_file(path).unwrap();
+}
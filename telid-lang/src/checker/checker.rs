@@ -0,0 +1,654 @@
+use crate::{
+  error::{TypeError, TypeErrorKind},
+  evaluator::{scope::Scope, value::Value},
+  parser::ast::{
+    BinaryOperator, Binding, Expression, ExpressionKind, Identifier, Statement, StatementKind,
+    Type, UnaryOperator,
+  },
+  resolver::{DeclarationKind, Reference, Resolution},
+};
+use std::{
+  collections::{HashMap, HashSet},
+  ops::Range,
+};
+
+// Infers what it can about the types of the values in `program`, and reports
+// every value that can never have the type it needs: the one it's annotated
+// with, or the one an operator, condition or builtin expects. Whatever can't
+// be inferred is `Any`, which is never an error. Only mismatches with an
+// annotation are marked `annotated`, so code without annotations is never
+// rejected, just warned about. `resolution` is `program` resolved in `scope`
+pub fn check(program: &[Statement], resolution: &Resolution, scope: &Scope) -> Vec<TypeError> {
+  let mut checker = Checker {
+    resolution,
+    scope,
+    references: resolution
+      .references
+      .iter()
+      .map(|reference| (reference.span.start, reference))
+      .collect(),
+    declarations: resolution
+      .declarations
+      .iter()
+      .enumerate()
+      .filter_map(|(index, declaration)| declaration.span.as_ref().map(|span| (span.start, index)))
+      .collect(),
+    assigned: resolution
+      .references
+      .iter()
+      .filter(|reference| reference.assignment)
+      .filter_map(|reference| reference.declaration)
+      .collect(),
+    types: HashMap::new(),
+    annotations: HashMap::new(),
+    signatures: HashMap::new(),
+    errors: Vec::new(),
+  };
+
+  for statement in program {
+    checker.statement(statement);
+  }
+
+  let mut errors = checker.errors;
+  errors.sort_by_key(|error| (error.span.start, error.span.end));
+  errors
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Signature {
+  parameters: Vec<Type>,
+  returns: Type,
+  // Whether the parameter types are annotations, rather than a builtin's
+  annotated: bool,
+}
+
+struct Checker<'a> {
+  resolution: &'a Resolution,
+  scope: &'a Scope,
+  // References and declarations by the token their identifier is at
+  references: HashMap<usize, &'a Reference>,
+  declarations: HashMap<usize, usize>,
+  // Declarations that something assigns to, which could hold anything
+  assigned: HashSet<usize>,
+  // What's known about declarations, by index
+  types: HashMap<usize, Type>,
+  annotations: HashMap<usize, Type>,
+  signatures: HashMap<usize, Signature>,
+  errors: Vec<TypeError>,
+}
+
+impl Checker<'_> {
+  fn error(&mut self, kind: TypeErrorKind, span: Range<usize>, annotated: bool) {
+    self.errors.push(TypeError {
+      kind,
+      span,
+      annotated,
+    });
+  }
+
+  // `annotated` is whether `expected` is an annotation
+  fn expect(&mut self, found: &Type, expected: &Type, span: Range<usize>, annotated: bool) {
+    if !compatible(found, expected) {
+      self.error(
+        TypeErrorKind::InvalidType(found.to_string(), expected.to_string()),
+        span,
+        annotated,
+      );
+    }
+  }
+
+  fn declaration_type(&self, declaration: usize) -> Type {
+    if let Some(known) = self.types.get(&declaration) {
+      return known.clone();
+    }
+
+    let declaration = &self.resolution.declarations[declaration];
+    match declaration.kind {
+      DeclarationKind::Function { .. } => Type::Function,
+      DeclarationKind::Global { .. } => match self.scope.get(&declaration.name) {
+        Some(variable) => match &variable.annotation {
+          Some(annotation) => annotation.clone(),
+          None if variable.constant => type_of(&variable.value),
+          None => Type::Any,
+        },
+        None => Type::Any,
+      },
+      _ => Type::Any,
+    }
+  }
+
+  // Only names that are sure to be found where the resolver says have a known
+  // type. Function bodies see their caller's variables, but functions are
+  // rarely shadowed, so calls to them are checked anywhere
+  fn declaration(&self, identifier: &Identifier) -> Option<usize> {
+    let reference = self.references.get(&identifier.1.start)?;
+    let declaration = reference.declaration?;
+    match self.resolution.declarations[declaration].kind {
+      DeclarationKind::Function { .. } | DeclarationKind::Global { .. } => Some(declaration),
+      _ if reference.binding != Binding::Dynamic => Some(declaration),
+      _ => None,
+    }
+  }
+
+  fn reference_type(&self, identifier: &Identifier) -> Type {
+    self
+      .declaration(identifier)
+      .map_or(Type::Any, |declaration| self.declaration_type(declaration))
+  }
+
+  fn signature(&self, identifier: &Identifier) -> Option<Signature> {
+    let declaration = self.declaration(identifier)?;
+    if let Some(signature) = self.signatures.get(&declaration) {
+      return Some(signature.clone());
+    }
+
+    let declaration = &self.resolution.declarations[declaration];
+    if !matches!(declaration.kind, DeclarationKind::Global { .. }) {
+      return None;
+    }
+    match &self.scope.get(&declaration.name)?.value {
+      Value::Function {
+        parameter_types,
+        return_type,
+        generator,
+        ..
+      } => Some(Signature {
+        parameters: parameter_types
+          .iter()
+          .map(|annotation| annotation.clone().unwrap_or(Type::Any))
+          .collect(),
+        returns: match generator {
+          true => Type::Generator,
+          false => return_type.clone().unwrap_or(Type::Any),
+        },
+        annotated: true,
+      }),
+      Value::RustFunction {
+        parameter_count, ..
+      } => builtin_signature(&declaration.name)
+        .filter(|signature| signature.parameters.len() == *parameter_count),
+      _ => None,
+    }
+  }
+
+  // The type of the value the statement evaluates to
+  fn statement(&mut self, statement: &Statement) -> Type {
+    match &statement.kind {
+      StatementKind::Block(statements) => {
+        let mut last = Type::Void;
+        for statement in statements {
+          last = self.statement(statement);
+        }
+        last
+      }
+      StatementKind::Let {
+        name,
+        value,
+        constant,
+        annotation,
+      } => {
+        let found = self.expression(value);
+        if let Some(annotation) = annotation {
+          self.expect(&found, annotation, value.span.clone(), true);
+        }
+
+        if let Some(declaration) = self.declarations.get(&name.1.start).copied() {
+          let known = match annotation {
+            Some(annotation) => {
+              self.annotations.insert(declaration, annotation.clone());
+              annotation.clone()
+            }
+            None if *constant || !self.assigned.contains(&declaration) => found.clone(),
+            None => Type::Any,
+          };
+          self.types.insert(declaration, known);
+        }
+
+        annotation.clone().unwrap_or(found)
+      }
+      StatementKind::Expression(expression) => self.expression(expression),
+      StatementKind::FunctionDeclaration {
+        name,
+        parameters,
+        parameter_types,
+        return_type,
+        body,
+      } => {
+        let parameter_types = parameter_types
+          .iter()
+          .map(|annotation| annotation.clone().unwrap_or(Type::Any))
+          .collect::<Vec<_>>();
+        for (parameter, parameter_type) in parameters.iter().zip(&parameter_types) {
+          if let Some(declaration) = self.declarations.get(&parameter.1.start).copied() {
+            self.types.insert(declaration, parameter_type.clone());
+          }
+        }
+
+        // Reassigned functions could be anything by the time they're called
+        let declaration = self
+          .declarations
+          .get(&name.1.start)
+          .copied()
+          .filter(|declaration| !self.assigned.contains(declaration));
+
+        // Known before the body is checked, for recursive calls. Calling a
+        // generator function makes a generator, and the return type is what
+        // the generator returns
+        let generator = body.yields();
+        if let Some(declaration) = declaration {
+          self.signatures.insert(
+            declaration,
+            Signature {
+              parameters: parameter_types,
+              returns: match generator {
+                true => Type::Generator,
+                false => return_type.clone().unwrap_or(Type::Any),
+              },
+              annotated: true,
+            },
+          );
+        }
+
+        let found = self.statement(body);
+        match return_type {
+          Some(return_type) => self.expect(&found, return_type, body.span.clone(), true),
+          None if generator => {}
+          None => {
+            if let Some(signature) = declaration.and_then(|d| self.signatures.get_mut(&d)) {
+              signature.returns = found;
+            }
+          }
+        }
+
+        Type::Void
+      }
+      StatementKind::Assignment { name, value } => {
+        let found = self.expression(value);
+        let annotation = self
+          .declaration(name)
+          .and_then(|declaration| self.annotations.get(&declaration))
+          .cloned();
+        if let Some(annotation) = annotation {
+          self.expect(&found, &annotation, value.span.clone(), true);
+        }
+        found
+      }
+    }
+  }
+
+  fn expression(&mut self, expression: &Expression) -> Type {
+    match &expression.kind {
+      ExpressionKind::Void => Type::Void,
+      ExpressionKind::Error => Type::Any,
+      ExpressionKind::Identifier(identifier) => self.reference_type(identifier),
+      ExpressionKind::NumberLiteral(_) => Type::Number,
+      ExpressionKind::StringLiteral(_) => Type::String,
+      ExpressionKind::BooleanLiteral(_) => Type::Boolean,
+      ExpressionKind::ArrayLiteral(expressions) => {
+        let elements = expressions
+          .iter()
+          .map(|expression| self.expression(expression))
+          .collect::<Vec<_>>();
+        if elements.is_empty() {
+          Type::Array(Box::new(Type::Any))
+        } else {
+          Type::Array(Box::new(union(elements)))
+        }
+      }
+      ExpressionKind::Index { iterable, index } => {
+        let iterable_type = self.expression(iterable);
+        let index_type = self.expression(index);
+        self.expect(
+          &iterable_type,
+          &iterable_types(),
+          iterable.span.clone(),
+          false,
+        );
+        self.expect(&index_type, &Type::Number, index.span.clone(), false);
+        element(&iterable_type)
+      }
+      ExpressionKind::Slice {
+        iterable,
+        start,
+        end,
+      } => {
+        let iterable_type = self.expression(iterable);
+        self.expect(
+          &iterable_type,
+          &iterable_types(),
+          iterable.span.clone(),
+          false,
+        );
+        for bound in [start.as_ref(), end.as_ref()].into_iter().flatten() {
+          let bound_type = self.expression(bound);
+          self.expect(&bound_type, &Type::Number, bound.span.clone(), false);
+        }
+        union(
+          alternatives(&iterable_type)
+            .into_iter()
+            .filter(|alternative| compatible(alternative, &iterable_types())),
+        )
+      }
+      ExpressionKind::FunctionCall { name, arguments } => {
+        let callee = self.reference_type(name);
+        self.expect(&callee, &callable_types(), name.1.clone(), false);
+
+        let signature = self.signature(name);
+        let found = arguments
+          .iter()
+          .map(|argument| self.expression(argument))
+          .collect::<Vec<_>>();
+
+        match signature {
+          Some(signature) => {
+            for ((argument, found), expected) in
+              arguments.iter().zip(&found).zip(&signature.parameters)
+            {
+              self.expect(found, expected, argument.span.clone(), signature.annotated);
+            }
+            signature.returns
+          }
+          None => Type::Any,
+        }
+      }
+      ExpressionKind::If {
+        condition,
+        consequence,
+        alternative,
+      } => {
+        let condition_type = self.expression(condition);
+        self.expect(
+          &condition_type,
+          &Type::Boolean,
+          condition.span.clone(),
+          false,
+        );
+
+        let consequence = self.statement(consequence);
+        let alternative = match alternative.as_ref() {
+          Some(alternative) => self.statement(alternative),
+          None => Type::Void,
+        };
+        union([consequence, alternative])
+      }
+      ExpressionKind::For {
+        variable,
+        iterable,
+        body,
+      } => {
+        let iterable_type = self.expression(iterable);
+        let expected = union([iterable_types(), Type::Generator]);
+        self.expect(&iterable_type, &expected, iterable.span.clone(), false);
+        if let Some(declaration) = self.declarations.get(&variable.1.start).copied() {
+          self.types.insert(declaration, element(&iterable_type));
+        }
+
+        Type::Array(Box::new(self.statement(body)))
+      }
+      ExpressionKind::Yield(value) => {
+        self.expression(value);
+        Type::Void
+      }
+      ExpressionKind::While { condition, body } => {
+        let condition_type = self.expression(condition);
+        self.expect(
+          &condition_type,
+          &Type::Boolean,
+          condition.span.clone(),
+          false,
+        );
+
+        Type::Array(Box::new(self.statement(body)))
+      }
+      ExpressionKind::Binary {
+        operator,
+        left,
+        right,
+      } => {
+        let left = self.expression(left);
+        let right = self.expression(right);
+
+        let mut results = Vec::new();
+        for left in alternatives(&left) {
+          for right in alternatives(&right) {
+            results.extend(binary(operator, &left, &right));
+          }
+        }
+
+        // Whatever the operands turn out to be, the result is one of these
+        if results.is_empty() {
+          self.error(
+            TypeErrorKind::InvalidOperator(
+              operator.to_string(),
+              left.to_string(),
+              right.to_string(),
+            ),
+            expression.span.clone(),
+            false,
+          );
+          Type::Any
+        } else {
+          union(results)
+        }
+      }
+      ExpressionKind::Unary { operator, operand } => {
+        let operand = self.expression(operand);
+
+        let results = alternatives(&operand)
+          .iter()
+          .filter_map(|operand| unary(operator, operand))
+          .collect::<Vec<_>>();
+
+        if results.is_empty() {
+          self.error(
+            TypeErrorKind::InvalidOperator(
+              operator.to_string(),
+              operand.to_string(),
+              String::new(),
+            ),
+            expression.span.clone(),
+            false,
+          );
+          Type::Any
+        } else {
+          union(results)
+        }
+      }
+    }
+  }
+}
+
+// Whether a value of type `found` could also be of type `expected`
+fn compatible(found: &Type, expected: &Type) -> bool {
+  match (found, expected) {
+    (Type::Any, _) | (_, Type::Any) => true,
+    (Type::Union(types), _) => types.iter().any(|found| compatible(found, expected)),
+    (_, Type::Union(types)) => types.iter().any(|expected| compatible(found, expected)),
+    (Type::Array(found), Type::Array(expected)) => compatible(found, expected),
+    // A range can be used wherever an array of numbers can
+    (Type::Range, Type::Array(expected)) => compatible(&Type::Number, expected),
+    _ => found == expected,
+  }
+}
+
+// Every type a value of type `t` could have
+fn alternatives(t: &Type) -> Vec<Type> {
+  match t {
+    Type::Any => vec![
+      Type::Void,
+      Type::Number,
+      Type::String,
+      Type::Boolean,
+      Type::Regex,
+      Type::Function,
+      Type::Generator,
+      Type::Range,
+      Type::Array(Box::new(Type::Any)),
+    ],
+    Type::Union(types) => types.iter().flat_map(alternatives).collect(),
+    _ => vec![t.clone()],
+  }
+}
+
+fn union(types: impl IntoIterator<Item = Type>) -> Type {
+  let mut alternatives = Vec::new();
+  for t in types {
+    let flattened = match t {
+      Type::Any => return Type::Any,
+      Type::Union(types) => types,
+      t => vec![t],
+    };
+    for t in flattened {
+      if !alternatives.contains(&t) {
+        alternatives.push(t);
+      }
+    }
+  }
+
+  match alternatives.len() {
+    0 => Type::Any,
+    1 => alternatives.remove(0),
+    _ => Type::Union(alternatives),
+  }
+}
+
+fn iterable_types() -> Type {
+  Type::Union(vec![Type::Array(Box::new(Type::Any)), Type::String])
+}
+
+// Calling a generator resumes it
+fn callable_types() -> Type {
+  Type::Union(vec![Type::Function, Type::Generator])
+}
+
+// The type of the elements of an array, string or range of type `t`, or of
+// what a generator of type `t` yields
+fn element(t: &Type) -> Type {
+  union(alternatives(t).into_iter().filter_map(|t| match t {
+    Type::Array(element) => Some(*element),
+    Type::String => Some(Type::String),
+    Type::Range => Some(Type::Number),
+    Type::Generator => Some(Type::Any),
+    _ => None,
+  }))
+}
+
+pub fn type_of(value: &Value) -> Type {
+  match value {
+    Value::Void => Type::Void,
+    Value::Number(_) => Type::Number,
+    Value::String(_) => Type::String,
+    Value::Boolean(_) => Type::Boolean,
+    Value::Regex(_) => Type::Regex,
+    Value::Function { .. } | Value::RustFunction { .. } => Type::Function,
+    Value::Generator(_) => Type::Generator,
+    Value::Range(_) => Type::Range,
+    Value::Array(array) if array.is_empty() => Type::Array(Box::new(Type::Any)),
+    Value::Array(array) => Type::Array(Box::new(union(array.iter().map(type_of)))),
+  }
+}
+
+// Mirrors what the evaluator accepts for each operator
+fn binary(operator: &BinaryOperator, left: &Type, right: &Type) -> Option<Type> {
+  match (operator, left, right) {
+    (BinaryOperator::Equal | BinaryOperator::NotEqual, _, _) => Some(Type::Boolean),
+    (BinaryOperator::And | BinaryOperator::Or, Type::Boolean, Type::Boolean) => Some(Type::Boolean),
+    (
+      BinaryOperator::Add
+      | BinaryOperator::Subtract
+      | BinaryOperator::Multiply
+      | BinaryOperator::Divide
+      | BinaryOperator::Modulo,
+      Type::Number,
+      Type::Number,
+    ) => Some(Type::Number),
+    (
+      BinaryOperator::LessThan
+      | BinaryOperator::LessThanOrEqual
+      | BinaryOperator::GreaterThan
+      | BinaryOperator::GreaterThanOrEqual,
+      Type::Number,
+      Type::Number,
+    )
+    | (
+      BinaryOperator::LessThan
+      | BinaryOperator::LessThanOrEqual
+      | BinaryOperator::GreaterThan
+      | BinaryOperator::GreaterThanOrEqual,
+      Type::String,
+      Type::String,
+    ) => Some(Type::Boolean),
+    (BinaryOperator::Range, Type::Number, Type::Number) => Some(Type::Range),
+    (BinaryOperator::Add, Type::String, _) | (BinaryOperator::Add, _, Type::String) => {
+      Some(Type::String)
+    }
+    _ => None,
+  }
+}
+
+fn unary(operator: &UnaryOperator, operand: &Type) -> Option<Type> {
+  match (operator, operand) {
+    (UnaryOperator::Negate, Type::Number) => Some(Type::Number),
+    (UnaryOperator::Not, Type::Boolean) => Some(Type::Boolean),
+    _ => None,
+  }
+}
+
+// The types of the builtins in `scope::builtins`, in the same order
+fn builtin_signature(name: &str) -> Option<Signature> {
+  let array = || Type::Array(Box::new(Type::Any));
+  let pattern = || Type::Union(vec![Type::String, Type::Regex]);
+
+  let (parameters, returns) = match name {
+    "println" | "print" => (vec![Type::Any], Type::Void),
+    // Never returns
+    "exit" => (vec![Type::Number], Type::Any),
+    "readln" => (vec![], Type::String),
+    "args" => (vec![], Type::Array(Box::new(Type::String))),
+    "assert" => (vec![Type::Boolean], Type::Void),
+    "assert_eq" => (vec![Type::Any, Type::Any], Type::Void),
+    "assert_throws" => (vec![Type::Function], Type::Void),
+    "parse" => (
+      vec![Type::String],
+      Type::Union(vec![Type::Number, Type::Void]),
+    ),
+    "type" => (vec![Type::Any], Type::String),
+    "len" => (vec![Type::Union(vec![Type::String, array()])], Type::Number),
+    "filter" => (vec![array(), Type::String], array()),
+    "concat" => (vec![array(), array()], array()),
+    "range" => (vec![Type::Number, Type::Number, Type::Number], Type::Range),
+    "array" => (
+      vec![Type::Union(vec![Type::String, array(), Type::Generator])],
+      array(),
+    ),
+    "random" => (vec![], Type::Number),
+    "random_int" => (vec![Type::Number, Type::Number], Type::Number),
+    "choice" => (vec![array()], Type::Any),
+    "shuffle" => (vec![array()], array()),
+    "seed" => (vec![Type::Number], Type::Void),
+    "now" | "monotonic" => (vec![], Type::Number),
+    "sleep" => (vec![Type::Number], Type::Void),
+    "format_time" => (vec![Type::Number, Type::String], Type::String),
+    "regex" => (vec![pattern()], Type::Regex),
+    "regex_match" => (
+      vec![pattern(), Type::String],
+      Type::Union(vec![array(), Type::Void]),
+    ),
+    "regex_find_all" => (vec![pattern(), Type::String], array()),
+    "regex_replace" => (vec![pattern(), Type::String, Type::String], Type::String),
+    "regex_split" => (
+      vec![pattern(), Type::String],
+      Type::Array(Box::new(Type::String)),
+    ),
+    "spawn" => (vec![Type::Function], Type::Generator),
+    "resume" => (vec![Type::Generator], Type::Any),
+    "status" => (vec![Type::Generator], Type::String),
+    _ => return None,
+  };
+
+  Some(Signature {
+    parameters,
+    returns,
+    annotated: false,
+  })
+}
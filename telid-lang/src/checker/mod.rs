@@ -0,0 +1,7 @@
+#[allow(clippy::module_inception)]
+mod checker;
+
+#[cfg(test)]
+mod tests;
+
+pub use checker::*;
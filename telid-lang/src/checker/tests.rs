@@ -0,0 +1,159 @@
+use crate::{
+  checker::check,
+  error::{TypeError, TypeErrorKind},
+  evaluator::scope,
+  resolver::resolve,
+  test_util::{examples, parse},
+};
+
+fn check_errors(source: &str) -> Vec<TypeError> {
+  let ast = parse(source);
+  let scope = scope::default();
+  let resolution = resolve(&ast, &scope);
+  assert!(resolution.errors.is_empty());
+
+  check(&ast, &resolution, &scope)
+}
+
+fn check_source(source: &str) -> Vec<TypeErrorKind> {
+  check_errors(source)
+    .into_iter()
+    .map(|error| error.kind)
+    .collect()
+}
+
+fn invalid_type(found: &str, expected: &str) -> TypeErrorKind {
+  TypeErrorKind::InvalidType(found.to_string(), expected.to_string())
+}
+
+#[test]
+fn test_annotations() {
+  assert_eq!(
+    check_source(
+      "
+      let x: Number = 'a';
+      let fn add (a: Number) (b: Number) -> Number = + a b;
+      add(1, '2');
+      let fn name -> String = 1;
+      let y: [String] = ['a', 'b'];
+      let z: Number | Void = parse('1');
+      z = true;
+      ",
+    ),
+    vec![
+      invalid_type("String", "Number"),
+      invalid_type("String", "Number"),
+      invalid_type("Number", "String"),
+      invalid_type("Boolean", "Number | Void"),
+    ]
+  );
+}
+
+#[test]
+fn test_inference() {
+  assert_eq!(
+    check_source(
+      "
+      let x = 1;
+      let fn greet who = + 'Hi ' who;
+      - greet('you') x;
+      if x println(len(5));
+      let parsed = parse('1');
+      + parsed 1;
+      let fn f a = - a 1;
+      f('any');
+      for c in 'abc' !c;
+      ",
+    ),
+    vec![
+      TypeErrorKind::InvalidOperator(
+        String::from("-"),
+        String::from("String"),
+        String::from("Number")
+      ),
+      invalid_type("Number", "Boolean"),
+      invalid_type("Number", "String | Array"),
+      TypeErrorKind::InvalidOperator(String::from("!"), String::from("String"), String::new()),
+    ]
+  );
+
+  // Reassigned variables could hold anything
+  assert_eq!(check_source("let x = 1; x = 'a'; - x 1"), vec![]);
+}
+
+#[test]
+fn test_annotated() {
+  // Only mismatches with an annotation stop a program from running
+  let annotated = check_errors(
+    "
+    let x: Number = 'a';
+    let fn half (n: Number) -> Number = / n 2;
+    half('4');
+    let fn name -> String = 1;
+    x = true;
+    if false println(+ 1 true);
+    len(5);
+    if 1 2;
+    ",
+  )
+  .into_iter()
+  .map(|error| error.annotated)
+  .collect::<Vec<_>>();
+  assert_eq!(annotated, [true, true, true, true, false, false, false]);
+}
+
+#[test]
+fn test_generators() {
+  assert_eq!(
+    check_source(
+      "
+      let fn count n -> [Void] = for i in .. 1 n yield i;
+      let g = count(3);
+      let x: Number = g;
+      for i in g i;
+      g();
+      let y = 1;
+      y();
+      spawn(count);
+      status(count);
+      ",
+    ),
+    vec![
+      invalid_type("Generator", "Number"),
+      invalid_type("Number", "Function | Generator"),
+      invalid_type("Function", "Generator"),
+    ]
+  );
+}
+
+#[test]
+fn test_ranges() {
+  assert_eq!(
+    check_source(
+      "
+      let r = .. 1 10;
+      let numbers: [Number] = r;
+      let strings: [String] = r;
+      let stepped: Range = range(0, 10, 2);
+      for i in r - i 1;
+      + [0]r [1..2]r;
+      len(array(r));
+      ",
+    ),
+    vec![
+      invalid_type("Range", "[String]"),
+      TypeErrorKind::InvalidOperator(
+        String::from("+"),
+        String::from("Number"),
+        String::from("Range")
+      ),
+    ]
+  );
+}
+
+#[test]
+fn test_examples() {
+  examples(|path, source| {
+    assert_eq!(check_source(source), vec![], "{}", path.display());
+  });
+}
@@ -0,0 +1,212 @@
+use crate::{
+  error::parse_error_message,
+  evaluator::{
+    context::Context,
+    evaluate_statement,
+    hook::Hook,
+    scope::Scope,
+    value::{Value, Variable},
+  },
+  lexer::{tokens::TokenKind, Lexer},
+  parser::{
+    ast::{Statement, StatementKind},
+    parser,
+  },
+  position::{position_of, positions, Position},
+};
+use chumsky::Parser;
+use std::collections::BTreeSet;
+
+// How a paused program should carry on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+  // Until the next breakpoint
+  Continue,
+  // To the next statement, even inside a function it calls
+  Into,
+  // To the next statement that isn't inside a function it calls
+  Over,
+  // To the next statement after the current function returns
+  Out,
+}
+
+// Why a program paused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+  // Before the first statement runs
+  Entry,
+  Breakpoint,
+  Step,
+  // The frontend asked for it while the program was running
+  Pause,
+}
+
+// Whoever is doing the debugging. Gets called every time the program pauses,
+// and decides when it runs again
+pub trait Frontend: std::fmt::Debug {
+  fn paused(&mut self, paused: &mut Paused) -> Step;
+
+  // Called before every statement, so that the frontend can keep up with its
+  // user while the program runs. The program pauses before the statement if
+  // this returns true
+  fn running(&mut self, _breakpoints: &mut BTreeSet<usize>) -> bool {
+    false
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+  Entry,
+  Continue,
+  Into,
+  // With the call depth the step started at
+  Over(usize),
+  Out(usize),
+}
+
+// A hook that pauses a program at breakpoints and after steps, and hands it
+// to a `Frontend` while it's paused. Lines are counted from 0
+#[derive(Debug)]
+pub struct Debugger<F> {
+  frontend: F,
+  positions: Vec<Position>,
+  breakpoints: BTreeSet<usize>,
+  mode: Mode,
+  // Line and call depth of the last statement that ran
+  last: Option<(usize, usize)>,
+}
+
+impl<F: Frontend> Debugger<F> {
+  // Debugs a program parsed from `source`. It pauses before the first
+  // statement so that breakpoints can be set
+  pub fn new(source: &str, frontend: F) -> Self {
+    Debugger {
+      frontend,
+      positions: positions(source),
+      breakpoints: BTreeSet::new(),
+      mode: Mode::Entry,
+      last: None,
+    }
+  }
+
+  fn reason(&self, line: usize, depth: usize) -> Option<Reason> {
+    let step = match self.mode {
+      Mode::Entry => return Some(Reason::Entry),
+      Mode::Continue => false,
+      Mode::Into => true,
+      Mode::Over(from) => depth <= from,
+      Mode::Out(from) => depth < from,
+    };
+
+    if step {
+      Some(Reason::Step)
+    } else if self.breakpoints.contains(&line) && self.last != Some((line, depth)) {
+      // Only the first statement on a line stops at its breakpoint
+      Some(Reason::Breakpoint)
+    } else {
+      None
+    }
+  }
+}
+
+impl<F: Frontend> Hook for Debugger<F> {
+  fn before_statement(&mut self, statement: &Statement, scope: &mut Scope, context: &mut Context) {
+    let position = position_of(&self.positions, statement.span.start);
+    let line = position.line;
+    let depth = context.call_stack.len();
+
+    // Blocks only pause at the statements in them, but entering one counts as
+    // leaving the line before, so loops stop at their breakpoints every time.
+    // Empty loops have nothing else to pause at when asked to. Nothing can
+    // interrupt the pause at the entry, which the frontend sets up from
+    let interrupted = self.mode != Mode::Entry && self.frontend.running(&mut self.breakpoints);
+    if matches!(statement.kind, StatementKind::Block(_)) && !interrupted {
+      self.last = Some((line, depth));
+      return;
+    }
+
+    let reason = match interrupted {
+      true => Some(Reason::Pause),
+      false => self.reason(line, depth),
+    };
+    self.last = Some((line, depth));
+    let Some(reason) = reason else {
+      return;
+    };
+
+    let mut paused = Paused {
+      reason,
+      position,
+      breakpoints: &mut self.breakpoints,
+      positions: &self.positions,
+      scope,
+      context,
+    };
+    self.mode = match self.frontend.paused(&mut paused) {
+      Step::Continue => Mode::Continue,
+      Step::Into => Mode::Into,
+      Step::Over => Mode::Over(depth),
+      Step::Out => Mode::Out(depth),
+    };
+  }
+}
+
+// A program stopped before the statement at `position`. Breakpoints are by
+// line
+#[derive(Debug)]
+pub struct Paused<'a> {
+  pub reason: Reason,
+  pub position: Position,
+  pub breakpoints: &'a mut BTreeSet<usize>,
+  positions: &'a [Position],
+  scope: &'a mut Scope,
+  context: &'a mut Context,
+}
+
+impl Paused<'_> {
+  // The variables in each frame of the scope, innermost first. Functions run
+  // in their caller's scope, so this includes the caller's frames too
+  pub fn frames(&self) -> impl Iterator<Item = &[(String, Variable)]> {
+    self.scope.frames()
+  }
+
+  // The functions being called, innermost first, with where each one was
+  // called from
+  pub fn call_stack(&self) -> Vec<(String, Position)> {
+    self
+      .context
+      .call_stack
+      .iter()
+      .rev()
+      .map(|frame| {
+        (
+          frame.name.clone(),
+          position_of(self.positions, frame.span.start),
+        )
+      })
+      .collect()
+  }
+
+  // Runs `source` as if it came right before the paused statement, and
+  // returns the value of its last statement
+  pub fn evaluate(&mut self, source: &str) -> Result<Value, String> {
+    let mut tokens = Vec::new();
+    for (token, _) in Lexer::new(source).lex(false) {
+      match token {
+        TokenKind::Error(error) => return Err(error.to_string()),
+        token => tokens.push(token),
+      }
+    }
+
+    let program = parser()
+      .parse(tokens)
+      .map_err(|errors| parse_error_message(&errors[0]))?;
+
+    let mut value = Value::Void;
+    for statement in program {
+      value = evaluate_statement(statement, self.scope, self.context)
+        .map_err(|error| error.kind.to_string())?;
+    }
+    Ok(value)
+  }
+}
@@ -0,0 +1,7 @@
+#[allow(clippy::module_inception)]
+mod debugger;
+
+#[cfg(test)]
+mod tests;
+
+pub use debugger::*;
@@ -0,0 +1,196 @@
+use crate::{
+  debugger::{Debugger, Frontend, Paused, Reason, Step},
+  evaluator::{context::Context, evaluate_with_context, scope, value::Value},
+  position::{positions, Position},
+  test_util::parse,
+};
+use std::{cell::RefCell, collections::BTreeSet, fmt, rc::Rc};
+
+struct Script<F>(F);
+
+impl<F> fmt::Debug for Script<F> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Script")
+  }
+}
+
+impl<F: FnMut(&mut Paused) -> Step> Frontend for Script<F> {
+  fn paused(&mut self, paused: &mut Paused) -> Step {
+    (self.0)(paused)
+  }
+}
+
+// Runs `source` under the debugger, letting `script` decide what to do at
+// each pause, and returns why and where it paused each time
+fn debug(
+  source: &str,
+  mut script: impl FnMut(&mut Paused) -> Step + 'static,
+) -> Vec<(Reason, usize)> {
+  let pauses = Rc::new(RefCell::new(Vec::new()));
+  let log = pauses.clone();
+  run(
+    source,
+    Script(move |paused: &mut Paused| {
+      log.borrow_mut().push((paused.reason, paused.position.line));
+      script(paused)
+    }),
+  );
+
+  pauses.take()
+}
+
+fn run(source: &str, frontend: impl Frontend + 'static) {
+  let mut context = Context::new();
+  context.hook = Some(Box::new(Debugger::new(source, frontend)));
+  evaluate_with_context(parse(source), scope::default(), &mut context).unwrap();
+}
+
+// Asks for a pause once, after a number of statements, and ends the loop in
+// `test_pause` when it gets it
+#[derive(Debug)]
+struct Interrupt {
+  statements: usize,
+  pauses: Rc<RefCell<Vec<(Reason, usize)>>>,
+}
+
+impl Frontend for Interrupt {
+  fn paused(&mut self, paused: &mut Paused) -> Step {
+    self
+      .pauses
+      .borrow_mut()
+      .push((paused.reason, paused.position.line));
+    if paused.reason == Reason::Pause {
+      paused.evaluate("done = true").unwrap();
+    }
+    Step::Continue
+  }
+
+  fn running(&mut self, _: &mut BTreeSet<usize>) -> bool {
+    self.statements = self.statements.wrapping_sub(1);
+    self.statements == 0
+  }
+}
+
+const SOURCE: &str = "let fn double x =
+  * x 2;
+let a = 1;
+let b = double(a);
+let c = + a b;
+";
+
+#[test]
+fn test_steps() {
+  let mut steps = vec![
+    Step::Into,
+    Step::Into,
+    Step::Into,
+    Step::Out,
+    Step::Continue,
+  ]
+  .into_iter();
+  let pauses = debug(SOURCE, move |paused| {
+    if paused.position.line == 1 {
+      assert_eq!(
+        paused.call_stack(),
+        vec![(String::from("double"), Position { line: 3, column: 8 })]
+      );
+      assert_eq!(paused.evaluate("x"), Ok(Value::Number(1.0)));
+    }
+    steps.next().unwrap()
+  });
+
+  assert_eq!(
+    pauses,
+    vec![
+      (Reason::Entry, 0),
+      (Reason::Step, 2),
+      (Reason::Step, 3),
+      (Reason::Step, 1),
+      (Reason::Step, 4),
+    ]
+  );
+
+  // Stepping over the call never stops inside it
+  let mut steps = vec![Step::Over; 5].into_iter();
+  let pauses = debug(SOURCE, move |_| steps.next().unwrap());
+  assert_eq!(
+    pauses.iter().map(|pause| pause.1).collect::<Vec<_>>(),
+    vec![0, 2, 3, 4]
+  );
+}
+
+#[test]
+fn test_breakpoints() {
+  let pauses = debug(SOURCE, |paused| {
+    if paused.reason == Reason::Entry {
+      paused.breakpoints.insert(1);
+    } else {
+      let frames = paused.frames().collect::<Vec<_>>();
+      assert_eq!(frames[0][0].0, "x");
+      assert!(frames[1].iter().any(|(name, _)| name == "a"));
+    }
+    Step::Continue
+  });
+  assert_eq!(pauses, vec![(Reason::Entry, 0), (Reason::Breakpoint, 1)]);
+
+  // Every iteration stops at a breakpoint in a loop's body
+  let source = "let i = 0;
+while < i 3 {
+  i = + i 1;
+}
+";
+  let pauses = debug(source, |paused| {
+    paused.breakpoints.insert(2);
+    Step::Continue
+  });
+  assert_eq!(pauses.len(), 4);
+}
+
+#[test]
+fn test_pause() {
+  // An empty loop pauses at its body
+  let pauses = Rc::new(RefCell::new(Vec::new()));
+  run(
+    "let done = false;\nwhile !done {}\n",
+    Interrupt {
+      statements: 10,
+      pauses: pauses.clone(),
+    },
+  );
+  assert_eq!(pauses.take(), vec![(Reason::Entry, 0), (Reason::Pause, 1)]);
+}
+
+#[test]
+fn test_evaluate() {
+  let pauses = debug(SOURCE, |paused| {
+    if paused.position.line == 4 {
+      assert_eq!(paused.evaluate("+ a b"), Ok(Value::Number(3.0)));
+      assert_eq!(
+        paused.evaluate("z"),
+        Err(String::from("UndefinedVariable: z"))
+      );
+      assert!(paused.evaluate("let = ").is_err());
+    }
+    Step::Over
+  });
+  assert_eq!(pauses.last(), Some(&(Reason::Step, 4)));
+}
+
+#[test]
+fn test_positions() {
+  // The bad character is left out, like it is for the parser, and Eof starts
+  // where the last token does
+  let positions = positions("let x = 1;\n  ~ y");
+  assert_eq!(
+    positions,
+    vec![
+      Position { line: 0, column: 0 },
+      Position { line: 0, column: 4 },
+      Position { line: 0, column: 6 },
+      Position { line: 0, column: 8 },
+      Position { line: 0, column: 9 },
+      Position { line: 1, column: 4 },
+      Position { line: 1, column: 4 },
+    ]
+  );
+}
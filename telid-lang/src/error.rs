@@ -1,5 +1,6 @@
-use ariadne::{Label, Report, ReportKind};
-use std::ops::Range;
+use ariadne::{Color, Label, Report, ReportKind};
+use chumsky::{error::SimpleReason, prelude::Simple};
+use std::{fmt, ops::Range};
 use strum_macros::AsRefStr;
 
 use crate::lexer::tokens::TokenKind;
@@ -11,31 +12,107 @@ pub enum LexError {
 }
 
 impl LexError {
+  // Stable codes, which `telid --explain` describes
+  pub fn code(&self) -> &'static str {
+    match self {
+      LexError::UnexpectedCharacter(_) => "T0001",
+      LexError::UnterminatedStringLiteral => "T0002",
+    }
+  }
+
   pub fn report<'a>(
-    &'a self,
+    &self,
     src: &'a str,
     span: Range<usize>,
-  ) -> Report<'a, (&str, std::ops::Range<usize>)> {
+  ) -> Report<'a, (&'a str, std::ops::Range<usize>)> {
     Report::build(ReportKind::Error, src, span.start)
+      .with_code(self.code())
       .with_message(*self)
       .with_label(Label::new((src, span)))
       .finish()
   }
 }
 
-impl ToString for LexError {
-  fn to_string(&self) -> String {
+impl fmt::Display for LexError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
-      LexError::UnexpectedCharacter(c) => format!("Unexpected character: {}", c),
-      LexError::UnterminatedStringLiteral => "Unterminated string literal".to_string(),
+      LexError::UnexpectedCharacter(c) => write!(f, "Unexpected character: {}", c),
+      LexError::UnterminatedStringLiteral => write!(f, "Unterminated string literal"),
+    }
+  }
+}
+
+// What the parser produces. Its spans are in tokens, like the AST's
+pub type ParseError = Simple<TokenKind>;
+
+fn token_name(token: Option<&TokenKind>) -> &str {
+  match token {
+    Some(token) => token.as_ref(),
+    None => "None",
+  }
+}
+
+pub fn parse_error_code(error: &ParseError) -> &'static str {
+  match error.reason() {
+    SimpleReason::Unexpected | SimpleReason::Custom(_) => "T0003",
+    SimpleReason::Unclosed { .. } => "T0004",
+  }
+}
+
+pub fn parse_error_message(error: &ParseError) -> String {
+  match error.reason() {
+    SimpleReason::Unexpected => {
+      format!(
+        "Unexpected token: {:?}, expected one of: {:?}",
+        token_name(error.found()),
+        error
+          .expected()
+          .map(|t| token_name(t.as_ref()))
+          .collect::<Vec<_>>()
+      )
     }
+    SimpleReason::Unclosed { delimiter, .. } => format!(
+      "Unclosed delimiter {:?}, found {:?}",
+      delimiter.as_ref(),
+      token_name(error.found())
+    ),
+    SimpleReason::Custom(message) => message.clone(),
   }
 }
 
+pub fn parse_error_report<'a>(
+  error: &ParseError,
+  src: &'a str,
+  tokens: &[(TokenKind, Range<usize>)],
+) -> Report<'a, (&'a str, Range<usize>)> {
+  let span = source_span(tokens, error.span());
+  let mut report = Report::build(ReportKind::Error, src, span.start)
+    .with_code(parse_error_code(error))
+    .with_message(parse_error_message(error))
+    .with_label(Label::new((src, span)));
+
+  if let SimpleReason::Unclosed { span, .. } = error.reason() {
+    report = report
+      .with_label(Label::new((src, source_span(tokens, span.clone()))).with_message("Opened here"));
+  }
+
+  report.finish()
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct EvaluationError {
   pub kind: EvaluationErrorKind,
   pub span: Range<usize>,
+  // Calls that were active when the error happened, innermost first
+  pub stack: Vec<StackFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StackFrame {
+  // Name of the function being called
+  pub name: String,
+  // Span of the call expression, in tokens
+  pub span: Range<usize>,
 }
 
 #[derive(Debug, Clone, PartialEq, AsRefStr)]
@@ -47,7 +124,43 @@ pub enum EvaluationErrorKind {
   IncorrectParameterCount(usize, usize),
   ConstantReassignment(String),
   InvalidRange(f64, f64),
+  InvalidRegex(String),
   AssertionFailed,
+  // Both values, as they would be written in Telid
+  AssertionNotEqual(String, String),
+  // What the function returned instead
+  ExpectedError(String),
+  // The limits from `Limits` that were crossed
+  OutOfFuel(u64),
+  // In seconds
+  TimeLimitExceeded(f64),
+  CallDepthExceeded(usize),
+  // Size found, the most allowed
+  ValueTooLarge(usize, usize),
+  // In bytes
+  MemoryLimitExceeded(usize),
+  // Name of the builtin
+  CapabilityDenied(String),
+  YieldOutsideGenerator,
+  // What the generator is doing instead, `running` or `done`
+  GeneratorNotSuspended(String),
+  // A step that's 0, or that goes away from the end of its range
+  InvalidStep(f64),
+  // In milliseconds
+  InvalidDuration(f64),
+  // How many had started and not finished
+  TooManyGenerators(usize),
+}
+
+// Deep recursion would otherwise bury the error under thousands of labels
+const MAX_REPORTED_FRAMES: usize = 8;
+
+// Converts a span in token indices to one in characters. Empty spans and
+// spans past the end (errors at `Eof`) cover the nearest token
+pub fn source_span(tokens: &[(TokenKind, Range<usize>)], span: Range<usize>) -> Range<usize> {
+  let start = span.start.min(tokens.len() - 1);
+  let end = span.end.clamp(start + 1, tokens.len());
+  tokens[start].1.start..tokens[end - 1].1.end
 }
 
 impl EvaluationError {
@@ -56,20 +169,72 @@ impl EvaluationError {
     src: &'a str,
     span: Range<usize>,
     tokens: Vec<(TokenKind, Range<usize>)>,
-  ) -> Report<'a, (&str, std::ops::Range<usize>)> {
-    let span = tokens[span.start].1.start..tokens[span.end - 1].1.end;
-    Report::build(ReportKind::Error, src, span.start)
+  ) -> Report<'a, (&'a str, std::ops::Range<usize>)> {
+    let span = source_span(&tokens, span);
+    let mut report = Report::build(ReportKind::Error, src, span.start)
+      .with_code(self.kind.code())
       .with_message(self.kind.to_string())
-      .with_label(Label::new((src, span)))
-      .finish()
+      .with_label(Label::new((src, span)).with_order(-1));
+
+    for (index, frame) in self.stack.iter().take(MAX_REPORTED_FRAMES).enumerate() {
+      report = report.with_label(
+        Label::new((src, source_span(&tokens, frame.span.clone())))
+          .with_message(format!("#{} in call to {}", index, frame.name))
+          .with_color(Color::Fixed(246))
+          .with_order(index as i32),
+      );
+    }
+
+    if self.stack.len() > MAX_REPORTED_FRAMES {
+      report = report.with_note(format!(
+        "{} more calls not shown",
+        self.stack.len() - MAX_REPORTED_FRAMES
+      ));
+    }
+
+    report.finish()
+  }
+}
+
+impl EvaluationErrorKind {
+  // Shared with the resolver's and type checker's errors for the same
+  // mistakes
+  pub fn code(&self) -> &'static str {
+    match self {
+      EvaluationErrorKind::UndefinedVariable(_) => "T0005",
+      EvaluationErrorKind::ConstantReassignment(_) => "T0007",
+      EvaluationErrorKind::InvalidType(..) => "T0008",
+      EvaluationErrorKind::InvalidOperator(..) => "T0009",
+      EvaluationErrorKind::IndexOutOfBounds(..) => "T0010",
+      EvaluationErrorKind::IncorrectParameterCount(..) => "T0011",
+      EvaluationErrorKind::InvalidRange(..) => "T0012",
+      EvaluationErrorKind::InvalidRegex(_) => "T0013",
+      EvaluationErrorKind::AssertionFailed => "T0014",
+      EvaluationErrorKind::AssertionNotEqual(..) => "T0015",
+      EvaluationErrorKind::ExpectedError(_) => "T0016",
+      EvaluationErrorKind::OutOfFuel(_) => "T0017",
+      EvaluationErrorKind::TimeLimitExceeded(_) => "T0018",
+      EvaluationErrorKind::CallDepthExceeded(_) => "T0019",
+      EvaluationErrorKind::ValueTooLarge(..) => "T0020",
+      EvaluationErrorKind::MemoryLimitExceeded(_) => "T0021",
+      EvaluationErrorKind::CapabilityDenied(_) => "T0022",
+      EvaluationErrorKind::YieldOutsideGenerator => "T0023",
+      EvaluationErrorKind::GeneratorNotSuspended(_) => "T0024",
+      EvaluationErrorKind::InvalidStep(_) => "T0025",
+      EvaluationErrorKind::InvalidDuration(_) => "T0026",
+      EvaluationErrorKind::TooManyGenerators(_) => "T0027",
+    }
   }
 }
 
-impl ToString for EvaluationErrorKind {
-  fn to_string(&self) -> String {
+impl fmt::Display for EvaluationErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
-      EvaluationErrorKind::AssertionFailed => self.as_ref().to_string(),
-      _ => format!(
+      EvaluationErrorKind::AssertionFailed | EvaluationErrorKind::YieldOutsideGenerator => {
+        write!(f, "{}", self.as_ref())
+      }
+      _ => write!(
+        f,
         "{}: {}",
         self.as_ref(),
         match self {
@@ -90,9 +255,247 @@ impl ToString for EvaluationErrorKind {
             format!("expected {}, found {}", expected, found),
           EvaluationErrorKind::ConstantReassignment(identifier) => identifier.to_string(),
           EvaluationErrorKind::InvalidRange(start, end) => format!("{}..{}", start, end),
+          EvaluationErrorKind::InvalidRegex(message) => message.to_string(),
+          EvaluationErrorKind::AssertionNotEqual(left, right) => {
+            // Points at the first character that differs
+            let common = left
+              .chars()
+              .zip(right.chars())
+              .take_while(|(a, b)| a == b)
+              .count();
+            format!(
+              "values differ\n  left:  {}\n  right: {}\n         {}^",
+              left,
+              right,
+              " ".repeat(common)
+            )
+          }
+          EvaluationErrorKind::ExpectedError(value) =>
+            format!("the function returned {} instead", value),
+          EvaluationErrorKind::OutOfFuel(fuel) => format!("ran more than {} statements", fuel),
+          EvaluationErrorKind::TimeLimitExceeded(seconds) =>
+            format!("ran for more than {}s", seconds),
+          EvaluationErrorKind::CallDepthExceeded(depth) =>
+            format!("more than {} calls deep", depth),
+          EvaluationErrorKind::ValueTooLarge(size, limit) =>
+            format!("{} is more than the limit of {}", size, limit),
+          EvaluationErrorKind::MemoryLimitExceeded(bytes) =>
+            format!("made more than {} bytes of strings and arrays", bytes),
+          EvaluationErrorKind::CapabilityDenied(name) => format!("{} isn't allowed here", name),
+          EvaluationErrorKind::GeneratorNotSuspended(status) =>
+            format!("the generator is {}", status),
+          EvaluationErrorKind::InvalidStep(step) =>
+            format!("a step of {} never reaches the end", step),
+          EvaluationErrorKind::InvalidDuration(milliseconds) =>
+            format!("can't sleep for {}ms", milliseconds),
+          EvaluationErrorKind::TooManyGenerators(count) => format!(
+            "couldn't make another stack with {} generators running",
+            count
+          ),
           _ => unreachable!(),
         }
       ),
     }
   }
 }
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolveError {
+  pub kind: ResolveErrorKind,
+  pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, AsRefStr)]
+pub enum ResolveErrorKind {
+  UndefinedVariable(String),
+  DuplicateParameter(String),
+  ConstantReassignment(String),
+  YieldOutsideGenerator,
+}
+
+impl ResolveError {
+  pub fn report<'a>(
+    &self,
+    src: &'a str,
+    tokens: &[(TokenKind, Range<usize>)],
+  ) -> Report<'a, (&'a str, std::ops::Range<usize>)> {
+    let span = source_span(tokens, self.span.clone());
+    Report::build(ReportKind::Error, src, span.start)
+      .with_code(self.kind.code())
+      .with_message(self.kind.to_string())
+      .with_label(Label::new((src, span)))
+      .finish()
+  }
+}
+
+impl ResolveErrorKind {
+  pub fn code(&self) -> &'static str {
+    match self {
+      ResolveErrorKind::UndefinedVariable(_) => "T0005",
+      ResolveErrorKind::DuplicateParameter(_) => "T0006",
+      ResolveErrorKind::ConstantReassignment(_) => "T0007",
+      ResolveErrorKind::YieldOutsideGenerator => "T0023",
+    }
+  }
+}
+
+impl fmt::Display for ResolveErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ResolveErrorKind::UndefinedVariable(identifier)
+      | ResolveErrorKind::DuplicateParameter(identifier)
+      | ResolveErrorKind::ConstantReassignment(identifier) => {
+        write!(f, "{}: {}", self.as_ref(), identifier)
+      }
+      ResolveErrorKind::YieldOutsideGenerator => write!(f, "{}", self.as_ref()),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeError {
+  pub kind: TypeErrorKind,
+  pub span: Range<usize>,
+  // Whether the type that wasn't met is an annotation's. Only those stop a
+  // program from running, the rest are warnings, since unannotated code that
+  // never runs can't fail
+  pub annotated: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, AsRefStr)]
+pub enum TypeErrorKind {
+  // Type found, type expected
+  InvalidType(String, String),
+  // Operator, then the types of its operands
+  InvalidOperator(String, String, String),
+}
+
+impl TypeError {
+  pub fn report<'a>(
+    &self,
+    src: &'a str,
+    tokens: &[(TokenKind, Range<usize>)],
+  ) -> Report<'a, (&'a str, std::ops::Range<usize>)> {
+    let span = source_span(tokens, self.span.clone());
+    let kind = match self.annotated {
+      true => ReportKind::Error,
+      false => ReportKind::Warning,
+    };
+    Report::build(kind, src, span.start)
+      .with_code(self.kind.code())
+      .with_message(self.kind.to_string())
+      .with_label(Label::new((src, span)))
+      .finish()
+  }
+}
+
+impl TypeErrorKind {
+  pub fn code(&self) -> &'static str {
+    match self {
+      TypeErrorKind::InvalidType(..) => "T0008",
+      TypeErrorKind::InvalidOperator(..) => "T0009",
+    }
+  }
+}
+
+impl fmt::Display for TypeErrorKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TypeErrorKind::InvalidType(found, expected) => {
+        write!(
+          f,
+          "{}: found {}, expected {}",
+          self.as_ref(),
+          found,
+          expected
+        )
+      }
+      TypeErrorKind::InvalidOperator(operator, left, right) => {
+        write!(
+          f,
+          "{}: {:?} {:?} {:?}",
+          self.as_ref(),
+          left,
+          operator,
+          right
+        )
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+  pub kind: WarningKind,
+  pub span: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, AsRefStr)]
+pub enum WarningKind {
+  // A call, index or operand on a new line that continues the expression
+  // before it instead of starting a new statement
+  AmbiguousContinuation,
+  UnusedVariable(String),
+  Shadowing(String),
+  UndefinedVariable(String),
+  UndeclaredAssignment(String),
+  DuplicateParameter(String),
+  ConstantReassignment(String),
+  UnreachableCode,
+  // Name of the function, arguments found, parameters expected
+  IncorrectParameterCount(String, usize, usize),
+}
+
+impl Warning {
+  pub fn report<'a>(
+    &self,
+    src: &'a str,
+    tokens: &[(TokenKind, Range<usize>)],
+  ) -> Report<'a, (&'a str, std::ops::Range<usize>)> {
+    let span = source_span(tokens, self.span.clone());
+    let mut report = Report::build(ReportKind::Warning, src, span.start)
+      .with_message(self.kind.to_string())
+      .with_label(Label::new((src, span)));
+
+    if self.kind == WarningKind::AmbiguousContinuation {
+      report =
+        report.with_help("Add a semicolon before the line break if a new statement starts there");
+    }
+
+    report
+      .with_note(format!(
+        "Add `// telid-ignore {}` on the line before to silence this",
+        self.kind.as_ref()
+      ))
+      .finish()
+  }
+}
+
+impl fmt::Display for WarningKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      WarningKind::AmbiguousContinuation => write!(
+        f,
+        "{}: this line continues the expression on the line before",
+        self.as_ref()
+      ),
+      WarningKind::UnreachableCode => write!(f, "{}: the program exits before this", self.as_ref()),
+      WarningKind::UnusedVariable(identifier)
+      | WarningKind::Shadowing(identifier)
+      | WarningKind::UndefinedVariable(identifier)
+      | WarningKind::UndeclaredAssignment(identifier)
+      | WarningKind::DuplicateParameter(identifier)
+      | WarningKind::ConstantReassignment(identifier) => {
+        write!(f, "{}: {}", self.as_ref(), identifier)
+      }
+      WarningKind::IncorrectParameterCount(name, found, expected) => write!(
+        f,
+        "{}: {} expects {}, found {}",
+        self.as_ref(),
+        name,
+        expected,
+        found
+      ),
+    }
+  }
+}
@@ -0,0 +1,135 @@
+use std::{
+  fmt::Debug,
+  thread,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// Where `now`, `monotonic` and `sleep` get their time from. Hosts that need
+// reproducible runs can swap in a `FixedClock` (or their own implementation)
+pub trait Clock: Debug {
+  // Seconds since the Unix epoch
+  fn now(&self) -> f64;
+  // Seconds since some arbitrary point, never goes backwards
+  fn monotonic(&self) -> f64;
+  fn sleep(&mut self, duration: Duration);
+}
+
+#[derive(Debug, Clone)]
+pub struct SystemClock {
+  start: Instant,
+}
+
+impl SystemClock {
+  pub fn new() -> Self {
+    SystemClock {
+      start: Instant::now(),
+    }
+  }
+}
+
+impl Default for SystemClock {
+  fn default() -> Self {
+    SystemClock::new()
+  }
+}
+
+impl Clock for SystemClock {
+  fn now(&self) -> f64 {
+    SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_secs_f64())
+      .unwrap_or(0.0)
+  }
+
+  fn monotonic(&self) -> f64 {
+    self.start.elapsed().as_secs_f64()
+  }
+
+  fn sleep(&mut self, duration: Duration) {
+    thread::sleep(duration);
+  }
+}
+
+// A clock that only moves when something sleeps on it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedClock {
+  pub now: f64,
+  pub elapsed: f64,
+}
+
+impl FixedClock {
+  pub fn new(now: f64) -> Self {
+    FixedClock { now, elapsed: 0.0 }
+  }
+}
+
+impl Clock for FixedClock {
+  fn now(&self) -> f64 {
+    self.now + self.elapsed
+  }
+
+  fn monotonic(&self) -> f64 {
+    self.elapsed
+  }
+
+  fn sleep(&mut self, duration: Duration) {
+    self.elapsed += duration.as_secs_f64();
+  }
+}
+
+// strftime-style formatting of a Unix timestamp in UTC. Supports %Y, %m, %d,
+// %H, %M, %S, %f (milliseconds), %s (epoch seconds) and %%; anything else is
+// copied through untouched
+pub fn format_time(timestamp: f64, format: &str) -> String {
+  let milliseconds = (timestamp * 1000.0).floor() as i64;
+  let seconds = milliseconds.div_euclid(1000);
+  let days = seconds.div_euclid(86400);
+  let time_of_day = seconds.rem_euclid(86400);
+  let (year, month, day) = civil_from_days(days);
+
+  let mut result = String::new();
+  let mut chars = format.chars();
+
+  while let Some(c) = chars.next() {
+    if c != '%' {
+      result.push(c);
+      continue;
+    }
+
+    match chars.next() {
+      Some('Y') => result.push_str(&format!("{:04}", year)),
+      Some('m') => result.push_str(&format!("{:02}", month)),
+      Some('d') => result.push_str(&format!("{:02}", day)),
+      Some('H') => result.push_str(&format!("{:02}", time_of_day / 3600)),
+      Some('M') => result.push_str(&format!("{:02}", time_of_day % 3600 / 60)),
+      Some('S') => result.push_str(&format!("{:02}", time_of_day % 60)),
+      Some('f') => result.push_str(&format!("{:03}", milliseconds.rem_euclid(1000))),
+      Some('s') => result.push_str(&seconds.to_string()),
+      Some('%') => result.push('%'),
+      Some(other) => {
+        result.push('%');
+        result.push(other);
+      }
+      None => result.push('%'),
+    }
+  }
+
+  result
+}
+
+// Days since 1970-01-01 to a (year, month, day) triple in the proleptic
+// Gregorian calendar (http://howardhinnant.github.io/date_algorithms.html)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+  let z = days + 719468;
+  let era = z.div_euclid(146097);
+  let day_of_era = z.rem_euclid(146097);
+  let year_of_era =
+    (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let mp = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  (year, month, day)
+}
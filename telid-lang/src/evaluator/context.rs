@@ -0,0 +1,93 @@
+use super::{
+  clock::{Clock, FixedClock, SystemClock},
+  coverage::Coverage,
+  hook::Hook,
+  io::{Io, StandardIo},
+  limits::{Limits, Usage},
+  pattern::Pattern,
+  profiler::Profiler,
+  random::Rng,
+};
+use crate::error::StackFrame;
+use std::collections::HashMap;
+
+// State that belongs to the interpreter rather than to any scope. Rust
+// functions get a mutable reference to it on every call
+#[derive(Debug)]
+pub struct Context {
+  pub rng: Rng,
+  pub clock: Box<dyn Clock>,
+  pub io: Box<dyn Io>,
+  // Regexes compiled from string patterns, keyed by the pattern
+  pub patterns: HashMap<String, Pattern>,
+  // Telid functions currently being executed, outermost first
+  pub call_stack: Vec<StackFrame>,
+  // Called before every statement, if there is one
+  pub hook: Option<Box<dyn Hook>>,
+  // Times every call and statement, if there is one
+  pub profiler: Option<Profiler>,
+  // Counts the statements and branches that run, if there is one
+  pub coverage: Option<Coverage>,
+  pub limits: Limits,
+  pub(crate) usage: Usage,
+  // What `args` returns, the arguments given after the script's path
+  pub args: Vec<String>,
+}
+
+impl Context {
+  pub fn new() -> Self {
+    Context {
+      rng: Rng::from_entropy(),
+      clock: Box::new(SystemClock::new()),
+      io: Box::new(StandardIo),
+      patterns: HashMap::new(),
+      call_stack: Vec::new(),
+      hook: None,
+      profiler: None,
+      coverage: None,
+      limits: Limits::default(),
+      usage: Usage::default(),
+      args: Vec::new(),
+    }
+  }
+
+  // Left in place of a context while a generator has it
+  pub(crate) fn placeholder() -> Self {
+    Context {
+      rng: Rng::new(0),
+      clock: Box::new(FixedClock::new(0.0)),
+      io: Box::new(StandardIo),
+      patterns: HashMap::new(),
+      call_stack: Vec::new(),
+      hook: None,
+      profiler: None,
+      coverage: None,
+      limits: Limits::default(),
+      usage: Usage::default(),
+      args: Vec::new(),
+    }
+  }
+
+  pub fn with_seed(seed: u64) -> Self {
+    Context {
+      rng: Rng::new(seed),
+      ..Context::new()
+    }
+  }
+
+  // Seeded randomness and a clock frozen at `now`, so that every run of a
+  // script produces exactly the same output
+  pub fn deterministic(seed: u64, now: f64) -> Self {
+    Context {
+      rng: Rng::new(seed),
+      clock: Box::new(FixedClock::new(now)),
+      ..Context::new()
+    }
+  }
+}
+
+impl Default for Context {
+  fn default() -> Self {
+    Context::new()
+  }
+}
@@ -0,0 +1,223 @@
+use crate::{
+  parser::ast::{Expression, ExpressionKind, Statement, StatementKind},
+  position::{position_of, positions},
+};
+use std::{
+  collections::{BTreeMap, HashMap},
+  fmt::Write,
+  ops::Range,
+};
+
+// Records which statements and which sides of each `if` a program runs. Set
+// `Context::coverage` to one made from the program being run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Coverage {
+  // Times each statement ran, by span in tokens. Blocks aren't counted, only
+  // the statements in them
+  pub statements: HashMap<Range<usize>, u64>,
+  // Times each `if` ran its consequence and its alternative, by the span of
+  // the `if`. An `if` without an `else` still has an alternative that does
+  // nothing
+  pub branches: HashMap<Range<usize>, [u64; 2]>,
+}
+
+// How much of a program ran, by line. Lines count from 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineCoverage {
+  // Times the most-run statement starting on each line ran
+  pub lines: BTreeMap<usize, u64>,
+  // Line of each `if`, and how often each side ran
+  pub branches: Vec<(usize, [u64; 2])>,
+}
+
+impl Coverage {
+  // Starts every statement and branch in `program` at 0, so the ones that never
+  // run show up too
+  pub fn new(program: &[Statement]) -> Self {
+    let mut coverage = Coverage::default();
+    for statement in program {
+      coverage.register_statement(statement);
+    }
+    coverage
+  }
+
+  pub(crate) fn statement(&mut self, span: &Range<usize>) {
+    if let Some(count) = self.statements.get_mut(span) {
+      *count += 1;
+    }
+  }
+
+  pub(crate) fn branch(&mut self, span: &Range<usize>, consequence: bool) {
+    if let Some(counts) = self.branches.get_mut(span) {
+      counts[usize::from(!consequence)] += 1;
+    }
+  }
+
+  fn register_statement(&mut self, statement: &Statement) {
+    match &statement.kind {
+      StatementKind::Block(statements) => {
+        for statement in statements {
+          self.register_statement(statement);
+        }
+        return;
+      }
+      StatementKind::Let { value, .. }
+      | StatementKind::Assignment { value, .. }
+      | StatementKind::Expression(value) => self.register_expression(value),
+      StatementKind::FunctionDeclaration { body, .. } => self.register_statement(body),
+    }
+    self.statements.insert(statement.span.clone(), 0);
+  }
+
+  fn register_expression(&mut self, expression: &Expression) {
+    match &expression.kind {
+      ExpressionKind::Void
+      | ExpressionKind::Identifier(_)
+      | ExpressionKind::NumberLiteral(_)
+      | ExpressionKind::StringLiteral(_)
+      | ExpressionKind::BooleanLiteral(_)
+      | ExpressionKind::Error => {}
+      ExpressionKind::ArrayLiteral(expressions)
+      | ExpressionKind::FunctionCall {
+        arguments: expressions,
+        ..
+      } => {
+        for expression in expressions {
+          self.register_expression(expression);
+        }
+      }
+      ExpressionKind::Index { iterable, index } => {
+        self.register_expression(iterable);
+        self.register_expression(index);
+      }
+      ExpressionKind::Slice {
+        iterable,
+        start,
+        end,
+      } => {
+        self.register_expression(iterable);
+        for bound in [start.as_ref(), end.as_ref()].into_iter().flatten() {
+          self.register_expression(bound);
+        }
+      }
+      ExpressionKind::If {
+        condition,
+        consequence,
+        alternative,
+      } => {
+        self.branches.insert(expression.span.clone(), [0, 0]);
+        self.register_expression(condition);
+        self.register_statement(consequence);
+        if let Some(alternative) = alternative.as_ref() {
+          self.register_statement(alternative);
+        }
+      }
+      ExpressionKind::For { iterable, body, .. } => {
+        self.register_expression(iterable);
+        self.register_statement(body);
+      }
+      ExpressionKind::While { condition, body } => {
+        self.register_expression(condition);
+        self.register_statement(body);
+      }
+      ExpressionKind::Binary { left, right, .. } => {
+        self.register_expression(left);
+        self.register_expression(right);
+      }
+      ExpressionKind::Unary { operand, .. } | ExpressionKind::Yield(operand) => {
+        self.register_expression(operand)
+      }
+    }
+  }
+
+  // Maps spans back to the lines of `source`, which the program was parsed from
+  pub fn lines(&self, source: &str) -> LineCoverage {
+    let positions = positions(source);
+    let line = |span: &Range<usize>| position_of(&positions, span.start).line;
+
+    let mut lines = BTreeMap::new();
+    for (span, count) in &self.statements {
+      let hits = lines.entry(line(span)).or_insert(0);
+      *hits = (*hits).max(*count);
+    }
+
+    let mut branches = self
+      .branches
+      .iter()
+      .map(|(span, counts)| (span.start, line(span), *counts))
+      .collect::<Vec<_>>();
+    branches.sort();
+
+    LineCoverage {
+      lines,
+      branches: branches
+        .into_iter()
+        .map(|(_, line, counts)| (line, counts))
+        .collect(),
+    }
+  }
+}
+
+impl LineCoverage {
+  // In the lcov tracefile format, with lines counted from 1
+  pub fn lcov(&self, path: &str) -> String {
+    let mut lcov = format!("TN:\nSF:{}\n", path);
+
+    for (block, (line, counts)) in self.branches.iter().enumerate() {
+      let evaluated = counts[0] + counts[1] > 0;
+      for (branch, count) in counts.iter().enumerate() {
+        let taken = if evaluated {
+          count.to_string()
+        } else {
+          String::from("-")
+        };
+        writeln!(lcov, "BRDA:{},{},{},{}", line + 1, block, branch, taken).unwrap();
+      }
+    }
+    let (found, hit) = self.branch_totals();
+    writeln!(lcov, "BRF:{}\nBRH:{}", found, hit).unwrap();
+
+    for (line, count) in &self.lines {
+      writeln!(lcov, "DA:{},{}", line + 1, count).unwrap();
+    }
+    let (found, hit) = self.line_totals();
+    writeln!(lcov, "LF:{}\nLH:{}", found, hit).unwrap();
+
+    lcov + "end_of_record\n"
+  }
+
+  // Lines with statements, and how many of them ran
+  pub fn line_totals(&self) -> (usize, usize) {
+    let hit = self.lines.values().filter(|count| **count > 0).count();
+    (self.lines.len(), hit)
+  }
+
+  // Sides of `if`s, and how many of them ran
+  pub fn branch_totals(&self) -> (usize, usize) {
+    let hit = self
+      .branches
+      .iter()
+      .flat_map(|(_, counts)| counts)
+      .filter(|count| **count > 0)
+      .count();
+    (self.branches.len() * 2, hit)
+  }
+
+  // Lines with statements that never ran, with consecutive ones merged
+  pub fn missed_lines(&self) -> Vec<Range<usize>> {
+    let mut missed: Vec<Range<usize>> = Vec::new();
+    let mut previous = None;
+
+    for (line, count) in &self.lines {
+      if *count == 0 {
+        match missed.last_mut() {
+          Some(range) if previous == Some(range.end - 1) => range.end = line + 1,
+          _ => missed.push(*line..line + 1),
+        }
+      }
+      previous = Some(*line);
+    }
+
+    missed
+  }
+}
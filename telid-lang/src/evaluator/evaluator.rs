@@ -1,61 +1,129 @@
 use super::{
+  context::Context,
+  generator::{self, Generator, Resumed},
+  iterator::Elements,
+  range::NumberRange,
   scope::Scope,
-  util::error,
+  stack::grow,
+  util::{check_type, error},
   value::{Value, Variable},
 };
 use crate::{
-  error::{EvaluationError, EvaluationErrorKind},
+  error::{EvaluationError, EvaluationErrorKind, StackFrame},
   parser::ast::{
-    BinaryOperator, Expression, ExpressionKind, Statement, StatementKind, UnaryOperator,
+    BinaryOperator, Expression, ExpressionKind, Identifier, Statement, StatementKind, Type,
+    UnaryOperator,
   },
 };
+use std::ops::Range;
 
-pub fn evaluate(
+// Values passed to a function, each with the span of the expression it came
+// from
+pub(crate) type Arguments = Vec<(Value, Range<usize>)>;
+
+pub fn evaluate(program: Vec<Statement>, scope: Scope) -> Result<(Value, Scope), EvaluationError> {
+  evaluate_with_context(program, scope, &mut Context::default())
+}
+
+pub fn evaluate_with_context(
   program: Vec<Statement>,
   mut scope: Scope,
+  context: &mut Context,
 ) -> Result<(Value, Scope), EvaluationError> {
   let mut value = Value::Void;
   for statement in program {
-    value = evaluate_statement(statement, &mut scope)?;
+    value = evaluate_statement(statement, &mut scope, context)?;
   }
   Ok((value, scope))
 }
 
-fn evaluate_statement(
+pub(crate) fn evaluate_statement(
+  statement: Statement,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Value, EvaluationError> {
+  evaluate_statement_with(statement, scope, context, execute_statement)
+}
+
+// Runs `execute` on a statement, with everything that happens around each one
+fn evaluate_statement_with<T>(
+  statement: Statement,
+  scope: &mut Scope,
+  context: &mut Context,
+  execute: impl FnOnce(Statement, &mut Scope, &mut Context) -> Result<T, EvaluationError>,
+) -> Result<T, EvaluationError> {
+  context
+    .usage
+    .statement(&context.limits, context.clock.as_ref(), &statement.span)?;
+
+  if let Some(mut hook) = context.hook.take() {
+    hook.before_statement(&statement, scope, context);
+    context.hook = Some(hook);
+  }
+
+  if let Some(coverage) = context.coverage.as_mut() {
+    coverage.statement(&statement.span);
+  }
+
+  let span = statement.span.clone();
+  let Some(profiler) = context.profiler.as_mut() else {
+    return grow(&span, || execute(statement, scope, context));
+  };
+  profiler.enter_statement(span.clone());
+  let result = grow(&span, || execute(statement, scope, context));
+  if let Some(profiler) = context.profiler.as_mut() {
+    profiler.exit_statement();
+  }
+  result
+}
+
+fn execute_statement(
   statement: Statement,
-  mut scope: &mut Scope,
+  scope: &mut Scope,
+  context: &mut Context,
 ) -> Result<Value, EvaluationError> {
   let Statement { kind, span } = statement;
 
   match kind {
     StatementKind::Block(statements) => {
+      // The frame is popped even if a statement fails, since `assert_throws`
+      // can catch the error and carry on in the same scope
       scope.push_scope();
-      let mut value = Value::Void;
+      let mut value = Ok(Value::Void);
       for statement in statements {
-        value = evaluate_statement(statement, &mut scope)?;
+        value = evaluate_statement(statement, scope, context);
+        if value.is_err() {
+          break;
+        }
       }
       scope.pop_scope();
-      Ok(value)
+      value
     }
     StatementKind::Let {
       name,
       value,
       constant,
+      annotation,
     } => {
-      let value = evaluate_expression(value, &mut scope)?;
+      let value_span = value.span.clone();
+      let value = evaluate_expression(value, scope, context)?;
+      check_type(&value, annotation.as_ref(), value_span)?;
       scope.insert(
         name.0,
         Variable {
           value: value.clone(),
           constant,
+          annotation,
         },
       );
       Ok(value)
     }
-    StatementKind::Expression(expression) => evaluate_expression(expression, &mut scope),
+    StatementKind::Expression(expression) => evaluate_expression(expression, scope, context),
     StatementKind::FunctionDeclaration {
       name,
       parameters,
+      parameter_types,
+      return_type,
       body,
     } => {
       scope.insert(
@@ -63,25 +131,34 @@ fn evaluate_statement(
         Variable {
           value: Value::Function {
             parameters: parameters.iter().map(|p| p.0.clone()).collect(),
+            parameter_types,
+            return_type,
+            generator: body.yields(),
             body,
           },
           constant: false,
+          annotation: None,
         },
       );
       Ok(Value::Void)
     }
-    StatementKind::Assignment { name, value } => match scope.clone().get(&name.0) {
+    StatementKind::Assignment { name, value } => match scope.lookup(&name) {
       Some(variable) => {
-        let value = evaluate_expression(value, &mut scope)?;
+        let constant = variable.constant;
+        let annotation = variable.annotation.clone();
+        let value_span = value.span.clone();
+        let value = evaluate_expression(value, scope, context)?;
 
-        if variable.constant {
+        if constant {
           error(EvaluationErrorKind::ConstantReassignment(name.0), span)
         } else {
+          check_type(&value, annotation.as_ref(), value_span)?;
           scope.insert_existing(
             name.0,
             Variable {
               value: value.clone(),
               constant: false,
+              annotation,
             },
           );
           Ok(value)
@@ -94,13 +171,38 @@ fn evaluate_statement(
 
 fn evaluate_expression(
   expression: Expression,
-  mut scope: &mut Scope,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Value, EvaluationError> {
+  // Only the expressions that make new strings and arrays count towards the
+  // limits on them. Calls to builtins are counted in `call`, and loops count
+  // each element as they add it
+  let makes_value = matches!(
+    expression.kind,
+    ExpressionKind::ArrayLiteral(_) | ExpressionKind::Slice { .. } | ExpressionKind::Binary { .. }
+  );
+  let span = expression.span.clone();
+  if !makes_value {
+    return grow(&span, || execute_expression(expression, scope, context));
+  }
+
+  let value = grow(&span, || execute_expression(expression, scope, context))?;
+  context.usage.value(&context.limits, &value, &span)?;
+  Ok(value)
+}
+
+fn execute_expression(
+  expression: Expression,
+  scope: &mut Scope,
+  context: &mut Context,
 ) -> Result<Value, EvaluationError> {
   let Expression { kind, span } = expression;
 
   match kind {
-    ExpressionKind::Void => Ok(Value::Void),
-    ExpressionKind::Identifier(identifier) => match scope.get(&identifier.0) {
+    // Programs with syntax errors are rejected before evaluation, so this is
+    // only reachable if a host evaluates a recovered AST anyway
+    ExpressionKind::Void | ExpressionKind::Error => Ok(Value::Void),
+    ExpressionKind::Identifier(identifier) => match scope.lookup(&identifier) {
       Some(variable) => Ok(variable.value.clone()),
       None => error(EvaluationErrorKind::UndefinedVariable(identifier.0), span),
     },
@@ -110,13 +212,13 @@ fn evaluate_expression(
     ExpressionKind::ArrayLiteral(expressions) => {
       let mut array = Vec::new();
       for expression in expressions {
-        array.push(evaluate_expression(expression, &mut scope)?);
+        array.push(evaluate_expression(expression, scope, context)?);
       }
       Ok(Value::Array(array))
     }
     ExpressionKind::Index { iterable, index } => {
-      let iterable = evaluate_expression(*iterable, &mut scope)?;
-      let index = evaluate_expression(*index, &mut scope)?;
+      let iterable = evaluate_expression(*iterable, scope, context)?;
+      let index = evaluate_expression(*index, scope, context)?;
       match (iterable.clone(), index) {
         (Value::Array(array), Value::Number(number)) => {
           let index = number as usize;
@@ -128,6 +230,16 @@ fn evaluate_expression(
             ),
           }
         }
+        (Value::Range(range), Value::Number(number)) => {
+          let index = number as usize;
+          match range.get(index) {
+            Some(number) => Ok(Value::Number(number)),
+            None => error(
+              EvaluationErrorKind::IndexOutOfBounds(index, range.len),
+              span,
+            ),
+          }
+        }
         (Value::String(string), Value::Number(number)) => {
           let index = number as usize;
           match string.chars().nth(index) {
@@ -152,13 +264,11 @@ fn evaluate_expression(
       start,
       end,
     } => {
-      let iterable = evaluate_expression(*iterable, &mut scope)?;
-      let array = match iterable.clone() {
-        Value::Array(array) => array,
-        Value::String(string) => string
-          .chars()
-          .map(|c| Value::String(c.to_string()))
-          .collect(), // TODO: More efficient way to do this?
+      let iterable = evaluate_expression(*iterable, scope, context)?;
+      let length = match &iterable {
+        Value::Array(array) => array.len(),
+        Value::String(string) => string.chars().count(),
+        Value::Range(range) => range.len,
         _ => {
           return error(
             EvaluationErrorKind::InvalidType(
@@ -171,7 +281,7 @@ fn evaluate_expression(
       };
 
       let start = match *start {
-        Some(start) => match evaluate_expression(start, &mut scope)? {
+        Some(start) => match evaluate_expression(start, scope, context)? {
           Value::Number(number) => number as usize,
           x => {
             return error(
@@ -184,7 +294,7 @@ fn evaluate_expression(
       };
 
       let end = match *end {
-        Some(end) => match evaluate_expression(end, &mut scope)? {
+        Some(end) => match evaluate_expression(end, scope, context)? {
           Value::Number(number) => number as usize,
           x => {
             return error(
@@ -193,7 +303,7 @@ fn evaluate_expression(
             );
           }
         },
-        None => array.len(),
+        None => length,
       };
 
       if start > end {
@@ -203,86 +313,26 @@ fn evaluate_expression(
         );
       }
 
-      if end > array.len() {
-        return error(
-          EvaluationErrorKind::IndexOutOfBounds(end, array.len()),
-          span,
-        );
-      }
-
-      let mut result = Vec::new();
-      for i in start..end {
-        result.push(array[i].clone());
+      if end > length {
+        return error(EvaluationErrorKind::IndexOutOfBounds(end, length), span);
       }
 
       match iterable {
-        Value::Array(_) => Ok(Value::Array(result)),
-        Value::String(_) => Ok(Value::String(
-          result
-            .iter()
-            .map(|value| value.to_string())
-            .collect::<Vec<_>>()
-            .join(""),
+        Value::Array(array) => Ok(Value::Array(array[start..end].to_vec())),
+        Value::String(string) => Ok(Value::String(
+          string.chars().skip(start).take(end - start).collect(),
         )),
+        // Still a range, however long it is
+        Value::Range(range) => Ok(Value::Range(range.slice(start, end))),
         _ => unreachable!(),
       }
     }
     ExpressionKind::FunctionCall { name, arguments } => {
-      let function = match scope.get(&name.0) {
-        Some(variable) => variable.value.clone(),
-        None => return error(EvaluationErrorKind::UndefinedVariable(name.0), span),
-      };
-
-      match function {
-        Value::RustFunction {
-          parameter_count,
-          function,
-        } => {
-          if arguments.len() != parameter_count {
-            return error(
-              EvaluationErrorKind::IncorrectParameterCount(arguments.len(), parameter_count),
-              span,
-            );
-          }
-          let mut passed_parameters: Vec<Value> = Vec::new();
-          for parameter in arguments {
-            passed_parameters.push(evaluate_expression(parameter, &mut scope)?);
-          }
-          function(span, passed_parameters)
-        }
-        Value::Function { parameters, body } => {
-          if parameters.len() != parameters.len() {
-            return error(
-              EvaluationErrorKind::IncorrectParameterCount(parameters.len(), parameters.len()),
-              span,
-            );
-          }
-          scope.push_scope();
-          for (parameter, value) in parameters.iter().zip(arguments) {
-            let value = evaluate_expression(value, &mut scope)?;
-            scope.insert(
-              parameter.clone(),
-              Variable {
-                value,
-                constant: true,
-              },
-            );
-          }
-          let result = evaluate_statement(*body, &mut scope);
-          scope.pop_scope();
-          result
-        }
-        _ => error(
-          EvaluationErrorKind::InvalidType(
-            function.as_ref().to_string(),
-            vec!["Function".to_string()],
-          ),
-          span,
-        ),
-      }
+      let (function, values) = prepare_call(&name, arguments, &span, scope, context)?;
+      call(function, name.0, values, span, scope, context)
     }
     ExpressionKind::Unary { operator, operand } => {
-      let operand = evaluate_expression(*operand, &mut scope)?;
+      let operand = evaluate_expression(*operand, scope, context)?;
       match (operator.clone(), operand.clone()) {
         (UnaryOperator::Negate, Value::Number(number)) => Ok(Value::Number(-number)),
         (UnaryOperator::Not, Value::Boolean(boolean)) => Ok(Value::Boolean(!boolean)),
@@ -304,78 +354,66 @@ fn evaluate_expression(
       match operator {
         // Make sure logical operators are short-circuited
         BinaryOperator::And => {
-          let left = evaluate_expression(*left.clone(), &mut scope)?;
+          let left = evaluate_expression(*left.clone(), scope, context)?;
           match left {
             Value::Boolean(left_result) => {
               if !left_result {
                 return Ok(Value::Boolean(false));
               }
 
-              let right = evaluate_expression(*right.clone(), &mut scope)?;
+              let right = evaluate_expression(*right.clone(), scope, context)?;
               match right {
-                Value::Boolean(right_result) => {
-                  return Ok(Value::Boolean(right_result));
-                }
-                _ => {
-                  return error(
-                    EvaluationErrorKind::InvalidType(
-                      right.as_ref().to_string(),
-                      vec!["Boolean".to_string()],
-                    ),
-                    span,
-                  );
-                }
-              }
-            }
-            _ => {
-              return error(
-                EvaluationErrorKind::InvalidType(
-                  left.as_ref().to_string(),
-                  vec!["Boolean".to_string()],
+                Value::Boolean(right_result) => Ok(Value::Boolean(right_result)),
+                _ => error(
+                  EvaluationErrorKind::InvalidType(
+                    right.as_ref().to_string(),
+                    vec!["Boolean".to_string()],
+                  ),
+                  span,
                 ),
-                span,
-              );
+              }
             }
+            _ => error(
+              EvaluationErrorKind::InvalidType(
+                left.as_ref().to_string(),
+                vec!["Boolean".to_string()],
+              ),
+              span,
+            ),
           }
         }
         BinaryOperator::Or => {
-          let left = evaluate_expression(*left.clone(), &mut scope)?;
+          let left = evaluate_expression(*left.clone(), scope, context)?;
           match left {
             Value::Boolean(left_result) => {
               if left_result {
                 return Ok(Value::Boolean(true));
               }
 
-              let right = evaluate_expression(*right.clone(), &mut scope)?;
+              let right = evaluate_expression(*right.clone(), scope, context)?;
               match right {
-                Value::Boolean(right_result) => {
-                  return Ok(Value::Boolean(right_result));
-                }
-                _ => {
-                  return error(
-                    EvaluationErrorKind::InvalidType(
-                      right.as_ref().to_string(),
-                      vec!["Boolean".to_string()],
-                    ),
-                    span,
-                  );
-                }
-              }
-            }
-            _ => {
-              return error(
-                EvaluationErrorKind::InvalidType(
-                  left.as_ref().to_string(),
-                  vec!["Boolean".to_string()],
+                Value::Boolean(right_result) => Ok(Value::Boolean(right_result)),
+                _ => error(
+                  EvaluationErrorKind::InvalidType(
+                    right.as_ref().to_string(),
+                    vec!["Boolean".to_string()],
+                  ),
+                  span,
                 ),
-                span,
-              );
+              }
             }
+            _ => error(
+              EvaluationErrorKind::InvalidType(
+                left.as_ref().to_string(),
+                vec!["Boolean".to_string()],
+              ),
+              span,
+            ),
           }
         }
         _ => {
-          let left = evaluate_expression(*left, &mut scope)?;
-          let right = evaluate_expression(*right, &mut scope)?;
+          let left = evaluate_expression(*left, scope, context)?;
+          let right = evaluate_expression(*right, scope, context)?;
           match (operator.clone(), left.clone(), right.clone()) {
             // any type, any type
             (BinaryOperator::Equal, left, right) => Ok(Value::Boolean(left == right)),
@@ -410,15 +448,10 @@ fn evaluate_expression(
               Ok(Value::Boolean(left >= right))
             }
             (BinaryOperator::Range, Value::Number(left), Value::Number(right)) => {
-              if left > right {
-                return error(EvaluationErrorKind::InvalidRange(left, right), span);
-              }
-
-              let mut array = Vec::new();
-              for i in left as usize..=right as usize {
-                array.push(Value::Number(i as f64));
+              match NumberRange::new(left.trunc(), right.trunc(), 1.0) {
+                Some(range) if left <= right => Ok(Value::Range(range)),
+                _ => error(EvaluationErrorKind::InvalidRange(left, right), span),
               }
-              Ok(Value::Array(array))
             }
 
             // string, string
@@ -437,10 +470,10 @@ fn evaluate_expression(
 
             // string, any type
             (BinaryOperator::Add, Value::String(left), right) => {
-              Ok(Value::String(format!("{}{}", left, right.to_string())))
+              Ok(Value::String(format!("{}{}", left, right)))
             }
             (BinaryOperator::Add, left, Value::String(right)) => {
-              Ok(Value::String(format!("{}{}", left.to_string(), right)))
+              Ok(Value::String(format!("{}{}", left, right)))
             }
 
             // unhandled cases
@@ -460,87 +493,71 @@ fn evaluate_expression(
       condition,
       consequence,
       alternative,
-    } => {
-      let condition = evaluate_expression(*condition, &mut scope)?;
-      match condition {
-        Value::Boolean(boolean) => {
-          if boolean {
-            evaluate_statement(*consequence, &mut scope)
-          } else {
-            match *alternative {
-              Some(alternative) => evaluate_statement(alternative, &mut scope),
-              None => Ok(Value::Void),
-            }
-          }
-        }
-        _ => error(
-          EvaluationErrorKind::InvalidType(
-            condition.as_ref().to_string(),
-            vec!["Boolean".to_string()],
-          ),
-          span,
-        ),
-      }
-    }
+    } => match branch(*condition, *consequence, *alternative, span, scope, context)? {
+      Some(statement) => evaluate_statement(statement, scope, context),
+      None => Ok(Value::Void),
+    },
     ExpressionKind::For {
       variable,
       iterable,
       body,
     } => {
-      let iterable = evaluate_expression(*iterable, &mut scope)?;
-      match iterable {
-        Value::Array(array) => {
-          let mut value = Vec::new();
-          for element in array {
-            scope.push_scope();
-            scope.insert(
-              variable.0.clone(),
-              Variable {
-                value: element.clone(),
-                constant: true,
-              },
-            );
-            value.push(evaluate_statement(*body.clone(), &mut scope)?);
-            scope.pop_scope();
-          }
-          Ok(Value::Array(value))
-        }
-        Value::String(string) => {
-          let mut value = Vec::new();
-          for character in string.chars() {
-            scope.push_scope();
-            scope.insert(
-              variable.0.clone(),
-              Variable {
-                value: Value::String(character.to_string()),
-                constant: true,
-              },
-            );
-            value.push(evaluate_statement(*body.clone(), &mut scope)?);
-            scope.pop_scope();
-          }
-          Ok(Value::Array(value))
+      let iterable = evaluate_expression(*iterable, scope, context)?;
+      let mut elements = match Elements::new(iterable) {
+        Ok(elements) => elements,
+        Err(iterable) => {
+          return error(
+            EvaluationErrorKind::InvalidType(
+              iterable.as_ref().to_string(),
+              vec!["Array".to_string()],
+            ),
+            span,
+          );
         }
-        _ => error(
-          EvaluationErrorKind::InvalidType(
-            iterable.as_ref().to_string(),
-            vec!["Array".to_string()],
-          ),
-          span,
-        ),
+      };
+
+      let mut value = Vec::new();
+      while let Some(element) = elements.next(&span, scope, context)? {
+        scope.push_scope();
+        scope.insert(
+          variable.0.clone(),
+          Variable {
+            value: element,
+            constant: true,
+            annotation: None,
+          },
+        );
+        let result = evaluate_statement(*body.clone(), scope, context);
+        scope.pop_scope();
+        let result = result?;
+        context
+          .usage
+          .element(&context.limits, value.len() + 1, &span)?;
+        value.push(result);
       }
+      Ok(Value::Array(value))
+    }
+    ExpressionKind::Yield(value) => {
+      let value = evaluate_expression(*value, scope, context)?;
+      generator::suspend(value, span, scope, context)?;
+      Ok(Value::Void)
     }
     ExpressionKind::While { condition, body } => {
       let mut value = Vec::new();
 
       loop {
-        let condition = evaluate_expression(*condition.clone(), &mut scope)?;
+        let condition = evaluate_expression(*condition.clone(), scope, context)?;
         match condition {
           Value::Boolean(boolean) => {
             if boolean {
               scope.push_scope();
-              value.push(evaluate_statement(*body.clone(), &mut scope)?);
+              let result = evaluate_statement(*body.clone(), scope, context);
               scope.pop_scope();
+              let result = result?;
+              context
+                .usage
+                .element(&context.limits, value.len() + 1, &span)?;
+              value.push(result);
             } else {
               break;
             }
@@ -561,3 +578,400 @@ fn evaluate_expression(
     }
   }
 }
+
+// Evaluates an `if`'s condition, and picks the statement to run next, if any
+fn branch(
+  condition: Expression,
+  consequence: Statement,
+  alternative: Option<Statement>,
+  span: Range<usize>,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Option<Statement>, EvaluationError> {
+  let condition = evaluate_expression(condition, scope, context)?;
+  match condition {
+    Value::Boolean(boolean) => {
+      if let Some(coverage) = context.coverage.as_mut() {
+        coverage.branch(&span, boolean);
+      }
+
+      Ok(if boolean {
+        Some(consequence)
+      } else {
+        alternative
+      })
+    }
+    _ => error(
+      EvaluationErrorKind::InvalidType(condition.as_ref().to_string(), vec!["Boolean".to_string()]),
+      span,
+    ),
+  }
+}
+
+// Looks up the function a call is to, and evaluates its arguments
+fn prepare_call(
+  name: &Identifier,
+  arguments: Vec<Expression>,
+  span: &Range<usize>,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<(Value, Arguments), EvaluationError> {
+  let function = match scope.lookup(name) {
+    Some(variable) => variable.value.clone(),
+    None => {
+      return error(
+        EvaluationErrorKind::UndefinedVariable(name.0.clone()),
+        span.clone(),
+      )
+    }
+  };
+
+  if let Value::RustFunction {
+    parameter_count, ..
+  } = function
+  {
+    if arguments.len() != parameter_count {
+      return error(
+        EvaluationErrorKind::IncorrectParameterCount(arguments.len(), parameter_count),
+        span.clone(),
+      );
+    }
+  }
+
+  // Arguments are evaluated in the caller's frame, before any parameter
+  // can shadow what they refer to
+  let mut values = Vec::new();
+  for argument in arguments {
+    let argument_span = argument.span.clone();
+    values.push((
+      evaluate_expression(argument, scope, context)?,
+      argument_span,
+    ));
+  }
+
+  Ok((function, values))
+}
+
+// What a statement in tail position evaluates to. A call to a Telid function
+// there is returned instead of made, so that the function it's in can end
+// before it starts, and tail recursion runs in constant space
+enum Tail {
+  Value(Value),
+  Call {
+    function: Value,
+    name: String,
+    arguments: Arguments,
+    span: Range<usize>,
+    // The variables the calling function could see in its own frames, which
+    // the function it calls can still see, since it's called from there
+    variables: Vec<(String, Variable)>,
+  },
+}
+
+// `base` is how many frames there were outside the function being called,
+// which are the ones left when a tail call is made
+fn evaluate_tail_statement(
+  statement: Statement,
+  base: usize,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Tail, EvaluationError> {
+  evaluate_statement_with(statement, scope, context, |statement, scope, context| {
+    execute_tail_statement(statement, base, scope, context)
+  })
+}
+
+fn execute_tail_statement(
+  statement: Statement,
+  base: usize,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Tail, EvaluationError> {
+  let Statement { kind, span } = statement;
+
+  match kind {
+    StatementKind::Block(statements) => {
+      scope.push_scope();
+      let last = statements.len().saturating_sub(1);
+      let mut value = Ok(Tail::Value(Value::Void));
+      for (index, statement) in statements.into_iter().enumerate() {
+        value = if index == last {
+          evaluate_tail_statement(statement, base, scope, context)
+        } else {
+          evaluate_statement(statement, scope, context).map(Tail::Value)
+        };
+        if value.is_err() {
+          break;
+        }
+      }
+      scope.pop_scope();
+      value
+    }
+    StatementKind::Expression(expression) => {
+      evaluate_tail_expression(expression, base, scope, context)
+    }
+    kind => execute_statement(Statement { kind, span }, scope, context).map(Tail::Value),
+  }
+}
+
+fn evaluate_tail_expression(
+  expression: Expression,
+  base: usize,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Tail, EvaluationError> {
+  let Expression { kind, span } = expression;
+
+  match kind {
+    ExpressionKind::If {
+      condition,
+      consequence,
+      alternative,
+    } => match branch(*condition, *consequence, *alternative, span, scope, context)? {
+      Some(statement) => evaluate_tail_statement(statement, base, scope, context),
+      None => Ok(Tail::Value(Value::Void)),
+    },
+    ExpressionKind::FunctionCall { name, arguments } => {
+      let (function, arguments) = prepare_call(&name, arguments, &span, scope, context)?;
+
+      match function {
+        Value::Function {
+          generator: false, ..
+        } => Ok(Tail::Call {
+          function,
+          name: name.0,
+          arguments,
+          span,
+          variables: scope.take_variables(base),
+        }),
+        _ => call(function, name.0, arguments, span, scope, context).map(Tail::Value),
+      }
+    }
+    kind => evaluate_expression(Expression { kind, span }, scope, context).map(Tail::Value),
+  }
+}
+
+fn enter_call(context: &mut Context, name: &str) {
+  if let Some(profiler) = context.profiler.as_mut() {
+    profiler.enter_call(name);
+  }
+}
+
+fn exit_call(context: &mut Context) {
+  if let Some(profiler) = context.profiler.as_mut() {
+    profiler.exit_call();
+  }
+}
+
+// Calls `function` with arguments that have already been evaluated
+fn call(
+  function: Value,
+  name: String,
+  arguments: Arguments,
+  span: Range<usize>,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Value, EvaluationError> {
+  match function {
+    Value::RustFunction {
+      parameter_count,
+      function,
+    } => {
+      if arguments.len() != parameter_count {
+        return error(
+          EvaluationErrorKind::IncorrectParameterCount(arguments.len(), parameter_count),
+          span,
+        );
+      }
+
+      let values = arguments.into_iter().map(|(value, _)| value).collect();
+      let callback_name = format!("<function passed to {}>", name);
+      enter_call(context, &name);
+      let result = function(
+        context,
+        span.clone(),
+        values,
+        &mut |context, function, arguments| {
+          let arguments = arguments
+            .into_iter()
+            .map(|value| (value, span.clone()))
+            .collect();
+          call(
+            function,
+            callback_name.clone(),
+            arguments,
+            span.clone(),
+            scope,
+            context,
+          )
+        },
+      );
+      exit_call(context);
+      let value = result?;
+      context.usage.value(&context.limits, &value, &span)?;
+      Ok(value)
+    }
+    // Calling a generator function only makes the generator
+    Value::Function {
+      generator: true, ..
+    } => Ok(Value::Generator(Generator::new(
+      function, name, arguments, span,
+    ))),
+    Value::Function { .. } => start(function, name, arguments, span, scope, context),
+    // Calling a generator resumes it
+    Value::Generator(generator) => {
+      if !arguments.is_empty() {
+        return error(
+          EvaluationErrorKind::IncorrectParameterCount(arguments.len(), 0),
+          span,
+        );
+      }
+      generator.resume(&span, scope, context).map(Resumed::value)
+    }
+    _ => error(
+      EvaluationErrorKind::InvalidType(function.as_ref().to_string(), vec!["Function".to_string()]),
+      span,
+    ),
+  }
+}
+
+// Runs a Telid function's body, even if it's a generator function, which is
+// how a generator starts. Anything else is just called
+pub(crate) fn start(
+  function: Value,
+  name: String,
+  arguments: Arguments,
+  span: Range<usize>,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Value, EvaluationError> {
+  if !matches!(function, Value::Function { .. }) {
+    return call(function, name, arguments, span, scope, context);
+  }
+  context.limits.call(context.call_stack.len(), &span)?;
+
+  enter_call(context, &name);
+  context.call_stack.push(StackFrame {
+    name,
+    span: span.clone(),
+  });
+  let result = call_function(function, arguments, span, scope, context);
+
+  // The innermost call an error passes through knows the whole stack
+  let result = result.map_err(|mut error| {
+    if error.stack.is_empty() {
+      error.stack = context.call_stack.iter().rev().cloned().collect();
+    }
+    error
+  });
+  context.call_stack.pop();
+  exit_call(context);
+  result
+}
+
+// Runs a Telid function, then each function it tail calls in its place. The
+// call to it has already been pushed onto the call stack, and each tail call
+// replaces it there
+fn call_function(
+  mut function: Value,
+  mut arguments: Arguments,
+  mut span: Range<usize>,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<Value, EvaluationError> {
+  // Return types of the calls that have ended in tail calls, which the value
+  // they all return still has to match
+  let mut return_types: Vec<(Type, Range<usize>)> = Vec::new();
+  // Variables of the calls that have ended in tail calls
+  let mut inherited: Vec<(String, Variable)> = Vec::new();
+  let base = scope.frame_count();
+
+  loop {
+    let Value::Function {
+      parameters,
+      parameter_types,
+      return_type,
+      body,
+      ..
+    } = function
+    else {
+      unreachable!("Only Telid functions are tail called");
+    };
+
+    if arguments.len() != parameters.len() {
+      return error(
+        EvaluationErrorKind::IncorrectParameterCount(arguments.len(), parameters.len()),
+        span,
+      );
+    }
+    for ((value, argument_span), annotation) in arguments.iter().zip(&parameter_types) {
+      check_type(value, annotation.as_ref(), argument_span.clone())?;
+    }
+
+    // They go in a frame of their own under the parameters, leaving out the
+    // ones the parameters hide
+    let inheriting = !inherited.is_empty();
+    if inheriting {
+      scope.push_scope();
+      for (name, variable) in inherited.drain(..) {
+        if !parameters.contains(&name) {
+          scope.insert(name, variable);
+        }
+      }
+    }
+    scope.push_scope();
+    for ((parameter, (value, _)), annotation) in
+      parameters.iter().zip(arguments).zip(parameter_types)
+    {
+      scope.insert(
+        parameter.clone(),
+        Variable {
+          value,
+          constant: true,
+          annotation,
+        },
+      );
+    }
+    let result = evaluate_tail_statement(*body, base, scope, context);
+    scope.pop_scope();
+    if inheriting {
+      scope.pop_scope();
+    }
+
+    match result? {
+      Tail::Value(value) => {
+        check_type(&value, return_type.as_ref(), span)?;
+        for (return_type, span) in return_types.into_iter().rev() {
+          check_type(&value, Some(&return_type), span)?;
+        }
+        return Ok(value);
+      }
+      Tail::Call {
+        function: callee,
+        name,
+        arguments: callee_arguments,
+        span: callee_span,
+        variables,
+      } => {
+        // A function that tail calls itself only needs its type checked once
+        if let Some(return_type) = return_type {
+          if return_types.last().map(|(last, _)| last) != Some(&return_type) {
+            return_types.push((return_type, span));
+          }
+        }
+
+        exit_call(context);
+        enter_call(context, &name);
+        *context.call_stack.last_mut().unwrap() = StackFrame {
+          name,
+          span: callee_span.clone(),
+        };
+
+        function = callee;
+        arguments = callee_arguments;
+        span = callee_span;
+        inherited = variables;
+      }
+    }
+  }
+}
@@ -0,0 +1,274 @@
+use super::{
+  context::Context,
+  evaluator::{start, Arguments},
+  scope::Scope,
+  stack,
+  util::error,
+  value::Value,
+};
+use crate::error::{EvaluationError, EvaluationErrorKind};
+use corosensei::{
+  stack::{DefaultStack, Stack},
+  Coroutine, CoroutineResult, Yielder,
+};
+use std::{
+  cell::{Cell, RefCell},
+  fmt, mem,
+  ops::Range,
+  rc::Rc,
+};
+
+// What a generator takes from whatever resumes it, and gives back when it
+// yields or finishes. Its own frames and calls go on top of these while it
+// runs, so it sees the same globals as anything else
+struct State {
+  scope: Scope,
+  context: Context,
+}
+
+type Body = Coroutine<State, (Value, State), (Result<Value, EvaluationError>, State), DefaultStack>;
+
+// A function call that runs on its own stack, up to each `yield` in turn.
+// Clones share the same call
+#[derive(Clone)]
+pub struct Generator(Rc<RefCell<Call>>);
+
+enum Call {
+  // Nothing runs until the generator is first resumed, and it only gets a
+  // stack then
+  Pending {
+    function: Value,
+    name: String,
+    arguments: Arguments,
+    span: Range<usize>,
+  },
+  Started(Started),
+  // The stack is given back as soon as the function returns
+  Done,
+}
+
+// A body that has a stack, counted in `STARTED` for as long as it does
+struct Started(Body);
+
+impl Drop for Started {
+  fn drop(&mut self) {
+    STARTED.set(STARTED.get() - 1);
+  }
+}
+
+// What resuming a generator ran into
+pub(crate) enum Resumed {
+  Yielded(Value),
+  Returned(Value),
+}
+
+impl Resumed {
+  pub(crate) fn value(self) -> Value {
+    match self {
+      Resumed::Yielded(value) | Resumed::Returned(value) => value,
+    }
+  }
+}
+
+// A generator that's running, and how much of the scope and call stack
+// belongs to whatever resumed it
+struct Running {
+  yielder: *const Yielder<State, (Value, State)>,
+  frames: usize,
+  calls: usize,
+}
+
+thread_local! {
+  // Innermost last. A generator is taken off while it's suspended
+  static RUNNING: RefCell<Vec<Running>> = const { RefCell::new(Vec::new()) };
+  // How many generators have started and not finished
+  static STARTED: Cell<usize> = const { Cell::new(0) };
+}
+
+pub(crate) fn started() -> usize {
+  STARTED.get()
+}
+
+impl Generator {
+  pub(crate) fn new(
+    function: Value,
+    name: String,
+    arguments: Arguments,
+    span: Range<usize>,
+  ) -> Self {
+    Generator(Rc::new(RefCell::new(Call::Pending {
+      function,
+      name,
+      arguments,
+      span,
+    })))
+  }
+
+  // A stack for another generator to start on, if the limits allow one
+  fn stack(span: &Range<usize>, context: &Context) -> Result<DefaultStack, EvaluationError> {
+    if let Some(generators) = context.limits.generators {
+      if started() >= generators {
+        return error(
+          EvaluationErrorKind::TooManyGenerators(started()),
+          span.clone(),
+        );
+      }
+    }
+    stack::new_stack(span)
+  }
+
+  // The function call, to run on `stack`
+  fn body(
+    stack: DefaultStack,
+    function: Value,
+    name: String,
+    arguments: Arguments,
+    span: Range<usize>,
+  ) -> Started {
+    let limit = stack.limit().get();
+    let body = Coroutine::with_stack(stack, move |yielder, state: State| {
+      let State {
+        mut scope,
+        mut context,
+      } = state;
+      stack::switch(Some(limit));
+      RUNNING.with_borrow_mut(|running| {
+        running.push(Running {
+          yielder,
+          frames: scope.frame_count(),
+          calls: context.call_stack.len(),
+        })
+      });
+
+      let result = start(function, name, arguments, span, &mut scope, &mut context);
+
+      // An error can leave frames and calls behind
+      if let Some(running) = RUNNING.with_borrow_mut(|running| running.pop()) {
+        scope.split_off(running.frames);
+        context.call_stack.truncate(running.calls);
+      }
+      (result, State { scope, context })
+    });
+
+    STARTED.set(started() + 1);
+    Started(body)
+  }
+
+  // `suspended` before it starts and between yields, then `running` or `done`
+  pub fn status(&self) -> &'static str {
+    match self.0.try_borrow() {
+      Err(_) => "running",
+      Ok(call) if matches!(*call, Call::Done) => "done",
+      Ok(_) => "suspended",
+    }
+  }
+
+  // Runs the generator up to its next `yield`, or until it finishes
+  pub(crate) fn resume(
+    &self,
+    span: &Range<usize>,
+    scope: &mut Scope,
+    context: &mut Context,
+  ) -> Result<Resumed, EvaluationError> {
+    let status = self.status();
+    if status != "suspended" {
+      return error(
+        EvaluationErrorKind::GeneratorNotSuspended(status.to_string()),
+        span.clone(),
+      );
+    }
+    let mut call = self.0.borrow_mut();
+    if let Call::Pending { .. } = *call {
+      let stack = Generator::stack(span, context)?;
+      let Call::Pending {
+        function,
+        name,
+        arguments,
+        span: call_span,
+      } = mem::replace(&mut *call, Call::Done)
+      else {
+        unreachable!()
+      };
+      *call = Call::Started(Generator::body(stack, function, name, arguments, call_span));
+    }
+    let Call::Started(Started(body)) = &mut *call else {
+      unreachable!("Only suspended generators are resumed");
+    };
+
+    // The generator's statements and calls don't nest inside the ones around
+    // them, so the profiler only sees the resume as a whole
+    let profiler = context.profiler.take();
+    let state = State {
+      scope: scope.split_off(0),
+      context: mem::replace(context, Context::placeholder()),
+    };
+
+    let outer = stack::limit();
+    let result = body.resume(state);
+    stack::switch(outer);
+
+    let (resumed, state) = match result {
+      CoroutineResult::Yield((value, state)) => (Ok(Resumed::Yielded(value)), state),
+      CoroutineResult::Return((result, state)) => {
+        *call = Call::Done;
+        (result.map(Resumed::Returned), state)
+      }
+    };
+    scope.append(state.scope);
+    *context = state.context;
+    context.profiler = profiler;
+    resumed
+  }
+}
+
+// Suspends the innermost running generator, handing `value` to whatever
+// resumed it, until it's resumed again
+pub(crate) fn suspend(
+  value: Value,
+  span: Range<usize>,
+  scope: &mut Scope,
+  context: &mut Context,
+) -> Result<(), EvaluationError> {
+  let Some(running) = RUNNING.with_borrow_mut(|running| running.pop()) else {
+    return error(EvaluationErrorKind::YieldOutsideGenerator, span);
+  };
+
+  let frames = scope.split_off(running.frames);
+  let calls = context.call_stack.split_off(running.calls);
+  let outer = State {
+    scope: scope.split_off(0),
+    context: mem::replace(context, Context::placeholder()),
+  };
+
+  let limit = stack::limit();
+  // SAFETY: the yielder belongs to the generator that's running this, which
+  // can't have finished yet
+  let yielder = unsafe { &*running.yielder };
+  let state = yielder.suspend((value, outer));
+  stack::switch(limit);
+
+  RUNNING.with_borrow_mut(|stack| {
+    stack.push(Running {
+      yielder: running.yielder,
+      frames: state.scope.frame_count(),
+      calls: state.context.call_stack.len(),
+    })
+  });
+  scope.append(state.scope);
+  scope.append(frames);
+  *context = state.context;
+  context.call_stack.extend(calls);
+  Ok(())
+}
+
+impl fmt::Debug for Generator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Generator({})", self.status())
+  }
+}
+
+impl PartialEq for Generator {
+  fn eq(&self, other: &Self) -> bool {
+    Rc::ptr_eq(&self.0, &other.0)
+  }
+}
@@ -0,0 +1,11 @@
+use super::{context::Context, scope::Scope};
+use crate::parser::ast::Statement;
+use std::fmt::Debug;
+
+// Lets a host watch a program as it runs, and stop it to look around. Set
+// `Context::hook` to install one. While a hook is being called it's taken out
+// of the context, so anything it evaluates runs without it
+pub trait Hook: Debug {
+  // Called before each statement runs, with the scope it runs in
+  fn before_statement(&mut self, statement: &Statement, scope: &mut Scope, context: &mut Context);
+}
@@ -0,0 +1,37 @@
+use std::{
+  fmt::Debug,
+  io::{stdin, stdout, Write},
+};
+
+// Where `print` and `println` write to, where `readln` reads from, and what
+// `exit` does. Hosts that use stdio for something else, like the debug
+// adapter, can swap in their own
+pub trait Io: Debug {
+  fn write(&mut self, text: &str);
+  // A line without its line ending, or `None` at the end of input
+  fn read_line(&mut self) -> Option<String>;
+  fn exit(&mut self, code: i32) -> !;
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StandardIo;
+
+impl Io for StandardIo {
+  fn write(&mut self, text: &str) {
+    let mut stdout = stdout();
+    stdout.write_all(text.as_bytes()).unwrap();
+    stdout.flush().unwrap();
+  }
+
+  fn read_line(&mut self) -> Option<String> {
+    let mut input = String::new();
+    match stdin().read_line(&mut input).expect("Failed to read line") {
+      0 => None,
+      _ => Some(input.trim_end_matches(['\n', '\r']).to_string()),
+    }
+  }
+
+  fn exit(&mut self, code: i32) -> ! {
+    std::process::exit(code)
+  }
+}
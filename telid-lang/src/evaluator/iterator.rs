@@ -0,0 +1,59 @@
+use super::{
+  context::Context,
+  generator::{Generator, Resumed},
+  range::NumberRange,
+  scope::Scope,
+  value::Value,
+};
+use crate::error::EvaluationError;
+use std::{ops::Range, vec};
+
+// What `for` loops over, one element at a time, so that neither a range nor
+// a generator is made into an array first
+pub(crate) enum Elements {
+  Array(vec::IntoIter<Value>),
+  // The string, and the byte offset of the next character
+  String(String, usize),
+  Range(NumberRange, usize),
+  Generator(Generator),
+}
+
+impl Elements {
+  // Gives the value back if it can't be looped over
+  pub(crate) fn new(value: Value) -> Result<Self, Value> {
+    Ok(match value {
+      Value::Array(array) => Elements::Array(array.into_iter()),
+      Value::String(string) => Elements::String(string, 0),
+      Value::Range(range) => Elements::Range(range, 0),
+      Value::Generator(generator) => Elements::Generator(generator),
+      value => return Err(value),
+    })
+  }
+
+  // Generators are resumed for their next element, until they return
+  pub(crate) fn next(
+    &mut self,
+    span: &Range<usize>,
+    scope: &mut Scope,
+    context: &mut Context,
+  ) -> Result<Option<Value>, EvaluationError> {
+    Ok(match self {
+      Elements::Array(array) => array.next(),
+      Elements::String(string, offset) => string[*offset..].chars().next().map(|character| {
+        *offset += character.len_utf8();
+        Value::String(character.to_string())
+      }),
+      Elements::Range(range, index) => range.get(*index).map(|number| {
+        *index += 1;
+        Value::Number(number)
+      }),
+      Elements::Generator(generator) if generator.status() == "suspended" => {
+        match generator.resume(span, scope, context)? {
+          Resumed::Yielded(value) => Some(value),
+          Resumed::Returned(_) => None,
+        }
+      }
+      Elements::Generator(_) => None,
+    })
+  }
+}
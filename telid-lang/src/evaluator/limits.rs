@@ -0,0 +1,226 @@
+use super::{clock::Clock, util::error, value::Value};
+use crate::error::{EvaluationError, EvaluationErrorKind};
+use std::{mem::size_of, ops::Range, time::Duration};
+
+// Bounds on what a program can do, for running code that isn't trusted.
+// Only call depth is limited by default. A program that goes past a limit
+// fails with an error, like it would for any other mistake
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+  // How many statements can run, counting each time a loop or a call runs one
+  pub fuel: Option<u64>,
+  // How long the program can run for, by `Context::clock`
+  pub time: Option<Duration>,
+  // How many calls to Telid functions can be active at once. Each one takes
+  // some of the stack, which grows until memory runs out without a limit
+  pub call_depth: Option<usize>,
+  // The most characters a string, or elements an array, can have
+  pub value_size: Option<usize>,
+  // Roughly how many bytes of strings and arrays the program can create over
+  // its whole run
+  pub memory: Option<usize>,
+  // How many generators can have started and not finished at once. Each one
+  // has a stack of its own
+  pub generators: Option<usize>,
+  pub capabilities: Capabilities,
+}
+
+// Builtins that reach outside the program, which a sandbox can take away.
+// Telid has no builtins for files or processes, so `exit` is the only one
+// that can affect the host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+  // `exit`, which ends the host's process
+  pub exit: bool,
+  // `readln`
+  pub stdin: bool,
+  // `sleep`, which blocks the host's thread
+  pub sleep: bool,
+}
+
+impl Default for Capabilities {
+  fn default() -> Self {
+    Capabilities {
+      exit: true,
+      stdin: true,
+      sleep: true,
+    }
+  }
+}
+
+impl Capabilities {
+  pub fn none() -> Self {
+    Capabilities {
+      exit: false,
+      stdin: false,
+      sleep: false,
+    }
+  }
+}
+
+impl Default for Limits {
+  fn default() -> Self {
+    Limits {
+      fuel: None,
+      time: None,
+      call_depth: Some(Limits::CALL_DEPTH),
+      value_size: None,
+      memory: None,
+      generators: None,
+      capabilities: Capabilities::default(),
+    }
+  }
+}
+
+impl Limits {
+  pub const CALL_DEPTH: usize = 10_000;
+
+  // Generous for small scripts, and small enough that a host running many
+  // of them stays responsive
+  pub fn sandbox() -> Self {
+    Limits {
+      fuel: Some(10_000_000),
+      time: Some(Duration::from_secs(5)),
+      call_depth: Some(1000),
+      value_size: Some(1 << 20),
+      memory: Some(256 << 20),
+      generators: Some(1000),
+      capabilities: Capabilities::none(),
+    }
+  }
+
+  // Called before a Telid function is called, with the number of calls
+  // already active
+  pub(crate) fn call(&self, depth: usize, span: &Range<usize>) -> Result<(), EvaluationError> {
+    match self.call_depth {
+      Some(call_depth) if depth >= call_depth => error(
+        EvaluationErrorKind::CallDepthExceeded(call_depth),
+        span.clone(),
+      ),
+      _ => Ok(()),
+    }
+  }
+
+  // Checks a size before something that big is built
+  pub(crate) fn size(&self, size: usize, span: &Range<usize>) -> Result<(), EvaluationError> {
+    match self.value_size {
+      Some(value_size) if size > value_size => error(
+        EvaluationErrorKind::ValueTooLarge(size, value_size),
+        span.clone(),
+      ),
+      _ => Ok(()),
+    }
+  }
+}
+
+// How much of its limits a program has used
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Usage {
+  fuel: u64,
+  // When the first statement ran, by the clock's `monotonic`
+  started: Option<f64>,
+  allocated: usize,
+}
+
+impl Usage {
+  // Called before every statement
+  pub(crate) fn statement(
+    &mut self,
+    limits: &Limits,
+    clock: &dyn Clock,
+    span: &Range<usize>,
+  ) -> Result<(), EvaluationError> {
+    if let Some(fuel) = limits.fuel {
+      self.fuel += 1;
+      if self.fuel > fuel {
+        return error(EvaluationErrorKind::OutOfFuel(fuel), span.clone());
+      }
+    }
+
+    if let Some(time) = limits.time {
+      let now = clock.monotonic();
+      let started = *self.started.get_or_insert(now);
+      if now - started > time.as_secs_f64() {
+        return error(
+          EvaluationErrorKind::TimeLimitExceeded(time.as_secs_f64()),
+          span.clone(),
+        );
+      }
+    }
+
+    Ok(())
+  }
+
+  // Fails instead of sleeping past the time limit
+  pub(crate) fn sleep(
+    &mut self,
+    limits: &Limits,
+    clock: &dyn Clock,
+    duration: Duration,
+    span: Range<usize>,
+  ) -> Result<(), EvaluationError> {
+    if let Some(time) = limits.time {
+      let now = clock.monotonic();
+      let started = *self.started.get_or_insert(now);
+      if now + duration.as_secs_f64() - started > time.as_secs_f64() {
+        return error(
+          EvaluationErrorKind::TimeLimitExceeded(time.as_secs_f64()),
+          span,
+        );
+      }
+    }
+
+    Ok(())
+  }
+
+  // Called before a loop adds the `length`th element to the array it makes,
+  // so that one that doesn't end fails before the array gets too big
+  pub(crate) fn element(
+    &mut self,
+    limits: &Limits,
+    length: usize,
+    span: &Range<usize>,
+  ) -> Result<(), EvaluationError> {
+    limits.size(length, span)?;
+    self.allocate(limits, size_of::<Value>(), span)
+  }
+
+  // Called with every string and array an operator or a builtin makes
+  pub(crate) fn value(
+    &mut self,
+    limits: &Limits,
+    value: &Value,
+    span: &Range<usize>,
+  ) -> Result<(), EvaluationError> {
+    if limits.value_size.is_none() && limits.memory.is_none() {
+      return Ok(());
+    }
+
+    let (size, bytes) = match value {
+      Value::String(string) => (string.chars().count(), string.len()),
+      Value::Array(array) => (array.len(), array.len() * size_of::<Value>()),
+      _ => return Ok(()),
+    };
+    limits.size(size, span)?;
+    self.allocate(limits, bytes, span)
+  }
+
+  fn allocate(
+    &mut self,
+    limits: &Limits,
+    bytes: usize,
+    span: &Range<usize>,
+  ) -> Result<(), EvaluationError> {
+    if let Some(memory) = limits.memory {
+      self.allocated += bytes;
+      if self.allocated > memory {
+        return error(
+          EvaluationErrorKind::MemoryLimitExceeded(memory),
+          span.clone(),
+        );
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -1,5 +1,19 @@
+pub mod clock;
+pub mod context;
+pub mod coverage;
+#[allow(clippy::module_inception)]
 mod evaluator;
+pub mod generator;
+pub mod hook;
+pub mod io;
+mod iterator;
+pub mod limits;
+pub mod pattern;
+pub mod profiler;
+pub mod random;
+pub mod range;
 pub mod scope;
+mod stack;
 mod util;
 pub mod value;
 
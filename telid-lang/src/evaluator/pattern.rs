@@ -0,0 +1,79 @@
+use super::{context::Context, util::error, value::Value};
+use crate::error::{EvaluationError, EvaluationErrorKind};
+use regex::{Captures, Regex};
+use std::{fmt, ops::Range};
+
+// A compiled regular expression. Created once by `regex(pattern)` and then
+// reused by every regex_* call it is passed to
+#[derive(Debug, Clone)]
+pub struct Pattern(pub Regex);
+
+impl Pattern {
+  pub fn new(pattern: &str, span: Range<usize>) -> Result<Pattern, EvaluationError> {
+    match Regex::new(pattern) {
+      Ok(regex) => Ok(Pattern(regex)),
+      Err(regex_error) => {
+        // Syntax errors come with their own ascii art pointing into the
+        // pattern; the report already points at the call, so keep the last line
+        let message = regex_error.to_string();
+        let message = message.lines().last().unwrap_or_default();
+        error(
+          EvaluationErrorKind::InvalidRegex(message.trim_start_matches("error: ").to_string()),
+          span,
+        )
+      }
+    }
+  }
+}
+
+impl PartialEq for Pattern {
+  fn eq(&self, other: &Self) -> bool {
+    self.0.as_str() == other.0.as_str()
+  }
+}
+
+impl fmt::Display for Pattern {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "/{}/", self.0.as_str())
+  }
+}
+
+// Accepts either a `Regex` value or a string pattern. String patterns are
+// compiled on first use and cached on the context
+pub fn pattern(
+  context: &mut Context,
+  value: &Value,
+  span: Range<usize>,
+) -> Result<Pattern, EvaluationError> {
+  match value {
+    Value::Regex(pattern) => Ok(pattern.clone()),
+    Value::String(string) => match context.patterns.get(string) {
+      Some(pattern) => Ok(pattern.clone()),
+      None => {
+        let pattern = Pattern::new(string, span)?;
+        context.patterns.insert(string.clone(), pattern.clone());
+        Ok(pattern)
+      }
+    },
+    _ => error(
+      EvaluationErrorKind::InvalidType(
+        value.as_ref().to_string(),
+        vec![String::from("Regex"), String::from("String")],
+      ),
+      span,
+    ),
+  }
+}
+
+// Group 0 is the whole match; groups that didn't participate are void
+pub fn captures_to_value(captures: Captures) -> Value {
+  Value::Array(
+    captures
+      .iter()
+      .map(|group| match group {
+        Some(group) => Value::String(group.as_str().to_string()),
+        None => Value::Void,
+      })
+      .collect(),
+  )
+}
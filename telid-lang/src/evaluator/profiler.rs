@@ -0,0 +1,240 @@
+use std::{
+  collections::HashMap,
+  ops::Range,
+  time::{Duration, Instant},
+};
+
+// Name the folded stacks give to time spent outside any function
+const ROOT: &str = "main";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FunctionProfile {
+  pub calls: u64,
+  // Time from being called to returning. Recursive calls are only counted
+  // once, so this never adds up to more than the whole run
+  pub inclusive: Duration,
+  // Time not spent in other functions it called
+  pub exclusive: Duration,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatementProfile {
+  pub count: u64,
+  // Time not spent in statements nested in it, including the bodies of the
+  // functions it calls
+  pub time: Duration,
+}
+
+// Something running, and how much of its time went to things it started
+#[derive(Debug)]
+struct Running<T> {
+  item: T,
+  start: Instant,
+  children: Duration,
+}
+
+// A stack of calls, as a node in the tree of every stack seen so far. Calls
+// find their stack from their caller's, without looking at the whole stack
+#[derive(Debug)]
+struct Stack {
+  name: String,
+  parent: Option<usize>,
+  children: HashMap<String, usize>,
+  // Exclusive time of the calls with this stack, once one has returned
+  time: Option<Duration>,
+}
+
+// Records where a program spends its time. Set `Context::profiler` to one and
+// every function call and statement gets timed
+#[derive(Debug)]
+pub struct Profiler {
+  // With the index of their stack
+  calls: Vec<Running<usize>>,
+  running_statements: Vec<Running<Range<usize>>>,
+  pub functions: HashMap<String, FunctionProfile>,
+  // By span, in tokens
+  pub statements: HashMap<Range<usize>, StatementProfile>,
+  // The root's stack is first
+  stacks: Vec<Stack>,
+  // How many calls to each function are running, to tell recursive ones
+  running_functions: HashMap<String, usize>,
+}
+
+impl Profiler {
+  pub fn new() -> Self {
+    Profiler {
+      calls: vec![Running {
+        item: 0,
+        start: Instant::now(),
+        children: Duration::ZERO,
+      }],
+      running_statements: Vec::new(),
+      functions: HashMap::new(),
+      statements: HashMap::new(),
+      stacks: vec![Stack {
+        name: String::from(ROOT),
+        parent: None,
+        children: HashMap::new(),
+        time: None,
+      }],
+      running_functions: HashMap::new(),
+    }
+  }
+
+  pub(crate) fn enter_call(&mut self, name: &str) {
+    let caller = self.calls.last().map_or(0, |call| call.item);
+    let stack = match self.stacks[caller].children.get(name) {
+      Some(stack) => *stack,
+      None => {
+        let stack = self.stacks.len();
+        self.stacks.push(Stack {
+          name: name.to_string(),
+          parent: Some(caller),
+          children: HashMap::new(),
+          time: None,
+        });
+        self.stacks[caller].children.insert(name.to_string(), stack);
+        stack
+      }
+    };
+
+    match self.running_functions.get_mut(name) {
+      Some(running) => *running += 1,
+      None => {
+        self.running_functions.insert(name.to_string(), 1);
+      }
+    }
+
+    self.calls.push(Running {
+      item: stack,
+      start: Instant::now(),
+      children: Duration::ZERO,
+    });
+  }
+
+  pub(crate) fn exit_call(&mut self) {
+    // The root is only ended by `finish`
+    if self.calls.len() < 2 {
+      return;
+    }
+    let (exclusive, elapsed) = self.record_call();
+
+    let name = &self.stacks[self.calls.pop().unwrap().item].name;
+    let running = self.running_functions.get_mut(name).unwrap();
+    *running -= 1;
+    let recursive = *running > 0;
+
+    if !self.functions.contains_key(name) {
+      self
+        .functions
+        .insert(name.clone(), FunctionProfile::default());
+    }
+    let profile = self.functions.get_mut(name).unwrap();
+    profile.calls += 1;
+    profile.exclusive += exclusive;
+    if !recursive {
+      profile.inclusive += elapsed;
+    }
+  }
+
+  // Attributes the innermost call's exclusive time to its stack, and its
+  // whole time to its caller. Returns both
+  fn record_call(&mut self) -> (Duration, Duration) {
+    let call = self.calls.last().unwrap();
+    let elapsed = call.start.elapsed();
+    let exclusive = elapsed.saturating_sub(call.children);
+
+    let time = &mut self.stacks[call.item].time;
+    *time = Some(time.unwrap_or_default() + exclusive);
+
+    if let Some(caller) = self.calls.len().checked_sub(2) {
+      self.calls[caller].children += elapsed;
+    }
+    (exclusive, elapsed)
+  }
+
+  pub(crate) fn enter_statement(&mut self, span: Range<usize>) {
+    // The clock starts with the first statement, so whatever the host did
+    // before running the program isn't counted
+    if self.statements.is_empty() && self.running_statements.is_empty() {
+      if let [root] = self.calls.as_mut_slice() {
+        root.start = Instant::now();
+      }
+    }
+
+    self.running_statements.push(Running {
+      item: span,
+      start: Instant::now(),
+      children: Duration::ZERO,
+    });
+  }
+
+  pub(crate) fn exit_statement(&mut self) {
+    let Some(statement) = self.running_statements.pop() else {
+      return;
+    };
+    let elapsed = statement.start.elapsed();
+    if let Some(parent) = self.running_statements.last_mut() {
+      parent.children += elapsed;
+    }
+
+    let profile = self.statements.entry(statement.item).or_default();
+    profile.count += 1;
+    profile.time += elapsed.saturating_sub(statement.children);
+  }
+
+  // Stops the clock on time spent outside any function. Call it once the
+  // program has ended
+  pub fn finish(&mut self) {
+    if let [_] = self.calls.as_slice() {
+      self.record_call();
+      self.calls.clear();
+    }
+  }
+
+  // Functions, slowest first by exclusive time
+  pub fn functions(&self) -> Vec<(&str, &FunctionProfile)> {
+    let mut functions = self
+      .functions
+      .iter()
+      .map(|(name, profile)| (name.as_str(), profile))
+      .collect::<Vec<_>>();
+    functions.sort_by(|a, b| b.1.exclusive.cmp(&a.1.exclusive).then(a.0.cmp(b.0)));
+    functions
+  }
+
+  // Statements, slowest first
+  pub fn statements(&self) -> Vec<(&Range<usize>, &StatementProfile)> {
+    let mut statements = self.statements.iter().collect::<Vec<_>>();
+    statements.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(a.0.start.cmp(&b.0.start)));
+    statements
+  }
+
+  // Stacks in the folded format flame graph tools read: `main;f;g 120` for
+  // 120 microseconds spent in `g` called by `f`, one stack per line
+  pub fn folded(&self) -> String {
+    let mut lines = self
+      .stacks
+      .iter()
+      .filter_map(|stack| {
+        let time = stack.time?;
+        let mut names = vec![stack.name.as_str()];
+        let mut parent = stack.parent;
+        while let Some(index) = parent {
+          names.push(&self.stacks[index].name);
+          parent = self.stacks[index].parent;
+        }
+        names.reverse();
+        Some(format!("{} {}\n", names.join(";"), time.as_micros()))
+      })
+      .collect::<Vec<_>>();
+    lines.sort();
+    lines.concat()
+  }
+}
+
+impl Default for Profiler {
+  fn default() -> Self {
+    Profiler::new()
+  }
+}
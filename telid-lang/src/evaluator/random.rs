@@ -0,0 +1,73 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// SplitMix64 (https://prng.di.unimi.it/splitmix64.c). It's tiny, has no
+// dependencies and a given seed produces the same sequence on every platform,
+// which is all we need for reproducible scripts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rng {
+  state: u64,
+}
+
+impl Rng {
+  pub fn new(seed: u64) -> Self {
+    Rng { state: seed }
+  }
+
+  pub fn from_entropy() -> Self {
+    let nanos = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|duration| duration.as_nanos() as u64)
+      .unwrap_or(0);
+    Rng::new(nanos)
+  }
+
+  pub fn seed(&mut self, seed: u64) {
+    self.state = seed;
+  }
+
+  pub fn next_u64(&mut self) -> u64 {
+    self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = self.state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+  }
+
+  // Uniform in [0, 1)
+  pub fn next_f64(&mut self) -> f64 {
+    (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  // Uniform in [0, bound)
+  pub fn below(&mut self, bound: u64) -> u64 {
+    if bound == 0 {
+      return 0;
+    }
+
+    // Rejection sampling to avoid modulo bias
+    let zone = u64::MAX - u64::MAX % bound;
+    loop {
+      let value = self.next_u64();
+      if value < zone {
+        return value % bound;
+      }
+    }
+  }
+
+  // Uniform in [low, high], both ends inclusive like the `..` operator
+  pub fn between(&mut self, low: i64, high: i64) -> i64 {
+    let width = high.wrapping_sub(low) as u64;
+    match width.checked_add(1) {
+      Some(bound) => low.wrapping_add(self.below(bound) as i64),
+      None => self.next_u64() as i64,
+    }
+  }
+
+  // Fisher-Yates
+  pub fn shuffle<T>(&mut self, items: &mut [T]) {
+    for i in (1..items.len()).rev() {
+      let j = self.below(i as u64 + 1) as usize;
+      items.swap(i, j);
+    }
+  }
+}
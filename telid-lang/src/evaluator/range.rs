@@ -0,0 +1,95 @@
+use super::{limits::Limits, util::error, value::Value};
+use crate::error::{EvaluationError, EvaluationErrorKind};
+use std::{borrow::Cow, fmt, ops::Range};
+
+// Numbers `step` apart, starting at `start`. Only the bounds are stored, so
+// a range of any length takes the same space until it's made into an array
+#[derive(Debug, Clone, Copy)]
+pub struct NumberRange {
+  pub start: f64,
+  pub step: f64,
+  pub len: usize,
+}
+
+impl NumberRange {
+  // From `start` to `end`, inclusive, or as close to `end` as a whole number
+  // of steps gets. None if `step` is 0 or goes away from `end`
+  pub fn new(start: f64, end: f64, step: f64) -> Option<Self> {
+    let steps = (end - start) / step;
+    if step == 0.0 || steps.is_nan() || steps < 0.0 {
+      return None;
+    }
+
+    Some(NumberRange {
+      start,
+      step,
+      len: (steps.floor() as usize).saturating_add(1),
+    })
+  }
+
+  pub fn get(&self, index: usize) -> Option<f64> {
+    (index < self.len).then_some(self.start + index as f64 * self.step)
+  }
+
+  // The elements from `start` up to but not including `end`, which have to
+  // be in bounds
+  pub fn slice(&self, start: usize, end: usize) -> Self {
+    NumberRange {
+      start: self.start + start as f64 * self.step,
+      step: self.step,
+      len: end - start,
+    }
+  }
+
+  pub fn iter(&self) -> impl Iterator<Item = f64> + '_ {
+    (0..self.len).map(|index| self.start + index as f64 * self.step)
+  }
+
+  // Makes the range into an array, if the limits allow one that long
+  pub(crate) fn to_array(
+    self,
+    limits: &Limits,
+    span: &Range<usize>,
+  ) -> Result<Vec<Value>, EvaluationError> {
+    limits.size(self.len, span)?;
+    Ok(self.iter().map(Value::Number).collect())
+  }
+}
+
+// The elements of an array, or of a range made into one, for the builtins
+// that take either
+pub(crate) fn elements<'a>(
+  value: &'a Value,
+  limits: &Limits,
+  span: &Range<usize>,
+) -> Result<Cow<'a, [Value]>, EvaluationError> {
+  match value {
+    Value::Array(array) => Ok(Cow::Borrowed(array)),
+    Value::Range(range) => range.to_array(limits, span).map(Cow::Owned),
+    _ => error(
+      EvaluationErrorKind::InvalidType(value.as_ref().to_string(), vec![String::from("Array")]),
+      span.clone(),
+    ),
+  }
+}
+
+// Ranges with the same numbers are equal, however they were made
+impl PartialEq for NumberRange {
+  fn eq(&self, other: &Self) -> bool {
+    self.len == other.len
+      && (self.len == 0 || self.start == other.start)
+      && (self.len <= 1 || self.step == other.step)
+  }
+}
+
+// As the code that would make it
+impl fmt::Display for NumberRange {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let end = self.start + (self.len as f64 - 1.0) * self.step;
+    if self.step == 1.0 && self.len > 0 {
+      write!(f, ".. {} {}", self.start, end)
+    } else {
+      write!(f, "range({}, {}, {})", self.start, end, self.step)
+    }
+  }
+}
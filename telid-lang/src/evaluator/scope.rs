@@ -1,54 +1,288 @@
 use super::{
-  util::error,
+  clock::format_time,
+  generator::Generator,
+  pattern::{captures_to_value, pattern, Pattern},
+  range::{elements, NumberRange},
+  util::{describe, error},
   value::{Value, Variable},
 };
-use crate::error::EvaluationErrorKind;
-use scoped_stack::ScopedStack;
-use std::io::{stdin, stdout, Write};
+use crate::{
+  error::EvaluationErrorKind,
+  parser::ast::{Binding, Identifier},
+};
+use std::{collections::HashMap, time::Duration};
+
+// Variables by name, in frames that blocks, loops and function calls push and
+// pop. Each frame keeps its variables in the order they were declared, so
+// that the resolver's bindings can find them without hashing, and each name
+// keeps where every variable with it is, so that the innermost one is found
+// without looking through every frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scope {
+  frames: Vec<Frame>,
+  // The frame, counting from the outermost one, and the slot in it of each
+  // variable with the name, innermost last. Names stay when their variables
+  // are gone, since they're likely to be declared again
+  names: HashMap<String, Vec<(usize, usize)>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+struct Frame {
+  variables: Vec<(String, Variable)>,
+}
+
+impl Scope {
+  pub fn new() -> Self {
+    Scope {
+      frames: vec![Frame::default()],
+      names: HashMap::new(),
+    }
+  }
+
+  pub fn push_scope(&mut self) {
+    self.frames.push(Frame::default());
+  }
+
+  pub fn pop_scope(&mut self) {
+    if self.frames.len() > 1 {
+      let frame = self.frames.pop().unwrap();
+      self.forget(&frame);
+    }
+  }
+
+  // Declares `name` in the innermost frame, replacing any variable with the
+  // same name that was already declared there
+  pub fn insert(&mut self, name: String, variable: Variable) {
+    let index = self.frames.len() - 1;
+    let frame = &mut self.frames[index];
+    let positions = match self.names.get_mut(&name) {
+      Some(positions) => positions,
+      None => self.names.entry(name.clone()).or_default(),
+    };
+    match positions.last() {
+      Some(&(last, slot)) if last == index => frame.variables[slot].1 = variable,
+      _ => {
+        positions.push((index, frame.variables.len()));
+        frame.variables.push((name, variable));
+      }
+    }
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Variable> {
+    self
+      .position(name)
+      .map(|(depth, slot)| self.at(depth, slot))
+  }
+
+  // Looks `identifier` up through its binding if it has one, and by name
+  // otherwise or if the binding doesn't point at a variable with that name
+  pub fn lookup(&self, identifier: &Identifier) -> Option<&Variable> {
+    self
+      .bound(identifier)
+      .or_else(|| self.position(&identifier.0))
+      .map(|(depth, slot)| self.at(depth, slot))
+  }
+
+  // Replaces the outermost variable called `name`, or declares it in the
+  // outermost frame if there isn't one
+  pub fn insert_existing(&mut self, name: String, variable: Variable) {
+    match self
+      .names
+      .get(&name)
+      .and_then(|positions| positions.first())
+    {
+      Some(&(frame, slot)) => self.frames[frame].variables[slot].1 = variable,
+      None => {
+        let frame = &mut self.frames[0];
+        self
+          .names
+          .entry(name.clone())
+          .or_default()
+          .push((0, frame.variables.len()));
+        frame.variables.push((name, variable));
+      }
+    }
+  }
+
+  // Frames out from the innermost one, and the slot in that frame, of the
+  // innermost variable called `name`
+  pub fn position(&self, name: &str) -> Option<(usize, usize)> {
+    let &(frame, slot) = self.names.get(name)?.last()?;
+    Some((self.frames.len() - 1 - frame, slot))
+  }
+
+  // The number of frames, including the outermost one
+  pub(crate) fn frame_count(&self) -> usize {
+    self.frames.len()
+  }
+
+  // Takes every frame from the `base`th on, for a generator to put back on
+  // top of whatever scope it's resumed in
+  pub(crate) fn split_off(&mut self, base: usize) -> Scope {
+    let mut scope = Scope {
+      frames: Vec::new(),
+      names: HashMap::new(),
+    };
+    if base == 0 {
+      std::mem::swap(self, &mut scope);
+      return scope;
+    }
+
+    for frame in self.frames.split_off(base) {
+      self.forget(&frame);
+      scope.push_frame(frame);
+    }
+    scope
+  }
+
+  pub(crate) fn append(&mut self, scope: Scope) {
+    if self.frames.is_empty() {
+      *self = scope;
+      return;
+    }
+
+    for frame in scope.frames {
+      self.push_frame(frame);
+    }
+  }
+
+  // Takes the variables out of every frame from the `base`th on, leaving them
+  // empty, and returns the innermost one with each name
+  pub(crate) fn take_variables(&mut self, base: usize) -> Vec<(String, Variable)> {
+    let mut slots: HashMap<String, usize> = HashMap::new();
+    let mut visible: Vec<(String, Variable)> = Vec::new();
+    for index in base..self.frames.len() {
+      let frame = std::mem::take(&mut self.frames[index]);
+      self.forget(&frame);
+      for (name, variable) in frame.variables {
+        match slots.get(&name) {
+          Some(slot) => visible[*slot].1 = variable,
+          None => {
+            slots.insert(name.clone(), visible.len());
+            visible.push((name, variable));
+          }
+        }
+      }
+    }
+    visible
+  }
+
+  fn push_frame(&mut self, frame: Frame) {
+    let index = self.frames.len();
+    for (slot, (name, _)) in frame.variables.iter().enumerate() {
+      self
+        .names
+        .entry(name.clone())
+        .or_default()
+        .push((index, slot));
+    }
+    self.frames.push(frame);
+  }
+
+  // Drops the positions of a frame's variables, which are the innermost ones
+  // with their names
+  fn forget(&mut self, frame: &Frame) {
+    for (name, _) in &frame.variables {
+      if let Some(positions) = self.names.get_mut(name) {
+        positions.pop();
+      }
+    }
+  }
 
-pub type Scope = ScopedStack<String, Variable>;
+  // The number of variables declared in the innermost frame
+  pub fn len(&self) -> usize {
+    self.frames.last().unwrap().variables.len()
+  }
 
-// TODO: Pass spans to all Rust functions
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  // The variables in each frame in the order they were declared, innermost
+  // frame first
+  pub fn frames(&self) -> impl Iterator<Item = &[(String, Variable)]> {
+    self
+      .frames
+      .iter()
+      .rev()
+      .map(|frame| frame.variables.as_slice())
+  }
+
+  fn at(&self, depth: usize, slot: usize) -> &Variable {
+    &self.frames[self.frames.len() - 1 - depth].variables[slot].1
+  }
+
+  fn bound(&self, identifier: &Identifier) -> Option<(usize, usize)> {
+    let Binding::Static { depth, slot } = identifier.2 else {
+      return None;
+    };
+    let frame = self.frames.len().checked_sub(depth + 1)?;
+    let (name, _) = self.frames[frame].variables.get(slot)?;
+    (*name == identifier.0).then_some((depth, slot))
+  }
+}
+
+impl Default for Scope {
+  fn default() -> Self {
+    Scope::new()
+  }
+}
 
 pub fn default() -> Scope {
   let mut scope = Scope::new();
 
-  scope.insert(
+  for (name, variable) in builtins() {
+    scope.insert(name, variable);
+  }
+
+  scope
+}
+
+// Every Rust function available to Telid code, in the order they're documented
+pub fn builtins() -> Vec<(String, Variable)> {
+  let mut builtins = Vec::new();
+
+  builtins.push((
     String::from("println"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 1,
-        function: |_, parameters| {
-          println!("{}", parameters[0].to_string());
+        function: |context, _, parameters, _| {
+          context.io.write(&format!("{}\n", parameters[0]));
           Ok(Value::Void)
         },
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
+  builtins.push((
     String::from("print"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 1,
-        function: |_, parameters| {
-          print!("{}", parameters[0].to_string());
-          stdout().flush().unwrap();
+        function: |context, _, parameters, _| {
+          context.io.write(&format!("{}", parameters[0]));
           Ok(Value::Void)
         },
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
+  builtins.push((
     String::from("exit"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 1,
-        function: |span, parameters| match parameters[0] {
-          Value::Number(code) => std::process::exit(code as i32),
+        function: |context, span, parameters, _| match parameters[0] {
+          _ if !context.limits.capabilities.exit => error(
+            EvaluationErrorKind::CapabilityDenied(String::from("exit")),
+            span,
+          ),
+          Value::Number(code) => context.io.exit(code as i32),
           _ => error(
             EvaluationErrorKind::InvalidType(
               parameters[0].as_ref().to_string(),
@@ -59,30 +293,54 @@ pub fn default() -> Scope {
         },
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
+  builtins.push((
     String::from("readln"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 0,
-        function: |_, _| {
-          let mut input = String::new();
-          stdin().read_line(&mut input).expect("Failed to read line");
+        function: |context, span, _, _| {
+          if !context.limits.capabilities.stdin {
+            return error(
+              EvaluationErrorKind::CapabilityDenied(String::from("readln")),
+              span,
+            );
+          }
+
+          let input = context.io.read_line().unwrap_or_default();
           Ok(Value::String(input.trim().to_string()))
         },
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
+  builtins.push((
+    String::from("args"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 0,
+        function: |context, _, _, _| {
+          Ok(Value::Array(
+            context.args.iter().cloned().map(Value::String).collect(),
+          ))
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
     String::from("assert"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 1,
-        function: |span, parameters| match parameters[0] {
+        function: |_, span, parameters, _| match parameters[0] {
           Value::Boolean(true) => Ok(Value::Void),
           Value::Boolean(false) => error(EvaluationErrorKind::AssertionFailed, span),
           _ => error(
@@ -95,15 +353,59 @@ pub fn default() -> Scope {
         },
       },
       constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("assert_eq"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 2,
+        function: |_, span, parameters, _| {
+          if parameters[0] == parameters[1] {
+            Ok(Value::Void)
+          } else {
+            error(
+              EvaluationErrorKind::AssertionNotEqual(
+                describe(&parameters[0]),
+                describe(&parameters[1]),
+              ),
+              span,
+            )
+          }
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("assert_throws"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |context, span, parameters, call| match call(
+          context,
+          parameters[0].clone(),
+          Vec::new(),
+        ) {
+          Ok(value) => error(EvaluationErrorKind::ExpectedError(describe(&value)), span),
+          Err(_) => Ok(Value::Void),
+        },
+      },
+      constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
+  builtins.push((
     String::from("parse"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 1,
-        function: |span, parameters| match &parameters[0] {
+        function: |_, span, parameters, _| match &parameters[0] {
           Value::String(string) => match string.parse() {
             Ok(number) => Ok(Value::Number(number)),
             Err(_) => Ok(Value::Void),
@@ -118,28 +420,31 @@ pub fn default() -> Scope {
         },
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
+  builtins.push((
     String::from("type"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 1,
-        function: |_, parameters| Ok(Value::String(parameters[0].as_ref().to_string())),
+        function: |_, _, parameters, _| Ok(Value::String(parameters[0].as_ref().to_string())),
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
+  builtins.push((
     String::from("len"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 1,
-        function: |span, parameters| match &parameters[0] {
+        function: |_, span, parameters, _| match &parameters[0] {
           Value::String(string) => Ok(Value::Number(string.len() as f64)),
           Value::Array(array) => Ok(Value::Number(array.len() as f64)),
+          Value::Range(range) => Ok(Value::Number(range.len as f64)),
           _ => error(
             EvaluationErrorKind::InvalidType(
               parameters[0].as_ref().to_string(),
@@ -150,19 +455,21 @@ pub fn default() -> Scope {
         },
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
+  builtins.push((
     String::from("filter"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 2,
-        function: |span, parameters| match &parameters[0] {
-          Value::Array(array) => match &parameters[1] {
+        function: |context, span, parameters, _| {
+          let array = elements(&parameters[0], &context.limits, &span)?;
+          match &parameters[1] {
             Value::String(string) => {
               let mut result = Vec::new();
-              for element in array {
+              for element in array.iter() {
                 if element.as_ref() != string {
                   result.push(element.clone());
                 }
@@ -177,7 +484,183 @@ pub fn default() -> Scope {
               ),
               span,
             ),
-          },
+          }
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("concat"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 2,
+        function: |context, span, parameters, _| {
+          let array = elements(&parameters[0], &context.limits, &span)?;
+          let other = elements(&parameters[1], &context.limits, &span)?;
+
+          let mut result = Vec::new();
+          for element in array.iter() {
+            result.push(element.clone());
+          }
+          for element in other.iter() {
+            result.push(element.clone());
+          }
+
+          Ok(Value::Array(result))
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("range"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 3,
+        function: |_, span, parameters, _| match (&parameters[0], &parameters[1], &parameters[2]) {
+          (Value::Number(start), Value::Number(end), Value::Number(step)) => {
+            match NumberRange::new(*start, *end, *step) {
+              Some(range) => Ok(Value::Range(range)),
+              None => error(EvaluationErrorKind::InvalidStep(*step), span),
+            }
+          }
+          _ => {
+            let found = parameters
+              .iter()
+              .find(|parameter| !matches!(parameter, Value::Number(_)))
+              .unwrap();
+            error(
+              EvaluationErrorKind::InvalidType(
+                found.as_ref().to_string(),
+                vec![String::from("Number")],
+              ),
+              span,
+            )
+          }
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("array"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |context, span, parameters, call| match &parameters[0] {
+          Value::Array(_) => Ok(parameters[0].clone()),
+          Value::String(string) => Ok(Value::Array(
+            string
+              .chars()
+              .map(|character| Value::String(character.to_string()))
+              .collect(),
+          )),
+          Value::Range(range) => Ok(Value::Array(range.to_array(&context.limits, &span)?)),
+          // Everything the generator yields, but not what it returns
+          Value::Generator(generator) => {
+            let mut array = Vec::new();
+            while generator.status() == "suspended" {
+              let value = call(context, parameters[0].clone(), Vec::new())?;
+              if generator.status() == "suspended" {
+                context.limits.size(array.len() + 1, &span)?;
+                array.push(value);
+              }
+            }
+            Ok(Value::Array(array))
+          }
+          _ => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[0].as_ref().to_string(),
+              vec![
+                String::from("Array"),
+                String::from("String"),
+                String::from("Range"),
+                String::from("Generator"),
+              ],
+            ),
+            span,
+          ),
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("random"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 0,
+        function: |context, _, _, _| Ok(Value::Number(context.rng.next_f64())),
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("random_int"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 2,
+        function: |context, span, parameters, _| match (&parameters[0], &parameters[1]) {
+          (Value::Number(low), Value::Number(high)) => {
+            // Only the integers between fractional bounds can be picked, and
+            // there might not be any
+            let (first, last) = (low.ceil(), high.floor());
+            if first > last {
+              return error(EvaluationErrorKind::InvalidRange(*low, *high), span);
+            }
+
+            Ok(Value::Number(
+              context.rng.between(first as i64, last as i64) as f64,
+            ))
+          }
+          (Value::Number(_), _) => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[1].as_ref().to_string(),
+              vec![String::from("Number")],
+            ),
+            span,
+          ),
+          _ => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[0].as_ref().to_string(),
+              vec![String::from("Number")],
+            ),
+            span,
+          ),
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("choice"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |context, span, parameters, _| match &parameters[0] {
+          Value::Array(array) if array.is_empty() => Ok(Value::Void),
+          Value::Array(array) => {
+            let index = context.rng.below(array.len() as u64) as usize;
+            Ok(array[index].clone())
+          }
+          Value::Range(range) if range.len == 0 => Ok(Value::Void),
+          Value::Range(range) => {
+            let index = context.rng.below(range.len as u64) as usize;
+            Ok(Value::Number(range.get(index).unwrap()))
+          }
           _ => error(
             EvaluationErrorKind::InvalidType(
               parameters[0].as_ref().to_string(),
@@ -188,47 +671,366 @@ pub fn default() -> Scope {
         },
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope.insert(
-    String::from("concat"),
+  builtins.push((
+    String::from("shuffle"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |context, span, parameters, _| {
+          let mut result = elements(&parameters[0], &context.limits, &span)?.into_owned();
+          context.rng.shuffle(&mut result);
+          Ok(Value::Array(result))
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("seed"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |context, span, parameters, _| match parameters[0] {
+          Value::Number(seed) => {
+            context.rng.seed(seed as i64 as u64);
+            Ok(Value::Void)
+          }
+          _ => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[0].as_ref().to_string(),
+              vec![String::from("Number")],
+            ),
+            span,
+          ),
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("now"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 0,
+        function: |context, _, _, _| Ok(Value::Number(context.clock.now())),
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("monotonic"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 0,
+        function: |context, _, _, _| Ok(Value::Number(context.clock.monotonic())),
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("sleep"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |context, span, parameters, _| match parameters[0] {
+          _ if !context.limits.capabilities.sleep => error(
+            EvaluationErrorKind::CapabilityDenied(String::from("sleep")),
+            span,
+          ),
+          Value::Number(milliseconds) => {
+            // Negative lengths don't wait at all, but NaN or one too long to
+            // wait for is an error
+            let seconds = match milliseconds < 0.0 {
+              true => 0.0,
+              false => milliseconds / 1000.0,
+            };
+            let Ok(duration) = Duration::try_from_secs_f64(seconds) else {
+              return error(EvaluationErrorKind::InvalidDuration(milliseconds), span);
+            };
+            context
+              .usage
+              .sleep(&context.limits, context.clock.as_ref(), duration, span)?;
+            context.clock.sleep(duration);
+            Ok(Value::Void)
+          }
+          _ => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[0].as_ref().to_string(),
+              vec![String::from("Number")],
+            ),
+            span,
+          ),
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("format_time"),
     Variable {
       value: Value::RustFunction {
         parameter_count: 2,
-        function: |span, parameters| match &parameters[0] {
-          Value::Array(array) => match &parameters[1] {
-            Value::Array(other) => {
-              let mut result = Vec::new();
-              for element in array {
-                result.push(element.clone());
-              }
-              for element in other {
-                result.push(element.clone());
-              }
+        function: |_, span, parameters, _| match (&parameters[0], &parameters[1]) {
+          (Value::Number(timestamp), Value::String(format)) => {
+            Ok(Value::String(format_time(*timestamp, format)))
+          }
+          (Value::Number(_), _) => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[1].as_ref().to_string(),
+              vec![String::from("String")],
+            ),
+            span,
+          ),
+          _ => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[0].as_ref().to_string(),
+              vec![String::from("Number")],
+            ),
+            span,
+          ),
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
 
-              Ok(Value::Array(result))
-            }
+  builtins.push((
+    String::from("regex"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |_, span, parameters, _| match &parameters[0] {
+          Value::String(string) => Ok(Value::Regex(Pattern::new(string, span)?)),
+          Value::Regex(_) => Ok(parameters[0].clone()),
+          _ => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[0].as_ref().to_string(),
+              vec![String::from("String")],
+            ),
+            span,
+          ),
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("regex_match"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 2,
+        function: |context, span, parameters, _| {
+          let pattern = pattern(context, &parameters[0], span.clone())?;
+          match &parameters[1] {
+            Value::String(string) => Ok(match pattern.0.captures(string) {
+              Some(captures) => captures_to_value(captures),
+              None => Value::Void,
+            }),
             _ => error(
               EvaluationErrorKind::InvalidType(
                 parameters[1].as_ref().to_string(),
-                vec![String::from("Array")],
+                vec![String::from("String")],
               ),
               span,
             ),
-          },
+          }
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("regex_find_all"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 2,
+        function: |context, span, parameters, _| {
+          let pattern = pattern(context, &parameters[0], span.clone())?;
+          match &parameters[1] {
+            // Like Python's re.findall: plain strings without groups, capture arrays with them
+            Value::String(string) => Ok(Value::Array(if pattern.0.captures_len() == 1 {
+              pattern
+                .0
+                .find_iter(string)
+                .map(|found| Value::String(found.as_str().to_string()))
+                .collect()
+            } else {
+              pattern
+                .0
+                .captures_iter(string)
+                .map(captures_to_value)
+                .collect()
+            })),
+            _ => error(
+              EvaluationErrorKind::InvalidType(
+                parameters[1].as_ref().to_string(),
+                vec![String::from("String")],
+              ),
+              span,
+            ),
+          }
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("regex_replace"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 3,
+        function: |context, span, parameters, _| {
+          let pattern = pattern(context, &parameters[0], span.clone())?;
+          match (&parameters[1], &parameters[2]) {
+            (Value::String(string), Value::String(replacement)) => Ok(Value::String(
+              pattern
+                .0
+                .replace_all(string, replacement.as_str())
+                .to_string(),
+            )),
+            (Value::String(_), _) => error(
+              EvaluationErrorKind::InvalidType(
+                parameters[2].as_ref().to_string(),
+                vec![String::from("String")],
+              ),
+              span,
+            ),
+            _ => error(
+              EvaluationErrorKind::InvalidType(
+                parameters[1].as_ref().to_string(),
+                vec![String::from("String")],
+              ),
+              span,
+            ),
+          }
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("regex_split"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 2,
+        function: |context, span, parameters, _| {
+          let pattern = pattern(context, &parameters[0], span.clone())?;
+          match &parameters[1] {
+            Value::String(string) => Ok(Value::Array(
+              pattern
+                .0
+                .split(string)
+                .map(|part| Value::String(part.to_string()))
+                .collect(),
+            )),
+            _ => error(
+              EvaluationErrorKind::InvalidType(
+                parameters[1].as_ref().to_string(),
+                vec![String::from("String")],
+              ),
+              span,
+            ),
+          }
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("spawn"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |_, span, parameters, _| match &parameters[0] {
+          Value::Function { .. } | Value::RustFunction { .. } => {
+            Ok(Value::Generator(Generator::new(
+              parameters[0].clone(),
+              String::from("<function passed to spawn>"),
+              Vec::new(),
+              span,
+            )))
+          }
           _ => error(
             EvaluationErrorKind::InvalidType(
               parameters[0].as_ref().to_string(),
-              vec![String::from("Array")],
+              vec![String::from("Function")],
             ),
             span,
           ),
         },
       },
       constant: true,
+      annotation: None,
     },
-  );
+  ));
 
-  scope
+  builtins.push((
+    String::from("resume"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        // Generators are resumed by calling them, which only the caller can do
+        function: |context, span, parameters, call| match &parameters[0] {
+          Value::Generator(_) => call(context, parameters[0].clone(), Vec::new()),
+          _ => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[0].as_ref().to_string(),
+              vec![String::from("Generator")],
+            ),
+            span,
+          ),
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins.push((
+    String::from("status"),
+    Variable {
+      value: Value::RustFunction {
+        parameter_count: 1,
+        function: |_, span, parameters, _| match &parameters[0] {
+          Value::Generator(generator) => Ok(Value::String(generator.status().to_string())),
+          _ => error(
+            EvaluationErrorKind::InvalidType(
+              parameters[0].as_ref().to_string(),
+              vec![String::from("Generator")],
+            ),
+            span,
+          ),
+        },
+      },
+      constant: true,
+      annotation: None,
+    },
+  ));
+
+  builtins
 }
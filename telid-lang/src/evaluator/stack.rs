@@ -0,0 +1,64 @@
+use super::{generator, util::error};
+use crate::error::{EvaluationError, EvaluationErrorKind};
+use corosensei::stack::{DefaultStack, Stack};
+use std::{cell::Cell, ops::Range};
+
+// How close to the end of the stack evaluation can get before it moves to a
+// new segment, and how big each new segment, and each generator's stack, is
+const RED_ZONE: usize = 256 * 1024;
+pub(crate) const STACK_SEGMENT: usize = 4 * 1024 * 1024;
+
+thread_local! {
+  // The lowest address of the stack evaluation is running on, if it's a
+  // segment or a generator's stack rather than the thread's own. Generators
+  // switch it whenever they start, yield or finish
+  static LIMIT: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+pub(crate) fn limit() -> Option<usize> {
+  LIMIT.get()
+}
+
+// Sets the limit of the stack being switched to, and returns the old one
+pub(crate) fn switch(limit: Option<usize>) -> Option<usize> {
+  LIMIT.replace(limit)
+}
+
+// Fails if there's no memory left to map one, which generators holding on to
+// theirs are the likeliest cause of
+pub(crate) fn new_stack(span: &Range<usize>) -> Result<DefaultStack, EvaluationError> {
+  DefaultStack::new(STACK_SEGMENT).or_else(|_| {
+    error(
+      EvaluationErrorKind::TooManyGenerators(generator::started()),
+      span.clone(),
+    )
+  })
+}
+
+fn remaining() -> Option<usize> {
+  match LIMIT.get() {
+    Some(limit) => {
+      let marker = 0u8;
+      (&marker as *const u8 as usize).checked_sub(limit)
+    }
+    None => stacker::remaining_stack(),
+  }
+}
+
+// Telid calls recurse on the Rust stack, so it's grown on the heap when it
+// runs low instead of overflowing. `Limits::call_depth` is what stops deep
+// recursion
+pub(crate) fn grow<T>(
+  span: &Range<usize>,
+  evaluate: impl FnOnce() -> Result<T, EvaluationError>,
+) -> Result<T, EvaluationError> {
+  if remaining().is_some_and(|remaining| remaining >= RED_ZONE) {
+    return evaluate();
+  }
+
+  let stack = new_stack(span)?;
+  let outer = switch(Some(stack.limit().get()));
+  let result = corosensei::on_stack(stack, evaluate);
+  switch(outer);
+  result
+}
@@ -1,24 +1,23 @@
 use crate::{
+  error::EvaluationErrorKind,
   evaluator::{
-    evaluate,
-    scope::Scope,
+    context::Context,
+    coverage::Coverage,
+    evaluate, evaluate_with_context,
+    limits::Limits,
+    profiler::Profiler,
+    scope::{self, Scope},
     value::{Value, Variable},
   },
-  lexer::Lexer,
-  parser::parser,
+  test_util::parse,
 };
-use chumsky::Parser;
+use std::time::Duration;
 
 #[test]
 fn test_expression() {
   let source = "let x = + 5 * 5 2;";
 
-  let mut lexer = Lexer::new(source);
-  let tokens = lexer.lex(false);
-  let tokens = tokens.iter().map(|t| t.0.clone()).collect::<Vec<_>>();
-
-  let ast = parser().parse(tokens);
-  let ast = ast.unwrap();
+  let ast = parse(source);
 
   let scope = Scope::new();
   let result = evaluate(ast, scope);
@@ -29,6 +28,800 @@ fn test_expression() {
     Variable {
       value: Value::Number(15.0),
       constant: false,
+      annotation: None,
     }
   );
 }
+
+fn evaluate_source(source: &str, context: &mut Context) -> Scope {
+  evaluate_with_context(parse(source), scope::default(), context)
+    .unwrap()
+    .1
+}
+
+// The value `source` ends with, or the kind of error it stops with
+fn run(source: &str) -> Result<Value, EvaluationErrorKind> {
+  evaluate(parse(source), scope::default())
+    .map(|(value, _)| value)
+    .map_err(|error| error.kind)
+}
+
+fn numbers(numbers: &[f64]) -> Value {
+  Value::Array(numbers.iter().map(|n| Value::Number(*n)).collect())
+}
+
+#[test]
+fn test_seeded_random() {
+  let source = "
+    let a = random();
+    let b = random_int(1, 6);
+    let c = shuffle([1, 2, 3, 4, 5]);
+    let d = choice(['x', 'y', 'z']);
+  ";
+
+  let first = evaluate_source(source, &mut Context::with_seed(42));
+  let second = evaluate_source(source, &mut Context::with_seed(42));
+
+  for name in ["a", "b", "c", "d"] {
+    let name = String::from(name);
+    assert_eq!(first.get(&name), second.get(&name));
+  }
+
+  // Fractional bounds only let the integers between them be picked
+  let bounded = evaluate_source(
+    "let picks = for i in .. 1 200 random_int(0.5, 2.5);",
+    &mut Context::with_seed(7),
+  );
+  let Value::Array(picks) = &bounded.get(&String::from("picks")).unwrap().value else {
+    unreachable!()
+  };
+  assert!(picks
+    .iter()
+    .all(|pick| *pick == Value::Number(1.0) || *pick == Value::Number(2.0)));
+  assert!(picks.contains(&Value::Number(1.0)) && picks.contains(&Value::Number(2.0)));
+
+  assert_eq!(
+    evaluate(parse("random_int(1.2, 1.8)"), scope::default())
+      .unwrap_err()
+      .kind,
+    EvaluationErrorKind::InvalidRange(1.2, 1.8)
+  );
+
+  let reseeded = evaluate_source(
+    "seed(42); let a = random(); seed(42); let b = random();",
+    &mut Context::new(),
+  );
+  assert_eq!(
+    reseeded.get(&String::from("a")).unwrap().value,
+    reseeded.get(&String::from("b")).unwrap().value
+  );
+
+  let mut sorted = match &first.get(&String::from("c")).unwrap().value {
+    Value::Array(array) => array
+      .iter()
+      .map(|value| match value {
+        Value::Number(number) => *number,
+        _ => unreachable!(),
+      })
+      .collect::<Vec<_>>(),
+    _ => unreachable!(),
+  };
+  sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+  assert_eq!(sorted, vec![1.0, 2.0, 3.0, 4.0, 5.0]);
+}
+
+#[test]
+fn test_frozen_clock() {
+  let scope = evaluate_source(
+    "
+    let start = now();
+    let tick = monotonic();
+    sleep(1500);
+    let elapsed = - monotonic() tick;
+    let later = format_time(now(), '%Y-%m-%d %H:%M:%S.%f');
+    let epoch = format_time(0, '%Y-%m-%dT%H:%M:%S%%');
+    ",
+    &mut Context::deterministic(0, 1700000000.0),
+  );
+
+  let get = |name: &str| scope.get(&String::from(name)).unwrap().value.clone();
+
+  assert_eq!(get("start"), Value::Number(1700000000.0));
+  assert_eq!(get("elapsed"), Value::Number(1.5));
+  assert_eq!(
+    get("later"),
+    Value::String(String::from("2023-11-14 22:13:21.500"))
+  );
+  assert_eq!(
+    get("epoch"),
+    Value::String(String::from("1970-01-01T00:00:00%"))
+  );
+}
+
+#[test]
+fn test_regex() {
+  // Telid strings have their own escapes, so backslashes are doubled
+  let scope = evaluate_source(
+    r"
+    let date = regex('(\\d+)-(\\d+)-(\\d+)');
+    let parts = regex_match(date, 'on 2023-11-14!');
+    let missing = regex_match(date, 'no date');
+    let numbers = regex_find_all('\\d+', 'a1 b22 c333');
+    let pairs = regex_find_all('(\\w)=(\\d)', 'a=1 b=2');
+    let replaced = regex_replace(date, '2023-11-14', '$3/$2/$1');
+    let split = regex_split(' *, *', 'a, b ,c');
+    ",
+    &mut Context::new(),
+  );
+
+  let get = |name: &str| scope.get(&String::from(name)).unwrap().value.clone();
+  let strings = |strings: &[&str]| {
+    Value::Array(
+      strings
+        .iter()
+        .map(|string| Value::String(string.to_string()))
+        .collect(),
+    )
+  };
+
+  assert_eq!(get("parts"), strings(&["2023-11-14", "2023", "11", "14"]));
+  assert_eq!(get("missing"), Value::Void);
+  assert_eq!(get("numbers"), strings(&["1", "22", "333"]));
+  assert_eq!(
+    get("pairs"),
+    Value::Array(vec![
+      strings(&["a=1", "a", "1"]),
+      strings(&["b=2", "b", "2"])
+    ])
+  );
+  assert_eq!(get("replaced"), Value::String(String::from("14/11/2023")));
+  assert_eq!(get("split"), strings(&["a", "b", "c"]));
+
+  let ast = parse("regex_match('(', 'x')");
+
+  let error = evaluate(ast, scope::default()).unwrap_err();
+  assert!(matches!(error.kind, EvaluationErrorKind::InvalidRegex(_)));
+  assert_eq!(error.span, 0..6);
+}
+
+#[test]
+fn test_call_stack() {
+  let source =
+    "let fn get i = [i][1, 2]; let fn walk n = if == n 0 get(5) else + 1 walk(- n 1); walk(2);";
+
+  let ast = parse(source);
+
+  let mut context = Context::new();
+  let error = evaluate_with_context(ast, scope::default(), &mut context).unwrap_err();
+
+  assert_eq!(error.kind, EvaluationErrorKind::IndexOutOfBounds(5, 2));
+  assert_eq!(
+    error
+      .stack
+      .iter()
+      .map(|frame| frame.name.as_str())
+      .collect::<Vec<_>>(),
+    // `get` is a tail call, so it replaces the innermost `walk`
+    vec!["get", "walk", "walk"]
+  );
+  assert_eq!(error.stack.last().unwrap().span, 37..41);
+  assert!(context.call_stack.is_empty());
+}
+
+#[test]
+fn test_annotations() {
+  let run = |source: &str| {
+    let ast = parse(source);
+    evaluate(ast, scope::default()).map(|(value, _)| value)
+  };
+  let invalid_type = |found: &str, expected: &str| {
+    EvaluationErrorKind::InvalidType(found.to_string(), vec![expected.to_string()])
+  };
+
+  assert_eq!(
+    run("let fn add (a: Number) b -> Number = + a b; add(1, 2)"),
+    Ok(Value::Number(3.0))
+  );
+
+  // Tokens: let fn add ( a : Number ) b = + a b ; (0-13) add ( 'a' , 2 ) (14-19)
+  let error = run("let fn add (a: Number) b = + a b; add('a', 2)").unwrap_err();
+  assert_eq!(error.kind, invalid_type("String", "Number"));
+  assert_eq!(error.span, 16..17);
+
+  let error = run("let fn f -> Number = 'a'; f()").unwrap_err();
+  assert_eq!(error.kind, invalid_type("String", "Number"));
+
+  let error = run("let x: [Number | Void] = [1, void, 'a']").unwrap_err();
+  assert_eq!(error.kind, invalid_type("Array", "[Number | Void]"));
+
+  let error = run("let x: String = 'a'; x = 1").unwrap_err();
+  assert_eq!(error.kind, invalid_type("Number", "String"));
+}
+
+#[test]
+fn test_profiler() {
+  // Tokens: let fn f n = ... ; (0-17) f ( 3 ) ; (18-22) len ( 'ab' ) ; (23-27)
+  let source = "let fn f n = if == n 0 0 else f(- n 1); f(3); len('ab');";
+
+  let mut context = Context::new();
+  context.profiler = Some(Profiler::new());
+  evaluate_source(source, &mut context);
+  let mut profiler = context.profiler.unwrap();
+  profiler.finish();
+
+  let f = &profiler.functions["f"];
+  assert_eq!(f.calls, 4);
+  assert!(f.exclusive <= f.inclusive);
+  assert_eq!(profiler.functions["len"].calls, 1);
+
+  assert_eq!(profiler.statements[&(18..22)].count, 1);
+  // The body of `f`, once per call
+  assert_eq!(
+    profiler
+      .statements()
+      .iter()
+      .find(|(span, _)| span.start == 5)
+      .unwrap()
+      .1
+      .count,
+    4
+  );
+
+  let folded = profiler.folded();
+  let stacks = folded
+    .lines()
+    .map(|line| line.rsplit_once(' ').unwrap().0)
+    .collect::<Vec<_>>();
+  // Each tail call to `f` replaces the one before it
+  assert_eq!(stacks, vec!["main", "main;f", "main;len"]);
+
+  // Other recursive calls each get their own stack, and only the outermost
+  // one counts towards the inclusive time
+  let mut context = Context::new();
+  context.profiler = Some(Profiler::new());
+  evaluate_source(
+    "let fn f n = if == n 0 0 else + 1 f(- n 1); f(2);",
+    &mut context,
+  );
+  let mut profiler = context.profiler.unwrap();
+  profiler.finish();
+
+  let f = &profiler.functions["f"];
+  assert_eq!(f.calls, 3);
+  assert!(f.exclusive <= f.inclusive);
+  let folded = profiler.folded();
+  let stacks = folded
+    .lines()
+    .map(|line| line.rsplit_once(' ').unwrap().0)
+    .collect::<Vec<_>>();
+  assert_eq!(stacks, vec!["main", "main;f", "main;f;f", "main;f;f;f"]);
+}
+
+#[test]
+fn test_coverage() {
+  let source = "let fn sign n =
+  if < n 0
+    'negative'
+  else
+    'positive';
+sign(1);
+if false {
+  exit(1);
+}
+";
+
+  let ast = parse(source);
+
+  let mut context = Context::new();
+  context.coverage = Some(Coverage::new(&ast));
+  evaluate_with_context(ast, scope::default(), &mut context).unwrap();
+
+  let lines = context.coverage.unwrap().lines(source);
+  assert_eq!(
+    lines.lines.clone().into_iter().collect::<Vec<_>>(),
+    vec![(0, 1), (1, 1), (2, 0), (4, 1), (5, 1), (6, 1), (7, 0)]
+  );
+  assert_eq!(lines.branches, vec![(1, [0, 1]), (6, [0, 1])]);
+  assert_eq!(lines.missed_lines(), vec![2..3, 7..8]);
+  assert_eq!(lines.line_totals(), (7, 5));
+  assert_eq!(lines.branch_totals(), (4, 2));
+
+  let lcov = lines.lcov("test.tl");
+  assert!(lcov.starts_with("TN:\nSF:test.tl\nBRDA:2,0,0,0\nBRDA:2,0,1,1\n"));
+  assert!(lcov.contains("\nDA:3,0\n"));
+  assert!(lcov.ends_with("LF:7\nLH:5\nend_of_record\n"));
+}
+
+#[test]
+fn test_assertions() {
+  let run = |source: &str| {
+    let ast = parse(source);
+    let mut context = Context::new();
+    let result = evaluate_with_context(ast, scope::default(), &mut context);
+    assert!(context.call_stack.is_empty());
+    result.map(|_| ()).map_err(|error| error.kind)
+  };
+
+  assert_eq!(run("assert_eq([1, 'a'], [1, 'a'])"), Ok(()));
+  let error = run("assert_eq([1, 2], [1, '2'])").unwrap_err();
+  assert_eq!(
+    error,
+    EvaluationErrorKind::AssertionNotEqual(String::from("[1, 2]"), String::from("[1, '2']"))
+  );
+  assert_eq!(
+    run("assert_eq('ab', 'ac')").unwrap_err().to_string(),
+    "AssertionNotEqual: values differ\n  left:  'ab'\n  right: 'ac'\n           ^"
+  );
+
+  assert_eq!(
+    run("let fn fails = [5][1, 2]; assert_throws(fails)"),
+    Ok(())
+  );
+  assert_eq!(
+    run("let fn returns = 'x'; assert_throws(returns)"),
+    Err(EvaluationErrorKind::ExpectedError(String::from("'x'")))
+  );
+  // The frames of whatever failed are gone once the error is caught
+  for failing in [
+    "{ let inner = 'leaked'; { [5][9] } }",
+    "for i in [1] { let inner = 'leaked'; [5][9] }",
+    "while true { let inner = 'leaked'; [5][9] }",
+  ] {
+    assert_eq!(
+      run(&format!(
+        "let fn bad = {}; assert_throws(bad); let fn peek = inner; peek()",
+        failing
+      )),
+      Err(EvaluationErrorKind::UndefinedVariable(String::from(
+        "inner"
+      )))
+    );
+  }
+}
+
+#[test]
+fn test_limits() {
+  let run = |source: &str, limits: Limits| {
+    let ast = parse(source);
+    let mut context = Context::deterministic(0, 0.0);
+    context.limits = limits;
+    let result = evaluate_with_context(ast, scope::default(), &mut context);
+    result.map(|_| ()).map_err(|error| error.kind)
+  };
+
+  let fuel = Limits {
+    fuel: Some(10),
+    ..Limits::default()
+  };
+  assert_eq!(
+    run("let i = 0; while < i 3 { i = + i 1 }", fuel.clone()),
+    Ok(())
+  );
+  assert_eq!(
+    run("while true {}", fuel),
+    Err(EvaluationErrorKind::OutOfFuel(10))
+  );
+
+  let time = Limits {
+    time: Some(Duration::from_secs(1)),
+    ..Limits::default()
+  };
+  assert_eq!(run("sleep(600); sleep(300)", time.clone()), Ok(()));
+  assert_eq!(
+    run("sleep(600); sleep(600)", time.clone()),
+    Err(EvaluationErrorKind::TimeLimitExceeded(1.0))
+  );
+  // The fixed clock only moves when something sleeps
+  assert_eq!(
+    run("while true { sleep(100) }", time),
+    Err(EvaluationErrorKind::TimeLimitExceeded(1.0))
+  );
+
+  // Lengths that can't be waited for fail instead of panicking
+  assert_eq!(run("sleep((-5))", Limits::default()), Ok(()));
+  assert_eq!(
+    run("sleep(/ 1 0)", Limits::default()),
+    Err(EvaluationErrorKind::InvalidDuration(f64::INFINITY))
+  );
+  assert_eq!(
+    run("sleep(* 1000000000000 1000000000000)", Limits::default()),
+    Err(EvaluationErrorKind::InvalidDuration(1e24))
+  );
+  assert!(matches!(
+    run("sleep(/ 0 0)", Limits::default()),
+    Err(EvaluationErrorKind::InvalidDuration(milliseconds)) if milliseconds.is_nan()
+  ));
+
+  let depth = Limits {
+    call_depth: Some(3),
+    ..Limits::default()
+  };
+  let countdown = "let fn f n = if == n 0 0 else + 1 f(- n 1);";
+  assert_eq!(run(&format!("{} f(2)", countdown), depth.clone()), Ok(()));
+  assert_eq!(
+    run(&format!("{} f(3)", countdown), depth),
+    Err(EvaluationErrorKind::CallDepthExceeded(3))
+  );
+
+  let size = Limits {
+    value_size: Some(4),
+    memory: Some(1000),
+    ..Limits::default()
+  };
+  assert_eq!(run(".. 1 1000000000000", size.clone()), Ok(()));
+  assert_eq!(
+    run("array(.. 1 1000000000000)", size.clone()),
+    Err(EvaluationErrorKind::ValueTooLarge(1000000000000, 4))
+  );
+  assert_eq!(
+    run("+ 'abc' 'de'", size.clone()),
+    Err(EvaluationErrorKind::ValueTooLarge(5, 4))
+  );
+  assert_eq!(run("concat([1, 2], [3])", size.clone()), Ok(()));
+  // Loops fail as soon as their array gets too big, not once they end
+  assert_eq!(
+    run("while true 1", size),
+    Err(EvaluationErrorKind::ValueTooLarge(5, 4))
+  );
+
+  let memory = Limits {
+    memory: Some(1000),
+    ..Limits::default()
+  };
+  assert_eq!(
+    run("for i in .. 1 1000000000000 i", memory.clone()),
+    Err(EvaluationErrorKind::MemoryLimitExceeded(1000))
+  );
+  assert_eq!(
+    run("let s = 'a'; while true { s = + s 'b'; s = 'a' }", memory),
+    Err(EvaluationErrorKind::MemoryLimitExceeded(1000))
+  );
+
+  // Only generators that have started and not finished have a stack
+  let generators = Limits {
+    generators: Some(2),
+    ..Limits::default()
+  };
+  let twice = "let fn twice = { yield 1; yield 2 };";
+  assert_eq!(
+    run(
+      &format!("{} let gs = for i in .. 1 10 twice();", twice),
+      generators.clone()
+    ),
+    Ok(())
+  );
+  assert_eq!(
+    run(
+      &format!(
+        "{} for i in .. 1 10 {{ let g = twice(); g(); g(); g() }}",
+        twice
+      ),
+      generators.clone()
+    ),
+    Ok(())
+  );
+  assert_eq!(
+    run(
+      &format!(
+        "{} let gs = for i in .. 1 3 {{ let g = twice(); g(); g }}",
+        twice
+      ),
+      generators
+    ),
+    Err(EvaluationErrorKind::TooManyGenerators(2))
+  );
+
+  let sandbox = Limits::sandbox();
+  for (source, name) in [
+    ("exit(1)", "exit"),
+    ("readln()", "readln"),
+    ("sleep(1)", "sleep"),
+  ] {
+    assert_eq!(
+      run(source, sandbox.clone()),
+      Err(EvaluationErrorKind::CapabilityDenied(String::from(name)))
+    );
+  }
+}
+
+#[test]
+fn test_scope() {
+  let variable = |number: f64| Variable {
+    value: Value::Number(number),
+    constant: false,
+    annotation: None,
+  };
+  let value = |scope: &Scope, name: &str| scope.get(name).map(|variable| variable.value.clone());
+
+  let mut scope = Scope::new();
+  scope.insert(String::from("x"), variable(1.0));
+  scope.push_scope();
+  scope.insert(String::from("x"), variable(2.0));
+  scope.insert(String::from("x"), variable(3.0));
+  assert_eq!(scope.len(), 1);
+  assert_eq!(scope.position("x"), Some((0, 0)));
+  assert_eq!(value(&scope, "x"), Some(Value::Number(3.0)));
+
+  // Assigning to a name changes the outermost variable with it
+  scope.insert_existing(String::from("x"), variable(4.0));
+  scope.insert_existing(String::from("y"), variable(5.0));
+  assert_eq!(scope.position("y"), Some((1, 1)));
+
+  // Frames split off and put back are found again
+  scope.push_scope();
+  scope.insert(String::from("z"), variable(6.0));
+  let frames = scope.split_off(2);
+  assert_eq!(value(&scope, "z"), None);
+  assert_eq!(frames.position("z"), Some((0, 0)));
+  let outer = scope.split_off(0);
+  scope.append(outer);
+  scope.append(frames);
+  assert_eq!(value(&scope, "z"), Some(Value::Number(6.0)));
+
+  scope.pop_scope();
+  scope.pop_scope();
+  assert_eq!(value(&scope, "x"), Some(Value::Number(4.0)));
+  assert_eq!(value(&scope, "y"), Some(Value::Number(5.0)));
+  assert_eq!(value(&scope, "z"), None);
+}
+
+#[test]
+fn test_deep_recursion() {
+  let run = |source: &str, limits: Limits| {
+    let ast = parse(source);
+    let mut context = Context {
+      limits,
+      ..Context::default()
+    };
+    evaluate_with_context(ast, scope::default(), &mut context)
+      .map(|(value, _)| value)
+      .map_err(|error| error.kind)
+  };
+
+  // Much deeper than a test thread's stack would allow without growing it
+  let sum = "let fn sum n = if == n 0 0 else + n sum(- n 1); sum(2000)";
+  assert_eq!(run(sum, Limits::default()), Ok(Value::Number(2001000.0)));
+  let shallow = Limits {
+    call_depth: Some(1000),
+    ..Limits::default()
+  };
+  assert_eq!(
+    run(sum, shallow),
+    Err(EvaluationErrorKind::CallDepthExceeded(1000))
+  );
+}
+
+#[test]
+fn test_tail_calls() {
+  // A hundred times deeper than calls can nest
+  assert_eq!(
+    run("let fn countdown n = if == n 0 'done' else countdown(- n 1); countdown(1000000)"),
+    Ok(Value::String(String::from("done")))
+  );
+
+  // Through blocks, and between functions
+  let parity = "
+    let fn even n = if == n 0 true else { let m = - n 1; odd(m) };
+    let fn odd n = if == n 0 false else even(- n 1);
+  ";
+  assert_eq!(
+    run(&format!("{} even(20001)", parity)),
+    Ok(Value::Boolean(false))
+  );
+
+  // The function called can still see the caller's variables, wherever it
+  // was declared
+  assert_eq!(
+    run("let fn outer x = { let fn inner = x; inner() }; outer(5)"),
+    Ok(Value::Number(5.0))
+  );
+  assert_eq!(
+    run("let fn g = x; let fn f x = g(); f(5)"),
+    Ok(Value::Number(5.0))
+  );
+  assert_eq!(
+    run("let fn g = + x y; let fn f x = { let y = * x 2; g() }; f(4)"),
+    Ok(Value::Number(12.0))
+  );
+
+  // Without keeping a variable per call
+  assert_eq!(
+    run(
+      "
+      let fn count n = {
+        let next = - n 1;
+        if == n 0 'done' else count(next)
+      };
+      count(100000)
+    "
+    ),
+    Ok(Value::String(String::from("done")))
+  );
+
+  // Every return type on the way is checked
+  assert_eq!(
+    run("let fn text -> String = number(); let fn number = 5; text()"),
+    Err(EvaluationErrorKind::InvalidType(
+      String::from("Number"),
+      vec![String::from("String")]
+    ))
+  );
+
+  // So is the number of arguments of each call
+  assert_eq!(
+    run("let fn g a b = + a b; let fn f n = g(n); f(1)"),
+    Err(EvaluationErrorKind::IncorrectParameterCount(1, 2))
+  );
+  assert_eq!(
+    run("let fn g a b = + a b; g(1, 2, 3)"),
+    Err(EvaluationErrorKind::IncorrectParameterCount(3, 2))
+  );
+}
+
+#[test]
+fn test_generators() {
+  // Nothing runs until it's resumed, and each resume runs up to the next yield
+  let naturals = "
+    let ran = false;
+    let fn naturals = { ran = true; let n = 0; while true { yield n; n = + n 1 } };
+    let g = naturals();
+  ";
+  assert_eq!(
+    run(&format!("{} [ran, status(g)]", naturals)),
+    Ok(Value::Array(vec![
+      Value::Boolean(false),
+      Value::String(String::from("suspended"))
+    ]))
+  );
+  assert_eq!(
+    run(&format!("{} [g(), resume(g), g()]", naturals)),
+    Ok(numbers(&[0.0, 1.0, 2.0]))
+  );
+
+  // for stops once the function returns, and yields can be nested in loops
+  assert_eq!(
+    run("let fn g = for i in .. 1 3 for j in .. 1 i yield * i j; for x in g() x"),
+    Ok(numbers(&[1.0, 2.0, 4.0, 3.0, 6.0, 9.0]))
+  );
+
+  // Resuming one generator from another
+  assert_eq!(
+    run(
+      "
+      let fn inner = { yield 1; yield 2 };
+      let fn outer = for x in inner() yield * x 10;
+      for x in outer() x
+    "
+    ),
+    Ok(numbers(&[10.0, 20.0]))
+  );
+
+  // The last resume returns what the function does, and then it's done
+  assert_eq!(
+    run("let fn once = { yield 1; 'end' }; let g = once(); [g(), g(), status(g)]"),
+    Ok(Value::Array(vec![
+      Value::Number(1.0),
+      Value::String(String::from("end")),
+      Value::String(String::from("done"))
+    ]))
+  );
+  assert_eq!(
+    run("let fn once = yield 1; let g = once(); g(); g(); g()"),
+    Err(EvaluationErrorKind::GeneratorNotSuspended(String::from(
+      "done"
+    )))
+  );
+  assert_eq!(
+    run("let fn again = yield g(); let g = again(); g()"),
+    Err(EvaluationErrorKind::GeneratorNotSuspended(String::from(
+      "running"
+    )))
+  );
+
+  // spawn runs any function as a generator, and deep calls in a generator
+  // grow its stack like any other
+  assert_eq!(
+    run(
+      "
+      let fn sum n = if == n 0 0 else + n sum(- n 1);
+      let fn sums = { yield sum(2000); sum(3000) };
+      let g = spawn(sums);
+      [g(), g()]
+    "
+    ),
+    Ok(numbers(&[2001000.0, 4501500.0]))
+  );
+
+  // Without the resolver to catch it first
+  assert_eq!(
+    run("yield 1"),
+    Err(EvaluationErrorKind::YieldOutsideGenerator)
+  );
+}
+
+#[test]
+fn test_ranges() {
+  // Nothing is allocated for the numbers in a range
+  let huge = "let r = .. 1 1000000000000;";
+  assert_eq!(
+    run(&format!("{} [len(r), [5]r, [999999999999]r]", huge)),
+    Ok(numbers(&[1000000000000.0, 6.0, 1000000000000.0]))
+  );
+  assert_eq!(
+    run(&format!("{} array([2..5]r)", huge)),
+    Ok(numbers(&[3.0, 4.0, 5.0]))
+  );
+  assert_eq!(
+    run("let total = 0; for i in .. 1 100 total = + total i; total"),
+    Ok(Value::Number(5050.0))
+  );
+
+  // Bounds are truncated, and `range` takes a step in either direction
+  assert_eq!(
+    run("array(.. (-2.5) 1.9)"),
+    Ok(numbers(&[-2.0, -1.0, 0.0, 1.0]))
+  );
+  assert_eq!(
+    run("array(range(10, 1, (-3)))"),
+    Ok(numbers(&[10.0, 7.0, 4.0, 1.0]))
+  );
+  assert_eq!(
+    run("array(range(0, 1, 0.25))"),
+    Ok(numbers(&[0.0, 0.25, 0.5, 0.75, 1.0]))
+  );
+  assert_eq!(
+    run("range(1, 10, 0)"),
+    Err(EvaluationErrorKind::InvalidStep(0.0))
+  );
+  assert_eq!(
+    run(".. 5 1"),
+    Err(EvaluationErrorKind::InvalidRange(5.0, 1.0))
+  );
+
+  // The array builtins take ranges too
+  assert_eq!(run("concat(.. 1 2, [3])"), Ok(numbers(&[1.0, 2.0, 3.0])));
+  assert_eq!(
+    run("filter(.. 1 3, 'String')"),
+    Ok(numbers(&[1.0, 2.0, 3.0]))
+  );
+  assert_eq!(run("len(shuffle(.. 1 5))"), Ok(Value::Number(5.0)));
+  assert_eq!(run("choice([0..0](.. 1 5))"), Ok(Value::Void));
+
+  // Ranges equal other ranges and arrays with the same numbers, and can be
+  // passed as arrays of numbers
+  for (source, equal) in [
+    ("== (.. 1 3) range(1, 3.5, 1)", true),
+    ("== (.. 1 3) [1, 2, 3]", true),
+    ("== [1, 2, 3] (.. 1 3)", true),
+    ("let a = [.. 1 2]; let b = [[1, 2]]; == a b", true),
+    ("== (.. 1 3) [1, 2]", false),
+    ("== (.. 1 3) [1, 2, '3']", false),
+  ] {
+    assert_eq!(run(source), Ok(Value::Boolean(equal)), "{}", source);
+  }
+  assert_eq!(run("assert_eq(.. 1 3, [1, 2, 3])"), Ok(Value::Void));
+  assert_eq!(
+    run("let fn sum (xs: [Number]) = { let t = 0; for x in xs t = + t x; t }; sum(.. 1 4)"),
+    Ok(Value::Number(10.0))
+  );
+  assert_eq!(
+    run("[type(.. 1 2), + '' range(0, 4, 2), + '' .. 1 3]"),
+    Ok(Value::Array(vec![
+      Value::String(String::from("Range")),
+      Value::String(String::from("range(0, 4, 2)")),
+      Value::String(String::from(".. 1 3")),
+    ]))
+  );
+
+  // `array` makes anything a loop goes over into an array
+  assert_eq!(
+    run("let fn g = { yield 1; yield 2; 3 }; [array(g()), array('ab')]"),
+    Ok(Value::Array(vec![
+      numbers(&[1.0, 2.0]),
+      Value::Array(vec![
+        Value::String(String::from("a")),
+        Value::String(String::from("b"))
+      ]),
+    ]))
+  );
+}
@@ -1,6 +1,41 @@
-use crate::error::{EvaluationError, EvaluationErrorKind};
+use super::value::Value;
+use crate::{
+  error::{EvaluationError, EvaluationErrorKind},
+  parser::ast::Type,
+};
 use std::ops::Range;
 
 pub fn error<T>(kind: EvaluationErrorKind, span: Range<usize>) -> Result<T, EvaluationError> {
-  Err(EvaluationError { kind, span })
+  Err(EvaluationError {
+    kind,
+    span,
+    stack: Vec::new(),
+  })
+}
+
+// Fails if `value` crosses an annotation it doesn't match
+pub fn check_type(
+  value: &Value,
+  annotation: Option<&Type>,
+  span: Range<usize>,
+) -> Result<(), EvaluationError> {
+  match annotation {
+    Some(annotation) if !value.has_type(annotation) => error(
+      EvaluationErrorKind::InvalidType(value.as_ref().to_string(), vec![annotation.to_string()]),
+      span,
+    ),
+    _ => Ok(()),
+  }
+}
+
+// How `value` would be written in Telid, so that `'1'` and `1` look different
+pub fn describe(value: &Value) -> String {
+  match value {
+    Value::String(string) => format!("'{}'", string),
+    Value::Array(array) => format!(
+      "[{}]",
+      array.iter().map(describe).collect::<Vec<_>>().join(", ")
+    ),
+    value => value.to_string(),
+  }
 }
@@ -1,38 +1,141 @@
-use crate::{error::EvaluationError, parser::ast::Statement};
-use std::ops::Range;
+use super::{context::Context, generator::Generator, pattern::Pattern, range::NumberRange};
+use crate::{
+  error::EvaluationError,
+  parser::ast::{Statement, Type},
+};
+use std::{fmt, ops::Range};
 use strum_macros::AsRefStr;
 
+// Calls a Telid value as a function, for Rust functions that are passed one
+pub type Caller<'a> =
+  dyn FnMut(&mut Context, Value, Vec<Value>) -> Result<Value, EvaluationError> + 'a;
+
+pub type RustFunction =
+  fn(&mut Context, Range<usize>, Vec<Value>, &mut Caller) -> Result<Value, EvaluationError>;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Variable {
   pub value: Value,
   pub constant: bool,
+  // Values assigned to the variable are checked against this
+  pub annotation: Option<Type>,
 }
 
-#[derive(Debug, Clone, PartialEq, AsRefStr)]
+#[derive(Debug, Clone, AsRefStr)]
 pub enum Value {
   Void,
   Number(f64),
   String(String),
   Boolean(bool),
   Array(Vec<Value>),
+  Range(NumberRange),
+  Regex(Pattern),
   Function {
     parameters: Vec<String>,
+    parameter_types: Vec<Option<Type>>,
+    return_type: Option<Type>,
     body: Box<Statement>,
+    // Whether the body yields, so that calling it makes a generator
+    generator: bool,
   },
   RustFunction {
     parameter_count: usize,
-    function: fn(Range<usize>, Vec<Value>) -> Result<Value, EvaluationError>,
+    function: RustFunction,
   },
+  Generator(Generator),
+}
+
+impl Value {
+  pub fn has_type(&self, annotation: &Type) -> bool {
+    match (annotation, self) {
+      (Type::Any, _)
+      | (Type::Void, Value::Void)
+      | (Type::Number, Value::Number(_))
+      | (Type::String, Value::String(_))
+      | (Type::Boolean, Value::Boolean(_))
+      | (Type::Regex, Value::Regex(_))
+      | (Type::Function, Value::Function { .. } | Value::RustFunction { .. })
+      | (Type::Generator, Value::Generator(_))
+      | (Type::Range, Value::Range(_)) => true,
+      (Type::Array(element), Value::Array(array)) => {
+        array.iter().all(|value| value.has_type(element))
+      }
+      // A range can be used wherever an array of numbers can
+      (Type::Array(element), Value::Range(_)) => Value::Number(0.0).has_type(element),
+      (Type::Union(types), _) => types.iter().any(|annotation| self.has_type(annotation)),
+      _ => false,
+    }
+  }
 }
 
-impl ToString for Value {
-  fn to_string(&self) -> String {
+impl PartialEq for Value {
+  // Rust functions are compared by address, which is good enough for builtins
+  #[allow(unpredictable_function_pointer_comparisons)]
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (Value::Void, Value::Void) => true,
+      (Value::Number(a), Value::Number(b)) => a == b,
+      (Value::String(a), Value::String(b)) => a == b,
+      (Value::Boolean(a), Value::Boolean(b)) => a == b,
+      (Value::Array(a), Value::Array(b)) => a == b,
+      (Value::Range(a), Value::Range(b)) => a == b,
+      // A range is equal to the array of its numbers, since it can be used
+      // wherever that array can
+      (Value::Range(range), Value::Array(array)) | (Value::Array(array), Value::Range(range)) => {
+        range.len == array.len()
+          && range
+            .iter()
+            .zip(array)
+            .all(|(number, value)| *value == Value::Number(number))
+      }
+      (Value::Regex(a), Value::Regex(b)) => a == b,
+      (
+        Value::Function {
+          parameters,
+          parameter_types,
+          return_type,
+          body,
+          generator,
+        },
+        Value::Function {
+          parameters: other_parameters,
+          parameter_types: other_parameter_types,
+          return_type: other_return_type,
+          body: other_body,
+          generator: other_generator,
+        },
+      ) => {
+        parameters == other_parameters
+          && parameter_types == other_parameter_types
+          && return_type == other_return_type
+          && body == other_body
+          && generator == other_generator
+      }
+      (
+        Value::RustFunction {
+          parameter_count,
+          function,
+        },
+        Value::RustFunction {
+          parameter_count: other_parameter_count,
+          function: other_function,
+        },
+      ) => parameter_count == other_parameter_count && function == other_function,
+      (Value::Generator(a), Value::Generator(b)) => a == b,
+      _ => false,
+    }
+  }
+}
+
+impl fmt::Display for Value {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
     match self {
-      Value::Void => "void".to_string(),
-      Value::Number(number) => number.to_string(),
-      Value::String(string) => string.clone(),
-      Value::Boolean(boolean) => boolean.to_string(),
-      Value::Array(array) => format!(
+      Value::Void => write!(f, "void"),
+      Value::Number(number) => write!(f, "{}", number),
+      Value::String(string) => write!(f, "{}", string),
+      Value::Boolean(boolean) => write!(f, "{}", boolean),
+      Value::Array(array) => write!(
+        f,
         "[{}]",
         array
           .iter()
@@ -40,23 +143,23 @@ impl ToString for Value {
           .collect::<Vec<_>>()
           .join(", ")
       ),
-      Value::Function {
-        parameters,
-        body: _,
-      } => {
+      Value::Range(range) => write!(f, "{}", range),
+      Value::Regex(pattern) => write!(f, "{}", pattern),
+      Value::Function { parameters, .. } => {
         let mut string = String::from("fn (");
         for parameter in parameters {
-          string.push_str(&parameter);
+          string.push_str(parameter);
           string.push_str(", ");
         }
         string.push_str(") ");
-        string
+        write!(f, "{}", string)
       }
       Value::RustFunction {
         parameter_count, ..
       } => {
-        format!("RustFn({})", parameter_count)
+        write!(f, "RustFn({})", parameter_count)
       }
+      Value::Generator(generator) => write!(f, "<generator ({})>", generator.status()),
     }
   }
 }
@@ -0,0 +1,288 @@
+use std::fmt;
+
+// The long-form description of an error code, for `telid --explain`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Explanation {
+  pub code: &'static str,
+  pub title: &'static str,
+  pub description: &'static str,
+  // A program that fails with this error, and the same program fixed
+  pub failing: &'static str,
+  pub fixed: &'static str,
+}
+
+impl fmt::Display for Explanation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let indent = |code: &str| {
+      code
+        .lines()
+        .map(|line| format!("    {}", line))
+        .collect::<Vec<_>>()
+        .join("\n")
+    };
+
+    write!(
+      f,
+      "{}: {}\n\n{}\n\nErroneous code example:\n\n{}\n\nFixed:\n\n{}",
+      self.code,
+      self.title,
+      self.description,
+      indent(self.failing),
+      indent(self.fixed)
+    )
+  }
+}
+
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+  EXPLANATIONS
+    .iter()
+    .find(|explanation| explanation.code.eq_ignore_ascii_case(code))
+}
+
+// Every code an error can have, in order
+pub const EXPLANATIONS: [Explanation; 27] = [
+  Explanation {
+    code: "T0001",
+    title: "Unexpected character",
+    description: "The lexer found a character that isn't part of any Telid token. Operators \
+                  are written before their operands, and only the ones in the README exist.",
+    failing: "let total = 5 $ 3;",
+    fixed: "let total = + 5 3;",
+  },
+  Explanation {
+    code: "T0002",
+    title: "Unterminated string literal",
+    description: "A string literal was opened with a quote that is never closed, so the rest \
+                  of the file would be part of the string. Strings can use single or double \
+                  quotes, but have to end with the one they started with.",
+    failing: "let greeting = 'hello;",
+    fixed: "let greeting = 'hello';",
+  },
+  Explanation {
+    code: "T0003",
+    title: "Unexpected token",
+    description: "The parser found a token where it can't go. The message lists the tokens \
+                  that would have been accepted there. A common cause is writing an operator \
+                  between its operands instead of before them, or leaving out a name.",
+    failing: "let = 5;",
+    fixed: "let x = 5;",
+  },
+  Explanation {
+    code: "T0004",
+    title: "Unclosed delimiter",
+    description: "A parenthesis, bracket or brace was opened and the file ended, or something \
+                  else was found, before it was closed. The report also points at where it was \
+                  opened.",
+    failing: "let fn double x = {\n  * x 2;",
+    fixed: "let fn double x = {\n  * x 2;\n}",
+  },
+  Explanation {
+    code: "T0005",
+    title: "Undefined variable",
+    description: "A name is used that was never declared with `let`, `let const`, `let fn`, a \
+                  parameter or a `for` loop. Names are only visible after they're declared, \
+                  and inside the block they were declared in.",
+    failing: "let y = + x 1;",
+    fixed: "let x = 1;\nlet y = + x 1;",
+  },
+  Explanation {
+    code: "T0006",
+    title: "Duplicate parameter",
+    description: "A function has two parameters with the same name, so the second would hide \
+                  the first. Give each parameter its own name.",
+    failing: "let fn add x x = + x x;",
+    fixed: "let fn add x y = + x y;",
+  },
+  Explanation {
+    code: "T0007",
+    title: "Constant reassignment",
+    description: "A value was assigned to a name that can't change: a `let const` variable, a \
+                  function's parameter, a `for` loop's variable or a builtin. Declare it \
+                  without `const`, or use a new variable.",
+    failing: "let const limit = 10;\nlimit = 20;",
+    fixed: "let limit = 10;\nlimit = 20;",
+  },
+  Explanation {
+    code: "T0008",
+    title: "Invalid type",
+    description: "A value has a type that doesn't fit where it's used: an argument to a \
+                  builtin, a value for an annotated variable, parameter or return value, or \
+                  the condition of an `if` or `while`. The message shows the type found and \
+                  the types that would have been accepted.",
+    failing: "let n = len(5);",
+    fixed: "let n = len('five');",
+  },
+  Explanation {
+    code: "T0009",
+    title: "Invalid operator",
+    description: "An operator was applied to values it doesn't accept. Arithmetic and \
+                  comparisons need numbers (comparisons also accept two strings), `&&` and \
+                  `||` need booleans, and `+` joins a string with anything.",
+    failing: "let difference = - 'ten' 2;",
+    fixed: "let difference = - 10 2;",
+  },
+  Explanation {
+    code: "T0010",
+    title: "Index out of bounds",
+    description: "An index or the end of a slice is past the end of the array or string. \
+                  Indices start at 0, so the last element of an array of length `n` is at \
+                  `- n 1`.",
+    failing: "let xs = [1, 2, 3];\nlet last = [3]xs;",
+    fixed: "let xs = [1, 2, 3];\nlet last = [- len(xs) 1]xs;",
+  },
+  Explanation {
+    code: "T0011",
+    title: "Incorrect parameter count",
+    description: "A builtin was called with a different number of arguments than it takes. \
+                  The README lists the parameters of each one.",
+    failing: "let n = len('a', 'b');",
+    fixed: "let n = + len('a') len('b');",
+  },
+  Explanation {
+    code: "T0012",
+    title: "Invalid range",
+    description: "A range, a slice or `random_int` was given a start that's greater than its \
+                  end. Ranges only count upwards; reverse the bounds, or the array afterwards.",
+    failing: "let countdown = .. 5 1;",
+    fixed: "let countdown = .. 1 5;",
+  },
+  Explanation {
+    code: "T0013",
+    title: "Invalid regex",
+    description: "A string passed as a regex pattern isn't valid regex syntax. Characters \
+                  with special meanings, like parentheses, have to be escaped with a \
+                  backslash, which itself has to be escaped in a string literal.",
+    failing: "let r = regex('(');",
+    fixed: "let r = regex('\\\\(');",
+  },
+  Explanation {
+    code: "T0014",
+    title: "Assertion failed",
+    description: "The condition passed to `assert` was false. Use `assert_eq` to compare two \
+                  values, which shows both when they differ.",
+    failing: "assert(== + 1 1 3);",
+    fixed: "assert(== + 1 1 2);",
+  },
+  Explanation {
+    code: "T0015",
+    title: "Values not equal",
+    description: "The two values passed to `assert_eq` weren't equal. The message shows both, \
+                  and points at the first character where they differ.",
+    failing: "assert_eq(+ 'a' 'b', 'ba');",
+    fixed: "assert_eq(+ 'a' 'b', 'ab');",
+  },
+  Explanation {
+    code: "T0016",
+    title: "Expected an error",
+    description: "The function passed to `assert_throws` returned instead of failing. The \
+                  message shows what it returned.",
+    failing: "let fn double x = * x 2;\nlet fn double_number = double(2);\n\
+              assert_throws(double_number);",
+    fixed: "let fn double x = * x 2;\nlet fn double_string = double('a');\n\
+            assert_throws(double_string);",
+  },
+  Explanation {
+    code: "T0017",
+    title: "Out of fuel",
+    description: "The program ran more statements than its limit allows. Limits are only set \
+                  when running code that isn't trusted, like with `telid --sandbox`, and a \
+                  loop that never ends is the usual cause. Each statement counts every time it \
+                  runs, in loops and calls too.",
+    failing: "while true {}",
+    fixed: "let i = 0;\nwhile < i 10 {\n  i = + i 1;\n}",
+  },
+  Explanation {
+    code: "T0018",
+    title: "Time limit exceeded",
+    description: "The program ran for longer than its limit allows. `sleep` fails straight \
+                  away if it would wake up after the limit.",
+    failing: "sleep(10000);",
+    fixed: "sleep(100);",
+  },
+  Explanation {
+    code: "T0019",
+    title: "Call depth exceeded",
+    description: "Functions called each other more times over than the limit allows, before \
+                  any of them returned. Check that recursive functions have a case that stops \
+                  them. A call that's the last thing a function does doesn't count, since it \
+                  replaces the call to that function.",
+    failing: "let fn depth n = + 1 depth(n);\ndepth(0);",
+    fixed: "let fn depth n = if == n 0 0 else + 1 depth(- n 1);\ndepth(10);",
+  },
+  Explanation {
+    code: "T0020",
+    title: "Value too large",
+    description: "A string has more characters, or an array more elements, than the limit \
+                  allows. Ranges are checked before they're made into arrays, but a range on \
+                  its own can be any length, and `for` loops over it without making one.",
+    failing: "let numbers = array(.. 1 10000000);",
+    fixed: "let numbers = .. 1 10000000;",
+  },
+  Explanation {
+    code: "T0021",
+    title: "Memory limit exceeded",
+    description: "The program made more strings and arrays than the limit allows, counting \
+                  every one it made over its whole run, even ones it no longer uses. Loops \
+                  keep the value of every iteration, so a loop that copies a large array \
+                  adds up quickly.",
+    failing: "let chunk = .. 1 100000;\nwhile true {\n  chunk = concat(chunk, []);\n}",
+    fixed: "let chunk = .. 1 100000;\nchunk = concat(chunk, []);",
+  },
+  Explanation {
+    code: "T0022",
+    title: "Capability denied",
+    description: "A builtin that reaches outside the program was called where it isn't \
+                  allowed. In a sandbox, `exit`, `readln` and `sleep` are unavailable, so the \
+                  program can't stop its host, wait for input or block it.",
+    failing: "let name = readln();",
+    fixed: "let name = 'world';",
+  },
+  Explanation {
+    code: "T0023",
+    title: "Yield outside a generator",
+    description: "`yield` hands a value to whatever resumed the generator it's in, and a \
+                  function with a `yield` in its body is a generator function. There is no \
+                  generator to suspend at the top level, so `yield` has to be inside a \
+                  function.",
+    failing: "for i in .. 1 3 {\n  yield i;\n}",
+    fixed: "let fn numbers = for i in .. 1 3 {\n  yield i;\n};\nfor i in numbers() println(i);",
+  },
+  Explanation {
+    code: "T0024",
+    title: "Generator not suspended",
+    description: "A generator can only be resumed while it's suspended, before it starts or \
+                  at a `yield`. Once its function has returned it's done, and while it's \
+                  running it can't resume itself. `status(g)` tells which it is.",
+    failing: "let fn once = yield 1;\nlet g = once();\ng();\ng();\ng();",
+    fixed: "let fn once = yield 1;\nlet g = once();\nfor value in g println(value);",
+  },
+  Explanation {
+    code: "T0025",
+    title: "Invalid step",
+    description: "`range(start, end, step)` counts from `start` towards `end` in steps of \
+                  `step`, so the step can't be 0, and has to be negative to count down.",
+    failing: "let countdown = range(10, 0, 1);",
+    fixed: "let countdown = range(10, 0, (-1));",
+  },
+  Explanation {
+    code: "T0026",
+    title: "Invalid duration",
+    description: "`sleep(ms)` was given a length it can't wait for: one that isn't a number, \
+                  like `/ 0 0`, or one too long to measure, like `/ 1 0`. Negative lengths \
+                  don't wait at all.",
+    failing: "sleep(/ 1 0);",
+    fixed: "sleep(1);",
+  },
+  Explanation {
+    code: "T0027",
+    title: "Too many generators",
+    description: "Each generator that has started and not finished has a stack of its own, \
+                  so only so many can be kept at once: 1000 in the sandbox, and as many as \
+                  there's memory for otherwise. Generators only get a stack when they're \
+                  first resumed, and give it back when they finish.",
+    failing: "let fn count = { let n = 0; while true { yield n; n = + n 1; } };\n\
+              let counters = for i in .. 1 2000 { let counter = count(); counter(); counter };",
+    fixed: "let fn count = { let n = 0; while true { yield n; n = + n 1; } };\n\
+            let counters = for i in .. 1 2000 count();",
+  },
+];
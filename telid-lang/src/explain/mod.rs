@@ -0,0 +1,7 @@
+#[allow(clippy::module_inception)]
+mod explain;
+
+#[cfg(test)]
+mod tests;
+
+pub use explain::*;
@@ -0,0 +1,62 @@
+use crate::{
+  evaluator::{
+    context::Context,
+    evaluate_with_context,
+    limits::{Capabilities, Limits},
+    scope,
+  },
+  explain::{explain, EXPLANATIONS},
+  program::compile,
+  resolver::resolve,
+};
+
+// The code of the first error `source` fails with, before or while running.
+// It runs in a sandbox, with smaller limits so that the examples that cross
+// them don't take long
+fn error_code(source: &str) -> Option<&'static str> {
+  let program = compile(source, &scope::default(), resolve);
+  if let Some(error) = program.errors.first() {
+    return Some(error.code());
+  }
+
+  // `sleep` doesn't block on a fixed clock
+  let mut context = Context::deterministic(0, 0.0);
+  context.limits = Limits {
+    fuel: Some(100_000),
+    value_size: Some(100_000),
+    memory: Some(64 << 20),
+    capabilities: Capabilities {
+      sleep: true,
+      ..Capabilities::none()
+    },
+    ..Limits::sandbox()
+  };
+
+  evaluate_with_context(program.ast, scope::default(), &mut context)
+    .err()
+    .map(|error| error.kind.code())
+}
+
+#[test]
+fn test_examples() {
+  for (index, explanation) in EXPLANATIONS.iter().enumerate() {
+    assert_eq!(explanation.code, format!("T{:04}", index + 1));
+    assert_eq!(
+      error_code(explanation.failing),
+      Some(explanation.code),
+      "{}",
+      explanation.code
+    );
+    assert_eq!(error_code(explanation.fixed), None, "{}", explanation.code);
+  }
+}
+
+#[test]
+fn test_explain() {
+  assert_eq!(explain("t0004").unwrap().code, "T0004");
+  assert!(explain("T9999").is_none());
+
+  let text = explain("T0006").unwrap().to_string();
+  assert!(text.starts_with("T0006: Duplicate parameter\n\n"));
+  assert!(text.contains("\n\n    let fn add x x = + x x;\n\nFixed:"));
+}
@@ -0,0 +1,558 @@
+use crate::{
+  lexer::{tokens::TokenKind, Lexer},
+  parser::ast::{Expression, ExpressionKind, Statement, StatementKind, UnaryOperator},
+};
+
+const INDENT: &str = "  ";
+
+// Pretty-prints `program`, which must have been parsed from `source` without
+// errors. The source is lexed again to find the comments and blank lines the
+// AST doesn't keep
+//
+// The rules:
+// - Two spaces of indentation, one statement per line, and at most one blank
+//   line wherever the source had any
+// - Every statement ends with a semicolon, except expression statements that
+//   end with a block, and the last expression of a block (its value)
+// - Bodies that aren't blocks go on their own indented line, unless the `if`,
+//   `for` or `while` is part of a larger expression
+// - Operands that are binary expressions are parenthesized, except in chains
+//   of the same operator (`+ + a b c`) and where the parentheses would follow
+//   a name and turn it into a call. Other than that, prefix notation never
+//   needs them, so they're only kept where the program would parse
+//   differently without them
+// - Strings use single quotes
+pub fn format(source: &str, program: &[Statement]) -> String {
+  let mut formatter = Formatter::new(source);
+
+  formatter.statements(program, usize::MAX, false);
+  formatter.comments_until(usize::MAX, &mut false);
+
+  if !formatter.output.is_empty() {
+    formatter.output.push('\n');
+  }
+  formatter.output
+}
+
+struct Comment {
+  text: String,
+  // Index of the token after the comment, ignoring whitespace and comments,
+  // which is how the AST counts tokens
+  before: usize,
+  // Whether it's on the same line as the code before it
+  trailing: bool,
+  blank_line: bool,
+}
+
+// What comes right after an expression, which decides whether it needs
+// parentheses
+#[derive(Clone, Copy, Default)]
+struct Followed {
+  // Another expression, which a trailing unary or array literal would take
+  // as an operand or an index
+  by_expression: bool,
+  // An `else`, which a trailing `if` without one would take as its own
+  by_else: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Layout {
+  // Bodies that aren't blocks go on their own line
+  Lines,
+  // Everything that isn't a block stays on one line
+  Inline,
+}
+
+struct Formatter {
+  comments: Vec<Comment>,
+  next_comment: usize,
+  // Whether there's a blank line before each token, by AST token index
+  blank_lines: Vec<bool>,
+  indent: usize,
+  output: String,
+}
+
+impl Formatter {
+  fn new(source: &str) -> Self {
+    let chars = source.chars().collect::<Vec<_>>();
+    let mut comments = Vec::new();
+    let mut blank_lines = Vec::new();
+    // Newlines since the last comment or token, or `None` at the start
+    let mut newlines = None;
+
+    for (token, span) in Lexer::new(source).lex(true) {
+      match token {
+        TokenKind::Whitespace | TokenKind::Newline => {
+          let count = chars[span].iter().filter(|c| **c == '\n').count();
+          newlines = newlines.map(|newlines| newlines + count);
+        }
+        TokenKind::Comment => {
+          let text = chars[span].iter().collect::<String>();
+          comments.push(Comment {
+            text: text.trim_end().to_string(),
+            before: blank_lines.len(),
+            trailing: newlines == Some(0),
+            blank_line: newlines.unwrap_or(0) >= 2,
+          });
+          // Line comments include the newline that ends them
+          newlines = Some(if text.ends_with('\n') { 1 } else { 0 });
+        }
+        _ => {
+          blank_lines.push(newlines.unwrap_or(0) >= 2);
+          newlines = Some(0);
+        }
+      }
+    }
+
+    Formatter {
+      comments,
+      next_comment: 0,
+      blank_lines,
+      indent: 0,
+      output: String::new(),
+    }
+  }
+
+  fn write(&mut self, text: &str) {
+    self.output.push_str(text);
+  }
+
+  fn line(&mut self, blank_line: bool) {
+    if !self.output.is_empty() {
+      if blank_line {
+        self.output.push('\n');
+      }
+      self.output.push('\n');
+    }
+    self.output.push_str(&INDENT.repeat(self.indent));
+  }
+
+  // Writes the comments before the token at `end` on lines of their own
+  fn comments_until(&mut self, end: usize, first: &mut bool) {
+    while let Some(comment) = self.comments.get(self.next_comment) {
+      if comment.before > end {
+        break;
+      }
+
+      let (text, blank_line) = (comment.text.clone(), comment.blank_line && !*first);
+      self.line(blank_line);
+      self.write(&text);
+      self.next_comment += 1;
+      *first = false;
+    }
+  }
+
+  // Writes comments that were on the same line as the end of the statement
+  // before the token at `end`
+  fn trailing_comments(&mut self, end: usize) {
+    while let Some(comment) = self.comments.get(self.next_comment) {
+      if comment.before > end || !comment.trailing {
+        break;
+      }
+
+      let text = format!(" {}", comment.text);
+      self.write(&text);
+      self.next_comment += 1;
+    }
+  }
+
+  // `end` is the index of the token that closes the list
+  fn statements(&mut self, statements: &[Statement], end: usize, block: bool) {
+    let mut first = true;
+
+    for (i, statement) in statements.iter().enumerate() {
+      self.comments_until(statement.span.start, &mut first);
+
+      let blank_line = !first && self.blank_lines[statement.span.start];
+      self.line(blank_line);
+      self.statement(statement, Followed::default(), Layout::Lines);
+      first = false;
+
+      let last = i == statements.len() - 1;
+      let value = block && last && matches!(statement.kind, StatementKind::Expression(_));
+      if !value && !ends_with_block(statement) {
+        self.write(";");
+      }
+
+      self.trailing_comments(match statements.get(i + 1) {
+        Some(next) => next.span.start,
+        None => end,
+      });
+    }
+
+    self.comments_until(end, &mut first);
+  }
+
+  fn block(&mut self, statements: &[Statement], end: usize) {
+    if statements.is_empty()
+      && self
+        .comments
+        .get(self.next_comment)
+        .is_none_or(|comment| comment.before > end)
+    {
+      self.write("{}");
+      return;
+    }
+
+    self.write("{");
+    self.indent += 1;
+    self.statements(statements, end, true);
+    self.indent -= 1;
+    self.line(false);
+    self.write("}");
+  }
+
+  fn statement(&mut self, statement: &Statement, followed: Followed, layout: Layout) {
+    match &statement.kind {
+      StatementKind::Block(statements) => self.block(statements, statement.span.end - 1),
+      StatementKind::Let {
+        name,
+        value,
+        constant,
+        annotation,
+      } => {
+        self.write(if *constant { "let const " } else { "let " });
+        self.write(&name.0);
+        if let Some(annotation) = annotation {
+          self.write(&format!(": {}", annotation));
+        }
+        self.write(" = ");
+        self.expression(value, followed, Layout::Inline);
+      }
+      StatementKind::Expression(expression) => self.expression(expression, followed, layout),
+      StatementKind::FunctionDeclaration {
+        name,
+        parameters,
+        parameter_types,
+        return_type,
+        body,
+      } => {
+        self.write("let fn ");
+        self.write(&name.0);
+        for (parameter, annotation) in parameters.iter().zip(parameter_types) {
+          self.write(" ");
+          match annotation {
+            Some(annotation) => self.write(&format!("({}: {})", parameter.0, annotation)),
+            None => self.write(&parameter.0),
+          }
+        }
+        if let Some(return_type) = return_type {
+          self.write(&format!(" -> {}", return_type));
+        }
+        self.write(" =");
+        self.body(body, followed, Layout::Lines);
+      }
+      StatementKind::Assignment { name, value } => {
+        self.write(&name.0);
+        self.write(" = ");
+        self.expression(value, followed, Layout::Inline);
+      }
+    }
+  }
+
+  // The body of a function, `if`, `for` or `while`, after the space-less
+  // thing before it
+  fn body(&mut self, body: &Statement, followed: Followed, layout: Layout) {
+    match (&body.kind, layout) {
+      (StatementKind::Block(_), _) | (_, Layout::Inline) => {
+        self.write(" ");
+        self.statement(body, followed, layout);
+      }
+      (_, Layout::Lines) => {
+        self.indent += 1;
+        self.line(false);
+        self.statement(body, followed, layout);
+        self.indent -= 1;
+      }
+    }
+  }
+
+  fn expression(&mut self, expression: &Expression, followed: Followed, layout: Layout) {
+    let parenthesize = (followed.by_expression && ends_with(expression, &is_open))
+      || (followed.by_else && ends_with(expression, &is_dangling));
+    let followed = if parenthesize {
+      self.write("(");
+      Followed::default()
+    } else {
+      followed
+    };
+
+    match &expression.kind {
+      ExpressionKind::Void => self.write("void"),
+      ExpressionKind::Identifier(identifier) => self.write(&identifier.0),
+      ExpressionKind::NumberLiteral(number) => self.write(&number.to_string()),
+      ExpressionKind::StringLiteral(string) => self.write(&quote(string)),
+      ExpressionKind::BooleanLiteral(boolean) => self.write(&boolean.to_string()),
+      ExpressionKind::ArrayLiteral(elements) => {
+        self.write("[");
+        self.list(elements);
+        self.write("]");
+      }
+      ExpressionKind::Index { iterable, index } => {
+        self.write("[");
+        self.expression(index, Followed::default(), Layout::Inline);
+        self.write("]");
+        self.expression(iterable, followed, layout);
+      }
+      ExpressionKind::Slice {
+        iterable,
+        start,
+        end,
+      } => {
+        self.write("[");
+        if let Some(start) = start.as_ref() {
+          // `..` starts a range expression
+          let followed = Followed {
+            by_expression: true,
+            ..Default::default()
+          };
+          self.expression(start, followed, Layout::Inline);
+        }
+        self.write("..");
+        if let Some(end) = end.as_ref() {
+          self.expression(end, Followed::default(), Layout::Inline);
+        }
+        self.write("]");
+        self.expression(iterable, followed, layout);
+      }
+      ExpressionKind::FunctionCall { name, arguments } => {
+        self.write(&name.0);
+        self.write("(");
+        self.list(arguments);
+        self.write(")");
+      }
+      ExpressionKind::If {
+        condition,
+        consequence,
+        alternative,
+      } => {
+        let alternative = alternative.as_ref().as_ref();
+
+        self.write("if ");
+        // A parenthesized consequence would look like a call if the condition
+        // ended with a name
+        let dangling = alternative.is_some()
+          && matches!(&consequence.kind, StatementKind::Expression(consequence) if ends_with(consequence, &is_dangling));
+        if dangling {
+          self.write("(");
+          self.expression(condition, Followed::default(), Layout::Inline);
+          self.write(")");
+        } else {
+          self.expression(condition, body_follows(consequence), Layout::Inline);
+        }
+
+        match alternative {
+          Some(alternative) => {
+            let before_else = Followed {
+              by_else: true,
+              ..Default::default()
+            };
+            self.body(consequence, before_else, layout);
+
+            if layout == Layout::Lines && !matches!(consequence.kind, StatementKind::Block(_)) {
+              self.line(false);
+              self.write("else");
+            } else {
+              self.write(" else");
+            }
+
+            match &alternative.kind {
+              // `else if` chains stay flat
+              StatementKind::Expression(Expression {
+                kind: ExpressionKind::If { .. },
+                ..
+              }) => {
+                self.write(" ");
+                self.statement(alternative, followed, layout);
+              }
+              _ => self.body(alternative, followed, layout),
+            }
+          }
+          None => self.body(consequence, followed, layout),
+        }
+      }
+      ExpressionKind::For {
+        variable,
+        iterable,
+        body,
+      } => {
+        self.write("for ");
+        self.write(&variable.0);
+        self.write(" in ");
+        self.expression(iterable, body_follows(body), Layout::Inline);
+        self.body(body, followed, layout);
+      }
+      ExpressionKind::While { condition, body } => {
+        self.write("while ");
+        self.expression(condition, body_follows(body), Layout::Inline);
+        self.body(body, followed, layout);
+      }
+      ExpressionKind::Binary {
+        operator,
+        left,
+        right,
+      } => {
+        self.write(&operator.to_string());
+        self.write(" ");
+        // Chains of the same operator read fine without them
+        let parenthesize_left = matches!(
+          &left.kind,
+          ExpressionKind::Binary { operator: inner, .. } if inner != operator
+        );
+        let before_right = Followed {
+          by_expression: true,
+          ..Default::default()
+        };
+        self.operand(left, before_right, parenthesize_left);
+        self.write(" ");
+        // A parenthesized right operand would look like a call if the left one
+        // ended with a name
+        let parenthesize_right = matches!(right.kind, ExpressionKind::Binary { .. })
+          && (parenthesize_left || !ends_with(left, &is_identifier));
+        self.operand(right, followed, parenthesize_right);
+      }
+      ExpressionKind::Unary { operator, operand } => {
+        self.write(&operator.to_string());
+        // `- -a` rather than `--a`
+        if matches!(operand.kind, ExpressionKind::Unary { .. }) {
+          self.write(" ");
+        }
+        let parenthesize = matches!(operand.kind, ExpressionKind::Binary { .. });
+        self.operand(operand, followed, parenthesize);
+      }
+      ExpressionKind::Yield(value) => {
+        self.write("yield ");
+        self.expression(value, followed, layout);
+      }
+      ExpressionKind::Error => unreachable!("Formatting a program with syntax errors"),
+    }
+
+    if parenthesize {
+      self.write(")");
+    }
+  }
+
+  fn operand(&mut self, operand: &Expression, followed: Followed, parenthesize: bool) {
+    if parenthesize {
+      self.write("(");
+      self.expression(operand, Followed::default(), Layout::Inline);
+      self.write(")");
+    } else {
+      self.expression(operand, followed, Layout::Inline);
+    }
+  }
+
+  fn list(&mut self, expressions: &[Expression]) {
+    for (i, expression) in expressions.iter().enumerate() {
+      if i > 0 {
+        self.write(", ");
+      }
+      self.expression(expression, Followed::default(), Layout::Inline);
+    }
+  }
+}
+
+// What comes after the condition of an `if` or `while`, or the iterable of a
+// `for`
+fn body_follows(body: &Statement) -> Followed {
+  Followed {
+    by_expression: !matches!(body.kind, StatementKind::Block(_)),
+    ..Default::default()
+  }
+}
+
+// Whether the last thing in `expression` could take whatever comes after it
+// as part of itself
+fn ends_with(expression: &Expression, predicate: &dyn Fn(&Expression) -> bool) -> bool {
+  if predicate(expression) {
+    return true;
+  }
+
+  match &expression.kind {
+    ExpressionKind::Index { iterable, .. } | ExpressionKind::Slice { iterable, .. } => {
+      ends_with(iterable, predicate)
+    }
+    ExpressionKind::If {
+      consequence,
+      alternative,
+      ..
+    } => statement_ends_with(
+      alternative.as_ref().as_ref().unwrap_or(consequence),
+      predicate,
+    ),
+    ExpressionKind::For { body, .. } | ExpressionKind::While { body, .. } => {
+      statement_ends_with(body, predicate)
+    }
+    ExpressionKind::Binary { right, .. } => ends_with(right, predicate),
+    ExpressionKind::Unary { operand, .. } | ExpressionKind::Yield(operand) => {
+      ends_with(operand, predicate)
+    }
+    _ => false,
+  }
+}
+
+fn statement_ends_with(statement: &Statement, predicate: &dyn Fn(&Expression) -> bool) -> bool {
+  match &statement.kind {
+    StatementKind::Block(_) => false,
+    StatementKind::Expression(value)
+    | StatementKind::Let { value, .. }
+    | StatementKind::Assignment { value, .. } => ends_with(value, predicate),
+    StatementKind::FunctionDeclaration { body, .. } => statement_ends_with(body, predicate),
+  }
+}
+
+// `- a` followed by `b` is `- a b`, and `[a]` followed by `b` indexes `b`
+fn is_open(expression: &Expression) -> bool {
+  matches!(
+    expression.kind,
+    ExpressionKind::Unary {
+      operator: UnaryOperator::Identity | UnaryOperator::Negate,
+      ..
+    } | ExpressionKind::ArrayLiteral(_)
+  )
+}
+
+fn is_identifier(expression: &Expression) -> bool {
+  matches!(expression.kind, ExpressionKind::Identifier(_))
+}
+
+fn is_dangling(expression: &Expression) -> bool {
+  matches!(&expression.kind, ExpressionKind::If { alternative, .. } if alternative.is_none())
+}
+
+// Whether an expression statement ends with a block, so it doesn't need a
+// semicolon to end it
+fn ends_with_block(statement: &Statement) -> bool {
+  match &statement.kind {
+    StatementKind::Block(_) => true,
+    StatementKind::Expression(expression) => match &expression.kind {
+      ExpressionKind::If {
+        consequence,
+        alternative,
+        ..
+      } => ends_with_block(alternative.as_ref().as_ref().unwrap_or(consequence)),
+      ExpressionKind::For { body, .. } | ExpressionKind::While { body, .. } => {
+        ends_with_block(body)
+      }
+      _ => false,
+    },
+    _ => false,
+  }
+}
+
+fn quote(string: &str) -> String {
+  let mut quoted = String::from("'");
+  for c in string.chars() {
+    match c {
+      '\\' => quoted.push_str("\\\\"),
+      '\'' => quoted.push_str("\\'"),
+      '\n' => quoted.push_str("\\n"),
+      '\r' => quoted.push_str("\\r"),
+      '\t' => quoted.push_str("\\t"),
+      '\0' => quoted.push_str("\\0"),
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('\'');
+  quoted
+}
@@ -0,0 +1,7 @@
+#[allow(clippy::module_inception)]
+mod formatter;
+
+#[cfg(test)]
+mod tests;
+
+pub use formatter::*;
@@ -0,0 +1,68 @@
+use crate::{
+  formatter::format,
+  test_util::{examples, parse},
+};
+use regex::Regex;
+
+fn format_source(source: &str) -> String {
+  format(source, &parse(source))
+}
+
+// The AST without spans, which formatting is allowed to change
+fn shape(source: &str) -> String {
+  let spans = Regex::new(r"\d+\.\.\d+").unwrap();
+  spans
+    .replace_all(&format!("{:?}", parse(source)), "")
+    .to_string()
+}
+
+#[test]
+fn test_format() {
+  let source = "
+// Leading comment
+let x = - (-1) 2 ; let y = (+ x 1) // trailing
+
+
+
+let fn f a b = { if a { println(\"it's\") } else b };
+let z = [if x if y 1 else 2, [0]x];
+if (- a) (if b c) else d
+while < x 10 x = + x 1
+let fn add (a:Number) b->[Number|Void] = + a b; let const s : String = ''
+";
+
+  assert_eq!(
+    format_source(source),
+    "// Leading comment
+let x = - (-1) 2;
+let y = + x 1; // trailing
+
+let fn f a b = {
+  if a {
+    println('it\\'s')
+  } else
+    b
+};
+let z = [if x if y 1 else 2, [0]x];
+if (-a)
+  (if b
+    c)
+else
+  d;
+while < x 10
+  x = + x 1;
+let fn add (a: Number) b -> [Number | Void] =
+  + a b;
+let const s: String = '';
+"
+  );
+}
+
+#[test]
+fn test_examples() {
+  examples(|path, source| {
+    let formatted = format_source(source);
+    assert_eq!(shape(&formatted), shape(source), "{}", path.display());
+    assert_eq!(format_source(&formatted), formatted, "{}", path.display());
+  });
+}
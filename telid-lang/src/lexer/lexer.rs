@@ -9,7 +9,7 @@ pub struct Lexer<'a> {
 }
 
 impl<'a> Lexer<'a> {
-  pub fn new(source: &'a str) -> Lexer {
+  pub fn new(source: &'a str) -> Lexer<'a> {
     Lexer {
       source,
       start: 0,
@@ -19,7 +19,7 @@ impl<'a> Lexer<'a> {
 
   pub fn lex(&mut self, emit_ignored: bool) -> Vec<(TokenKind, Range<usize>)> {
     let mut tokens = Vec::new();
-    let chars = self.source.clone();
+    let chars = self.source;
     let mut chars = chars.chars().peekable();
 
     while let Some(c) = chars.next() {
@@ -63,7 +63,7 @@ impl<'a> Lexer<'a> {
         let mut has_dot = false;
 
         while let Some(d) = chars.peek() {
-          if !d.is_digit(10) && *d != '.' {
+          if !d.is_ascii_digit() && *d != '.' {
             break;
           }
 
@@ -74,7 +74,7 @@ impl<'a> Lexer<'a> {
               let mut chars = chars.clone();
               chars.next();
               if let Some(e) = chars.peek() {
-                if !e.is_digit(10) {
+                if !e.is_ascii_digit() {
                   break;
                 }
               }
@@ -135,7 +135,7 @@ impl<'a> Lexer<'a> {
         let mut literal = c.to_string();
 
         while let Some(d) = chars.peek() {
-          if !d.is_alphabetic() && !d.is_digit(10) && *d != '_' && *d != '$' {
+          if !d.is_alphabetic() && !d.is_ascii_digit() && *d != '_' && *d != '$' {
             break;
           }
 
@@ -147,14 +147,22 @@ impl<'a> Lexer<'a> {
       }
 
       '+' => TokenKind::Plus,
-      '-' => TokenKind::Minus,
+      '-' => {
+        if let Some('>') = chars.peek() {
+          chars.next();
+          self.current += 1;
+          TokenKind::Arrow
+        } else {
+          TokenKind::Minus
+        }
+      }
       '*' => TokenKind::Asterisk,
       '/' => {
         if let Some('/') = chars.peek() {
           chars.next();
           self.current += 1;
 
-          while let Some(c) = chars.next() {
+          for c in chars.by_ref() {
             self.current += 1;
 
             if c == '\n' {
@@ -259,6 +267,7 @@ impl<'a> Lexer<'a> {
         }
       }
       ';' => TokenKind::Semicolon,
+      ':' => TokenKind::Colon,
 
       _ => TokenKind::Error(LexError::UnexpectedCharacter(c)),
     }
@@ -77,3 +77,32 @@ fn test_identifiers_keywords() {
     ]
   );
 }
+
+#[test]
+fn test_errors() {
+  let source = "a $ b @ 'c";
+
+  let mut lexer = Lexer::new(source);
+  let tokens = lexer.lex(false);
+
+  assert_eq!(
+    tokens,
+    vec![
+      (TokenKind::Identifier("a".to_string()), 0..1),
+      (
+        TokenKind::Error(crate::error::LexError::UnexpectedCharacter('$')),
+        2..3
+      ),
+      (TokenKind::Identifier("b".to_string()), 4..5),
+      (
+        TokenKind::Error(crate::error::LexError::UnexpectedCharacter('@')),
+        6..7
+      ),
+      (
+        TokenKind::Error(crate::error::LexError::UnterminatedStringLiteral),
+        8..10
+      ),
+      (TokenKind::Eof, 8..10),
+    ]
+  );
+}
@@ -26,6 +26,7 @@ pub enum TokenKind {
   For,
   While,
   In,
+  Yield,
 
   Plus,
   Minus,
@@ -46,6 +47,8 @@ pub enum TokenKind {
   GreaterThanEquals,
 
   Equals,
+  Colon,
+  Arrow,
 
   LeftParen,
   RightParen,
@@ -63,6 +66,11 @@ pub enum TokenKind {
   Eof,
 }
 
+// Words that `TokenKind::from_identifier` doesn't lex as identifiers
+pub const KEYWORDS: [&str; 12] = [
+  "let", "const", "fn", "if", "else", "for", "while", "in", "yield", "true", "false", "void",
+];
+
 impl TokenKind {
   pub fn from_identifier(identifier: String) -> Self {
     let identifier = identifier.as_str();
@@ -75,6 +83,7 @@ impl TokenKind {
       "for" => Self::For,
       "while" => Self::While,
       "in" => Self::In,
+      "yield" => Self::Yield,
       "true" => Self::BooleanLiteral(true),
       "false" => Self::BooleanLiteral(false),
       "void" => Self::Void,
@@ -1,5 +1,14 @@
+pub mod checker;
+pub mod debugger;
+pub mod error;
 pub mod evaluator;
+pub mod explain;
+pub mod formatter;
 pub mod lexer;
+pub mod linter;
 pub mod parser;
-
-mod error;
+pub mod position;
+pub mod program;
+pub mod resolver;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,411 @@
+use crate::{
+  error::{ResolveErrorKind, Warning, WarningKind},
+  evaluator::scope::Scope,
+  lexer::{tokens::TokenKind, Lexer},
+  parser::ast::{BinaryOperator, Expression, ExpressionKind, Statement, StatementKind},
+  position::LineIndex,
+  resolver::{resolve, Declaration, DeclarationKind, Resolution},
+};
+use std::{
+  collections::{HashMap, HashSet},
+  ops::Range,
+};
+
+const IGNORE: &str = "telid-ignore";
+
+// Looks for likely mistakes in `program`, which must have been parsed from
+// `source` without errors. `scope` is the scope it will be run in. Warnings on
+// a line with a `// telid-ignore` comment, or right after a line with only
+// one, are left out. The comment can list the kinds of warnings to ignore
+// (`// telid-ignore UnusedVariable, Shadowing`), and ignores all of them
+// otherwise
+pub fn lint(source: &str, program: &[Statement], scope: &Scope) -> Vec<Warning> {
+  let resolution = resolve(program, scope);
+  let lines = Lines::new(source);
+
+  let mut linter = Linter {
+    resolution: &resolution,
+    lines: &lines,
+    warnings: Vec::new(),
+  };
+  linter.statements(program);
+  linter.resolution_warnings(scope);
+
+  let mut warnings = linter.warnings;
+  warnings.retain(|warning| !lines.ignored(warning));
+  warnings.sort_by_key(|warning| (warning.span.start, warning.span.end));
+  warnings
+}
+
+// Where tokens and `telid-ignore` comments are in the source
+struct Lines {
+  // Line each token starts and ends on, by AST token index
+  starts: Vec<usize>,
+  ends: Vec<usize>,
+  // Line and the kinds of warnings to ignore on it, or `None` for all of them
+  ignores: Vec<(usize, Option<Vec<String>>)>,
+}
+
+impl Lines {
+  fn new(source: &str) -> Self {
+    let chars = source.chars().collect::<Vec<_>>();
+    let index = LineIndex::new(source);
+    let line = |offset: usize| index.line(offset);
+
+    let mut starts = Vec::new();
+    let mut ends = Vec::new();
+    // Comments that were alone on their line apply to the next token's line
+    let mut pending = Vec::new();
+    let mut ignores = Vec::new();
+
+    for (token, span) in Lexer::new(source).lex(true) {
+      match token {
+        TokenKind::Whitespace | TokenKind::Newline | TokenKind::Error(_) => {}
+        TokenKind::Comment => {
+          let text = chars[span.clone()].iter().collect::<String>();
+          let Some(kinds) = text
+            .strip_prefix("//")
+            .map(str::trim)
+            .and_then(|text| text.strip_prefix(IGNORE))
+          else {
+            continue;
+          };
+
+          let kinds = kinds
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|kind| !kind.is_empty())
+            .map(String::from)
+            .collect::<Vec<_>>();
+          let kinds = if kinds.is_empty() { None } else { Some(kinds) };
+
+          if ends.last() == Some(&line(span.start)) {
+            ignores.push((line(span.start), kinds));
+          } else {
+            pending.push(kinds);
+          }
+        }
+        _ => {
+          let start = line(span.start);
+          for kinds in pending.drain(..) {
+            ignores.push((start, kinds));
+          }
+          starts.push(start);
+          ends.push(line(span.end));
+        }
+      }
+    }
+
+    Lines {
+      starts,
+      ends,
+      ignores,
+    }
+  }
+
+  fn ignored(&self, warning: &Warning) -> bool {
+    let line = self.starts[warning.span.start.min(self.starts.len() - 1)];
+    self.ignores.iter().any(|(ignored, kinds)| {
+      *ignored == line
+        && kinds
+          .as_ref()
+          .is_none_or(|kinds| kinds.iter().any(|kind| kind == warning.kind.as_ref()))
+    })
+  }
+
+  // Whether the token at `after` starts on a later line than the one at
+  // `before` ends
+  fn broken(&self, before: usize, after: usize) -> bool {
+    self.starts[after] > self.ends[before]
+  }
+}
+
+struct Linter<'a> {
+  resolution: &'a Resolution,
+  lines: &'a Lines,
+  warnings: Vec<Warning>,
+}
+
+impl Linter<'_> {
+  fn warn(&mut self, kind: WarningKind, span: Range<usize>) {
+    self.warnings.push(Warning { kind, span });
+  }
+
+  fn statements(&mut self, statements: &[Statement]) {
+    for statement in statements {
+      self.statement(statement);
+    }
+
+    // Only the first statement that can't be reached is worth pointing out
+    if let Some(exit) = statements.iter().position(|s| self.diverges(s)) {
+      if let (Some(first), Some(last)) = (statements.get(exit + 1), statements.last()) {
+        self.warn(
+          WarningKind::UnreachableCode,
+          first.span.start..last.span.end,
+        );
+      }
+    }
+  }
+
+  fn statement(&mut self, statement: &Statement) {
+    match &statement.kind {
+      StatementKind::Block(statements) => self.statements(statements),
+      StatementKind::Let { value, .. }
+      | StatementKind::Assignment { value, .. }
+      | StatementKind::Expression(value) => self.expression(value),
+      StatementKind::FunctionDeclaration { body, .. } => self.statement(body),
+    }
+  }
+
+  fn expression(&mut self, expression: &Expression) {
+    match &expression.kind {
+      ExpressionKind::Void
+      | ExpressionKind::Identifier(_)
+      | ExpressionKind::NumberLiteral(_)
+      | ExpressionKind::StringLiteral(_)
+      | ExpressionKind::BooleanLiteral(_)
+      | ExpressionKind::Error => {}
+      ExpressionKind::ArrayLiteral(expressions) => {
+        for expression in expressions {
+          self.expression(expression);
+        }
+      }
+      ExpressionKind::Index { iterable, index } => {
+        // `[0]` on the next line indexes whatever follows it, but `]` on the
+        // line before indexes this
+        if self
+          .lines
+          .broken(iterable.span.start - 1, iterable.span.start)
+        {
+          self.warn(WarningKind::AmbiguousContinuation, expression.span.clone());
+        }
+        self.expression(index);
+        self.expression(iterable);
+      }
+      ExpressionKind::Slice {
+        iterable,
+        start,
+        end,
+      } => {
+        for bound in [start.as_ref(), end.as_ref()].into_iter().flatten() {
+          self.expression(bound);
+        }
+        self.expression(iterable);
+      }
+      ExpressionKind::FunctionCall { name, arguments } => {
+        // `let x = y` followed by `(* 2 3)` on the next line is `y(* 2 3)`
+        if self.lines.broken(name.1.start, name.1.end) {
+          self.warn(WarningKind::AmbiguousContinuation, expression.span.clone());
+        }
+
+        if let Some(expected) = self.parameter_count(name.1.start) {
+          if arguments.len() != expected {
+            self.warn(
+              WarningKind::IncorrectParameterCount(name.0.clone(), arguments.len(), expected),
+              expression.span.clone(),
+            );
+          }
+        }
+
+        for argument in arguments {
+          self.expression(argument);
+        }
+      }
+      ExpressionKind::If {
+        condition,
+        consequence,
+        alternative,
+      } => {
+        self.expression(condition);
+        self.statement(consequence);
+        if let Some(alternative) = alternative.as_ref() {
+          self.statement(alternative);
+        }
+      }
+      ExpressionKind::For { iterable, body, .. } => {
+        self.expression(iterable);
+        self.statement(body);
+      }
+      ExpressionKind::While { condition, body } => {
+        self.expression(condition);
+        self.statement(body);
+      }
+      ExpressionKind::Binary {
+        operator,
+        left,
+        right,
+      } => {
+        // `- a` followed by `b` on the next line is `- a b`
+        if matches!(operator, BinaryOperator::Add | BinaryOperator::Subtract)
+          && self.lines.broken(left.span.end - 1, right.span.start)
+        {
+          self.warn(WarningKind::AmbiguousContinuation, expression.span.clone());
+        }
+        self.expression(left);
+        self.expression(right);
+      }
+      ExpressionKind::Unary { operand, .. } | ExpressionKind::Yield(operand) => {
+        self.expression(operand)
+      }
+    }
+  }
+
+  // The number of parameters of the function called by the name at `token`,
+  // if it's known
+  fn parameter_count(&self, token: usize) -> Option<usize> {
+    let declaration = self.resolution.declaration_at(token)?;
+    match &self.resolution.declarations[declaration].kind {
+      DeclarationKind::Function { parameters } => Some(parameters.len()),
+      DeclarationKind::Global { parameters, .. } => *parameters,
+      _ => None,
+    }
+  }
+
+  // Whether running `statement` always ends the program
+  fn diverges(&self, statement: &Statement) -> bool {
+    match &statement.kind {
+      StatementKind::Block(statements) => statements.iter().any(|s| self.diverges(s)),
+      StatementKind::Expression(expression) => match &expression.kind {
+        ExpressionKind::FunctionCall { name, .. } => {
+          name.0 == "exit"
+            && self
+              .resolution
+              .declaration_at(name.1.start)
+              .is_some_and(|declaration| {
+                matches!(
+                  self.resolution.declarations[declaration].kind,
+                  DeclarationKind::Global { .. }
+                )
+              })
+        }
+        ExpressionKind::If {
+          consequence,
+          alternative,
+          ..
+        } => alternative
+          .as_ref()
+          .as_ref()
+          .is_some_and(|alternative| self.diverges(consequence) && self.diverges(alternative)),
+        _ => false,
+      },
+      _ => false,
+    }
+  }
+
+  // Warnings that only need to know what each name refers to
+  fn resolution_warnings(&mut self, scope: &Scope) {
+    let resolution = self.resolution;
+
+    let assignments = resolution
+      .references
+      .iter()
+      .filter(|reference| reference.assignment)
+      .map(|reference| &reference.span)
+      .collect::<HashSet<_>>();
+
+    for error in &resolution.errors {
+      let kind = match &error.kind {
+        ResolveErrorKind::UndefinedVariable(name) => {
+          if assignments.contains(&error.span) {
+            WarningKind::UndeclaredAssignment(name.clone())
+          } else {
+            WarningKind::UndefinedVariable(name.clone())
+          }
+        }
+        ResolveErrorKind::DuplicateParameter(name) => WarningKind::DuplicateParameter(name.clone()),
+        ResolveErrorKind::ConstantReassignment(name) => {
+          WarningKind::ConstantReassignment(name.clone())
+        }
+        // An error rather than a likely mistake, which `telid check` reports
+        ResolveErrorKind::YieldOutsideGenerator => continue,
+      };
+      self.warn(kind, error.span.clone());
+    }
+
+    // Assignments inside functions to names the function can only see if its
+    // caller declared them
+    let reported = resolution
+      .errors
+      .iter()
+      .map(|error| &error.span)
+      .collect::<HashSet<_>>();
+    for reference in &resolution.references {
+      if reference.assignment
+        && reference.declaration.is_none()
+        && !reported.contains(&reference.span)
+      {
+        self.warn(
+          WarningKind::UndeclaredAssignment(reference.name.clone()),
+          reference.span.clone(),
+        );
+      }
+    }
+
+    // Names read from somewhere we couldn't resolve could belong to any
+    // declaration with that name
+    let dynamic = resolution
+      .references
+      .iter()
+      .filter(|reference| reference.declaration.is_none() && !reference.assignment)
+      .map(|reference| reference.name.as_str())
+      .collect::<HashSet<_>>();
+
+    // Which declarations are read anywhere, and the declarations with each
+    // name, so that neither check looks through everything for each one
+    let mut used = vec![false; resolution.declarations.len()];
+    for reference in &resolution.references {
+      if let (Some(declaration), false) = (reference.declaration, reference.assignment) {
+        used[declaration] = true;
+      }
+    }
+    let mut named = HashMap::<&str, Vec<&Declaration>>::new();
+    for declaration in &resolution.declarations {
+      named
+        .entry(declaration.name.as_str())
+        .or_default()
+        .push(declaration);
+    }
+
+    for (index, declaration) in resolution.declarations.iter().enumerate() {
+      let Some(span) = &declaration.span else {
+        continue;
+      };
+
+      let used = used[index];
+      let unused_kind = matches!(
+        declaration.kind,
+        DeclarationKind::Variable { .. }
+          | DeclarationKind::Function { .. }
+          | DeclarationKind::LoopVariable
+      );
+      // `telid test` calls test functions
+      let test = matches!(declaration.kind, DeclarationKind::Function { .. })
+        && declaration.name.starts_with("test_");
+      if unused_kind
+        && !used
+        && !test
+        && !declaration.name.starts_with('_')
+        && !dynamic.contains(declaration.name.as_str())
+      {
+        self.warn(
+          WarningKind::UnusedVariable(declaration.name.clone()),
+          span.clone(),
+        );
+      }
+
+      let shadows_declaration = named[declaration.name.as_str()].iter().any(|other| {
+        other.visible.contains(&span.start)
+          && other.span.as_ref().is_some_and(|other| other.start < span.start)
+          // Already a duplicate parameter
+          && !(matches!(other.kind, DeclarationKind::Parameter { .. })
+            && other.kind == declaration.kind
+            && other.visible == declaration.visible)
+      });
+      if shadows_declaration || scope.get(&declaration.name).is_some() {
+        self.warn(
+          WarningKind::Shadowing(declaration.name.clone()),
+          span.clone(),
+        );
+      }
+    }
+  }
+}
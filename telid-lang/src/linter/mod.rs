@@ -0,0 +1,7 @@
+#[allow(clippy::module_inception)]
+mod linter;
+
+#[cfg(test)]
+mod tests;
+
+pub use linter::*;
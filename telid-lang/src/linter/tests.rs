@@ -0,0 +1,82 @@
+use crate::{error::WarningKind, evaluator::scope, linter::lint, test_util::parse};
+
+fn lint_source(source: &str) -> Vec<WarningKind> {
+  lint(source, &parse(source), &scope::default())
+    .into_iter()
+    .map(|warning| warning.kind)
+    .collect()
+}
+
+#[test]
+fn test_lint() {
+  assert_eq!(
+    lint_source(
+      "
+      let y = 1;
+      let x = y
+      (* 2 3);
+      let z = - x
+      1;
+      let unused = 1;
+      let fn test_unused = assert(true);
+      let fn f a = {
+        let a = 1;
+        + a 1
+      };
+      f(1, 2);
+      undeclared = 1;
+      let const c = 1;
+      c = 2;
+      for _ in .. 1 2 println(z);
+      exit(0);
+      println(c);
+      "
+    ),
+    vec![
+      WarningKind::AmbiguousContinuation,
+      WarningKind::AmbiguousContinuation,
+      WarningKind::UnusedVariable(String::from("unused")),
+      WarningKind::Shadowing(String::from("a")),
+      WarningKind::IncorrectParameterCount(String::from("f"), 2, 1),
+      WarningKind::UndeclaredAssignment(String::from("undeclared")),
+      WarningKind::ConstantReassignment(String::from("c")),
+      WarningKind::UnreachableCode,
+    ]
+  );
+
+  // Functions can see their caller's variables, so neither of these is
+  // unused or undeclared
+  assert_eq!(
+    lint_source(
+      "
+      let fn count = { total = + total 1 };
+      let total = 0;
+      let fn show = println(shown);
+      let fn call = { let shown = 1; show() };
+      call();
+      count();
+      "
+    ),
+    vec![]
+  );
+}
+
+#[test]
+fn test_ignore() {
+  assert_eq!(
+    lint_source(
+      "
+      // telid-ignore
+      let a = 1;
+      let b = 1; // telid-ignore UnusedVariable
+      // telid-ignore Shadowing
+      let c = 1;
+      let println = 1; // telid-ignore UnusedVariable
+      "
+    ),
+    vec![
+      WarningKind::UnusedVariable(String::from("c")),
+      WarningKind::Shadowing(String::from("println")),
+    ]
+  );
+}
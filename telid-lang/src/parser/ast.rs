@@ -1,4 +1,4 @@
-use std::ops::Range;
+use std::{fmt, ops::Range};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Statement {
@@ -13,11 +13,15 @@ pub enum StatementKind {
     name: Identifier,
     value: Expression,
     constant: bool,
+    annotation: Option<Type>,
   },
   Expression(Expression),
   FunctionDeclaration {
     name: Identifier,
     parameters: Vec<Identifier>,
+    // The annotation of each parameter, in the same order
+    parameter_types: Vec<Option<Type>>,
+    return_type: Option<Type>,
     body: Box<Statement>,
   },
   Assignment {
@@ -26,12 +30,65 @@ pub enum StatementKind {
   },
 }
 
+impl Statement {
+  // Whether the statement has a `yield` in it, outside of any function
+  // declared in it. A function whose body does is a generator
+  pub fn yields(&self) -> bool {
+    match &self.kind {
+      StatementKind::Block(statements) => statements.iter().any(Statement::yields),
+      StatementKind::Let { value, .. }
+      | StatementKind::Expression(value)
+      | StatementKind::Assignment { value, .. } => value.yields(),
+      StatementKind::FunctionDeclaration { .. } => false,
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Expression {
   pub kind: ExpressionKind,
   pub span: Range<usize>,
 }
 
+impl Expression {
+  pub fn yields(&self) -> bool {
+    match &self.kind {
+      ExpressionKind::Yield(_) => true,
+      ExpressionKind::ArrayLiteral(elements) => elements.iter().any(Expression::yields),
+      ExpressionKind::FunctionCall { arguments, .. } => arguments.iter().any(Expression::yields),
+      ExpressionKind::Index { iterable, index } => iterable.yields() || index.yields(),
+      ExpressionKind::Slice {
+        iterable,
+        start,
+        end,
+      } => {
+        iterable.yields()
+          || start.as_ref().as_ref().is_some_and(Expression::yields)
+          || end.as_ref().as_ref().is_some_and(Expression::yields)
+      }
+      ExpressionKind::If {
+        condition,
+        consequence,
+        alternative,
+      } => {
+        condition.yields()
+          || consequence.yields()
+          || alternative.as_ref().as_ref().is_some_and(Statement::yields)
+      }
+      ExpressionKind::For { iterable, body, .. } => iterable.yields() || body.yields(),
+      ExpressionKind::While { condition, body } => condition.yields() || body.yields(),
+      ExpressionKind::Binary { left, right, .. } => left.yields() || right.yields(),
+      ExpressionKind::Unary { operand, .. } => operand.yields(),
+      ExpressionKind::Void
+      | ExpressionKind::Identifier(_)
+      | ExpressionKind::NumberLiteral(_)
+      | ExpressionKind::StringLiteral(_)
+      | ExpressionKind::BooleanLiteral(_)
+      | ExpressionKind::Error => false,
+    }
+  }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum ExpressionKind {
   Void,
@@ -76,10 +133,71 @@ pub enum ExpressionKind {
     operator: UnaryOperator,
     operand: Box<Expression>,
   },
+  // Makes the function it's in a generator
+  Yield(Box<Expression>),
+  // Left behind by the parser where it recovered from a syntax error
+  Error,
 }
 
+// The name, the span of the token it came from, and where the evaluator can
+// find it
 #[derive(Debug, Clone, PartialEq)]
-pub struct Identifier(pub String);
+pub struct Identifier(pub String, pub Range<usize>, pub Binding);
+
+// Filled in by `resolver::bind`. `Static` names the variable by how many
+// frames out from the innermost one it's in, and its slot in that frame
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Binding {
+  #[default]
+  Dynamic,
+  Static {
+    depth: usize,
+    slot: usize,
+  },
+}
+
+// What a value is annotated with. Values of type `Any` aren't checked
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+  Any,
+  Void,
+  Number,
+  String,
+  Boolean,
+  Regex,
+  Function,
+  Generator,
+  Range,
+  Array(Box<Type>),
+  Union(Vec<Type>),
+}
+
+impl fmt::Display for Type {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Type::Any => write!(f, "Any"),
+      Type::Void => write!(f, "Void"),
+      Type::Number => write!(f, "Number"),
+      Type::String => write!(f, "String"),
+      Type::Boolean => write!(f, "Boolean"),
+      Type::Regex => write!(f, "Regex"),
+      Type::Function => write!(f, "Function"),
+      Type::Generator => write!(f, "Generator"),
+      Type::Range => write!(f, "Range"),
+      Type::Array(element) if **element == Type::Any => write!(f, "Array"),
+      Type::Array(element) => write!(f, "[{}]", element),
+      Type::Union(types) => write!(
+        f,
+        "{}",
+        types
+          .iter()
+          .map(|t| t.to_string())
+          .collect::<Vec<_>>()
+          .join(" | ")
+      ),
+    }
+  }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOperator {
@@ -99,24 +217,25 @@ pub enum BinaryOperator {
   Range,
 }
 
-impl ToString for BinaryOperator {
-  fn to_string(&self) -> String {
-    match self {
-      BinaryOperator::Add => "+".to_string(),
-      BinaryOperator::Subtract => "-".to_string(),
-      BinaryOperator::Multiply => "*".to_string(),
-      BinaryOperator::Divide => "/".to_string(),
-      BinaryOperator::Modulo => "%".to_string(),
-      BinaryOperator::Equal => "==".to_string(),
-      BinaryOperator::NotEqual => "!=".to_string(),
-      BinaryOperator::LessThan => "<".to_string(),
-      BinaryOperator::LessThanOrEqual => "<=".to_string(),
-      BinaryOperator::GreaterThan => ">".to_string(),
-      BinaryOperator::GreaterThanOrEqual => ">=".to_string(),
-      BinaryOperator::And => "&&".to_string(),
-      BinaryOperator::Or => "||".to_string(),
-      BinaryOperator::Range => "..".to_string(),
-    }
+impl fmt::Display for BinaryOperator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let operator = match self {
+      BinaryOperator::Add => "+",
+      BinaryOperator::Subtract => "-",
+      BinaryOperator::Multiply => "*",
+      BinaryOperator::Divide => "/",
+      BinaryOperator::Modulo => "%",
+      BinaryOperator::Equal => "==",
+      BinaryOperator::NotEqual => "!=",
+      BinaryOperator::LessThan => "<",
+      BinaryOperator::LessThanOrEqual => "<=",
+      BinaryOperator::GreaterThan => ">",
+      BinaryOperator::GreaterThanOrEqual => ">=",
+      BinaryOperator::And => "&&",
+      BinaryOperator::Or => "||",
+      BinaryOperator::Range => "..",
+    };
+    write!(f, "{}", operator)
   }
 }
 
@@ -127,12 +246,13 @@ pub enum UnaryOperator {
   Not,
 }
 
-impl ToString for UnaryOperator {
-  fn to_string(&self) -> String {
-    match self {
-      UnaryOperator::Identity => "+".to_string(),
-      UnaryOperator::Negate => "-".to_string(),
-      UnaryOperator::Not => "!".to_string(),
-    }
+impl fmt::Display for UnaryOperator {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let operator = match self {
+      UnaryOperator::Identity => "+",
+      UnaryOperator::Negate => "-",
+      UnaryOperator::Not => "!",
+    };
+    write!(f, "{}", operator)
   }
 }
@@ -1,4 +1,5 @@
 pub mod ast;
+#[allow(clippy::module_inception)]
 mod parser;
 
 #[cfg(test)]
@@ -1,11 +1,13 @@
-use super::ast::{Expression, ExpressionKind, Identifier, Statement, StatementKind};
+use super::ast::{Binding, Expression, ExpressionKind, Identifier, Statement, StatementKind, Type};
 use crate::lexer::tokens::TokenKind;
 use chumsky::{
   prelude::Simple,
-  primitive::{choice, just},
+  primitive::{choice, just, none_of},
+  recovery::{nested_delimiters, skip_until},
   recursive::recursive,
   select, Parser,
 };
+use std::ops::Range;
 
 macro_rules! delimited_list {
   ($element:expr, $separator:expr, $left:expr, $right:expr) => {
@@ -16,18 +18,66 @@ macro_rules! delimited_list {
   };
 }
 
+// Stands in for whatever couldn't be parsed, so that parsing can carry on and
+// report every error in one go
+fn error_statement(span: Range<usize>) -> Statement {
+  Statement {
+    kind: StatementKind::Expression(Expression {
+      kind: ExpressionKind::Error,
+      span: span.clone(),
+    }),
+    span,
+  }
+}
+
+#[allow(clippy::result_large_err)]
 pub fn parser() -> impl Parser<TokenKind, Vec<Statement>, Error = Simple<TokenKind>> {
   // For when we don't want to wrap the identifier in an expression
-  let identifier = select! { TokenKind::Identifier(identifier) => Identifier(identifier) };
-  let literal = select! {
+  let identifier = select! { |span| TokenKind::Identifier(identifier) => Identifier(identifier, span, Binding::Dynamic) };
+  let literal = select! { |span|
     TokenKind::Void => ExpressionKind::Void,
-    TokenKind::Identifier(identifier) => ExpressionKind::Identifier(Identifier(identifier)),
+    TokenKind::Identifier(identifier) => ExpressionKind::Identifier(Identifier(identifier, span, Binding::Dynamic)),
     TokenKind::NumberLiteral(number) => ExpressionKind::NumberLiteral(*number),
     TokenKind::StringLiteral(string) => ExpressionKind::StringLiteral(string),
     TokenKind::BooleanLiteral(boolean) => ExpressionKind::BooleanLiteral(boolean),
   }
   .map_with_span(|kind, span| Expression { kind, span });
 
+  // `Number`, `[String]`, `Number | Void`
+  let type_annotation = recursive(|type_annotation| {
+    let named = select! { TokenKind::Identifier(name) => name }.try_map(|name, span| {
+      Ok(match name.as_str() {
+        "Any" => Type::Any,
+        "Void" => Type::Void,
+        "Number" => Type::Number,
+        "String" => Type::String,
+        "Boolean" => Type::Boolean,
+        "Regex" => Type::Regex,
+        "Function" => Type::Function,
+        "Generator" => Type::Generator,
+        "Range" => Type::Range,
+        "Array" => Type::Array(Box::new(Type::Any)),
+        _ => return Err(Simple::custom(span, format!("Unknown type: {}", name))),
+      })
+    });
+
+    let array = type_annotation
+      .delimited_by(just(TokenKind::LeftBracket), just(TokenKind::RightBracket))
+      .map(|element| Type::Array(Box::new(element)));
+
+    named
+      .or(array)
+      .separated_by(just(TokenKind::Pipe))
+      .at_least(1)
+      .map(|mut types| {
+        if types.len() == 1 {
+          types.remove(0)
+        } else {
+          Type::Union(types)
+        }
+      })
+  });
+
   let statement = recursive(|statement| {
     let expression = recursive(|expression| {
       let binary_operator = choice((
@@ -164,9 +214,28 @@ pub fn parser() -> impl Parser<TokenKind, Vec<Statement>, Error = Simple<TokenKi
           span,
         });
 
+      let yield_expression = just(TokenKind::Yield)
+        .ignore_then(expression.clone())
+        .map_with_span(|value, span| Expression {
+          kind: ExpressionKind::Yield(Box::new(value)),
+          span,
+        });
+
       // Grouping
       expression
         .delimited_by(just(TokenKind::LeftParen), just(TokenKind::RightParen))
+        .recover_with(nested_delimiters(
+          TokenKind::LeftParen,
+          TokenKind::RightParen,
+          [
+            (TokenKind::LeftBracket, TokenKind::RightBracket),
+            (TokenKind::LeftBrace, TokenKind::RightBrace),
+          ],
+          |span| Expression {
+            kind: ExpressionKind::Error,
+            span,
+          },
+        ))
         .or(binary_operator)
         .or(unary_operator)
         .or(index)
@@ -176,6 +245,7 @@ pub fn parser() -> impl Parser<TokenKind, Vec<Statement>, Error = Simple<TokenKi
         .or(if_expression)
         .or(for_loop)
         .or(while_loop)
+        .or(yield_expression)
         .or(literal)
     });
 
@@ -187,54 +257,102 @@ pub fn parser() -> impl Parser<TokenKind, Vec<Statement>, Error = Simple<TokenKi
         span,
       });
 
+    let annotation = just(TokenKind::Colon).ignore_then(type_annotation.clone());
+
     let variable_declaration = just(TokenKind::Let)
-    .ignore_then(just(TokenKind::Const).or_not())
-    .then(identifier)
-    .then_ignore(just(TokenKind::Equals))
-    .then(expression.clone())
-    .map_with_span(|((constant, name), value), span| Statement {
-      kind: StatementKind::Let {
-        name,
-        value,
-        constant: constant.is_some(),
-      },
-      span,
-    });
+      .ignore_then(just(TokenKind::Const).or_not())
+      .then(identifier)
+      .then(annotation.clone().or_not())
+      .then_ignore(just(TokenKind::Equals))
+      .then(expression.clone())
+      .map_with_span(|(((constant, name), annotation), value), span| Statement {
+        kind: StatementKind::Let {
+          name,
+          value,
+          constant: constant.is_some(),
+          annotation,
+        },
+        span,
+      });
+
+    // `a` or `(a: Number)`
+    let parameter = identifier.map(|name| (name, None)).or(
+      identifier
+        .then(annotation.map(Some))
+        .delimited_by(just(TokenKind::LeftParen), just(TokenKind::RightParen)),
+    );
 
     let function_declaration = just(TokenKind::Let)
-    .then(just(TokenKind::Fn))
-    .ignore_then(identifier)
-    .then(identifier.repeated())
-    .then_ignore(just(TokenKind::Equals))
-    .then(statement.clone())
-    .map_with_span(|((name, parameters), body), span| Statement {
-      kind: StatementKind::FunctionDeclaration {
-        name,
-        parameters,
-        body: Box::new(body),
-      },
-      span,
-    });
+      .then(just(TokenKind::Fn))
+      .ignore_then(identifier)
+      .then(parameter.repeated())
+      .then(just(TokenKind::Arrow).ignore_then(type_annotation).or_not())
+      .then_ignore(just(TokenKind::Equals))
+      .then(statement.clone())
+      .map_with_span(|(((name, parameters), return_type), body), span| {
+        let (parameters, parameter_types) = parameters.into_iter().unzip();
+        Statement {
+          kind: StatementKind::FunctionDeclaration {
+            name,
+            parameters,
+            parameter_types,
+            return_type,
+            body: Box::new(body),
+          },
+          span,
+        }
+      });
 
     let expression_statement = expression.map_with_span(|expression, span| Statement {
       kind: StatementKind::Expression(expression),
       span,
     });
 
-    statement
-      .clone()
-      .repeated()
-      .delimited_by(just(TokenKind::LeftBrace), just(TokenKind::RightBrace))
-      .map_with_span(|statements, span| Statement {
-        kind: StatementKind::Block(statements),
-        span,
-      })
-      .or(assignment)
-      .or(variable_declaration)
-      .or(function_declaration)
-      .or(expression_statement)
+    // Recovery below must always consume something, so a statement can't
+    // start at the end of a block or of the input
+    none_of([TokenKind::RightBrace, TokenKind::Eof])
+      .rewind()
+      .ignore_then(
+        statement
+          .clone()
+          .repeated()
+          .delimited_by(just(TokenKind::LeftBrace), just(TokenKind::RightBrace))
+          .map_with_span(|statements, span| Statement {
+            kind: StatementKind::Block(statements),
+            span,
+          })
+          .recover_with(nested_delimiters(
+            TokenKind::LeftBrace,
+            TokenKind::RightBrace,
+            [
+              (TokenKind::LeftParen, TokenKind::RightParen),
+              (TokenKind::LeftBracket, TokenKind::RightBracket),
+            ],
+            error_statement,
+          ))
+          .or(assignment)
+          .or(variable_declaration)
+          .or(function_declaration)
+          .or(expression_statement)
+          // Throw away the rest of a broken statement so the next one can be parsed
+          .recover_with(skip_until(
+            [TokenKind::Semicolon, TokenKind::RightBrace, TokenKind::Eof],
+            error_statement,
+          )),
+      )
       .then_ignore(just(TokenKind::Semicolon).or_not())
   });
 
   statement.repeated().then_ignore(just(TokenKind::Eof))
 }
+
+// Whether `tokens` stop partway through something, like after `{` or an
+// operator, so that more input could still make them parse. Error tokens
+// should already be left out
+pub fn incomplete(tokens: &[TokenKind]) -> bool {
+  let end = tokens.len().saturating_sub(1);
+  match parser().parse(tokens.to_vec()) {
+    Ok(_) => false,
+    Err(errors) => errors.iter().any(|error| error.span().start >= end),
+  }
+}
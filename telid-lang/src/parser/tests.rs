@@ -3,7 +3,7 @@ use crate::{
   parser::ast::{BinaryOperator, Statement, StatementKind},
   parser::{
     ast::{Expression, ExpressionKind, UnaryOperator},
-    parser,
+    incomplete, parser,
   },
 };
 use chumsky::Parser;
@@ -80,3 +80,59 @@ fn test_operators() {
     }])
   );
 }
+
+#[test]
+fn test_error_recovery() {
+  let source = "let a = (+ 1 2 3); let b = 2; { let c = ] } let d = 4";
+
+  let mut lexer = Lexer::new(source);
+  let tokens = lexer.lex(false);
+  let tokens = tokens.iter().map(|t| t.0.clone()).collect::<Vec<_>>();
+
+  let (ast, errors) = parser().parse_recovery(tokens);
+
+  assert_eq!(errors.len(), 2);
+  assert_eq!(errors[0].span(), 7..8);
+  assert_eq!(errors[1].span(), 19..20);
+
+  let names = ast
+    .unwrap()
+    .into_iter()
+    .filter_map(|statement| match statement.kind {
+      StatementKind::Let { name, .. } => Some(name.0),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+
+  assert_eq!(names, vec!["a", "b", "d"]);
+}
+
+#[test]
+fn test_incomplete() {
+  let incomplete = |source: &str| {
+    let tokens = Lexer::new(source).lex(false);
+    incomplete(&tokens.into_iter().map(|t| t.0).collect::<Vec<_>>())
+  };
+
+  for source in [
+    "let fn f x = {",
+    "println(1,",
+    "let x = [1, 2",
+    "let x = +",
+    "let x = * 2",
+    "let fn f x =",
+    "{ let x = 1; (",
+  ] {
+    assert!(incomplete(source), "{}", source);
+  }
+
+  for source in [
+    "",
+    "let x = 1",
+    "let fn f x = {\n  x\n}",
+    "let x = )",
+    "} 1 {",
+  ] {
+    assert!(!incomplete(source), "{}", source);
+  }
+}
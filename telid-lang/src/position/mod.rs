@@ -0,0 +1,7 @@
+#[allow(clippy::module_inception)]
+mod position;
+
+pub use position::*;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,103 @@
+use crate::lexer::{tokens::TokenKind, Lexer};
+
+// Where a token starts, counting lines and characters within the line from 0
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Position {
+  pub line: usize,
+  pub column: usize,
+}
+
+// The character offset each line of a source starts at, so that finding the
+// line of an offset doesn't mean counting the newlines before it. Also
+// converts the lexer's character offsets to bytes, and to the UTF-16 columns
+// editors count in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+  starts: Vec<usize>,
+  chars: Vec<char>,
+  // Byte offset each character starts at, then the length of the source
+  bytes: Vec<usize>,
+}
+
+impl LineIndex {
+  pub fn new(source: &str) -> Self {
+    let mut starts = vec![0];
+    let mut chars = Vec::new();
+    let mut bytes = Vec::new();
+    for (offset, (byte, c)) in source.char_indices().enumerate() {
+      if c == '\n' {
+        starts.push(offset + 1);
+      }
+      chars.push(c);
+      bytes.push(byte);
+    }
+    bytes.push(source.len());
+    LineIndex {
+      starts,
+      chars,
+      bytes,
+    }
+  }
+
+  // The line the character at `offset` is on
+  pub fn line(&self, offset: usize) -> usize {
+    self.starts.partition_point(|start| *start <= offset) - 1
+  }
+
+  pub fn position(&self, offset: usize) -> Position {
+    let line = self.line(offset);
+    Position {
+      line,
+      column: offset - self.starts[line],
+    }
+  }
+
+  // The byte offset of the character at `offset`, or the source's length if
+  // it's past the end
+  pub fn byte(&self, offset: usize) -> usize {
+    self.bytes[offset.min(self.chars.len())]
+  }
+
+  // The column of `offset` in UTF-16 code units
+  pub fn utf16_column(&self, offset: usize) -> usize {
+    let offset = offset.min(self.chars.len());
+    self.chars[self.starts[self.line(offset)]..offset]
+      .iter()
+      .map(|c| c.len_utf16())
+      .sum()
+  }
+
+  // The character offset at a line and UTF-16 column, kept within the line
+  pub fn utf16_offset(&self, line: usize, column: usize) -> usize {
+    let mut offset = self.starts[line.min(self.starts.len() - 1)];
+    let mut units = 0;
+    while offset < self.chars.len() && self.chars[offset] != '\n' {
+      units += self.chars[offset].len_utf16();
+      if units > column {
+        break;
+      }
+      offset += 1;
+    }
+    offset
+  }
+}
+
+// Where each token the parser sees in `source` starts, by AST token index
+pub fn positions(source: &str) -> Vec<Position> {
+  let lines = LineIndex::new(source);
+  Lexer::new(source)
+    .lex(false)
+    .into_iter()
+    .filter(|token| !matches!(token.0, TokenKind::Error(_)))
+    .map(|(_, span)| lines.position(span.start))
+    .collect()
+}
+
+// Where the token at `token` starts, or the last one if it's past the end
+pub fn position_of(positions: &[Position], token: usize) -> Position {
+  positions
+    .get(token)
+    .or(positions.last())
+    .copied()
+    .unwrap_or_default()
+}
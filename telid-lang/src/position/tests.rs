@@ -0,0 +1,50 @@
+use crate::position::{position_of, positions, LineIndex, Position};
+
+#[test]
+fn test_line_index() {
+  let lines = LineIndex::new("ab\n\ncdé\nf");
+  assert_eq!(
+    (0..10).map(|offset| lines.line(offset)).collect::<Vec<_>>(),
+    vec![0, 0, 0, 1, 2, 2, 2, 2, 3, 3]
+  );
+  assert_eq!(lines.position(6), Position { line: 2, column: 2 });
+
+  // 'é' is two bytes and '😀' is two UTF-16 units
+  let lines = LineIndex::new("é😀a\nb");
+  assert_eq!(
+    (0..7).map(|offset| lines.byte(offset)).collect::<Vec<_>>(),
+    vec![0, 2, 6, 7, 8, 9, 9]
+  );
+  assert_eq!(
+    (0..6)
+      .map(|offset| lines.utf16_column(offset))
+      .collect::<Vec<_>>(),
+    vec![0, 1, 3, 4, 0, 1]
+  );
+  assert_eq!(
+    (0..6)
+      .map(|column| lines.utf16_offset(0, column))
+      .collect::<Vec<_>>(),
+    vec![0, 1, 1, 2, 3, 3]
+  );
+  assert_eq!(lines.utf16_offset(1, 0), 4);
+  assert_eq!(lines.utf16_offset(9, 9), 5);
+}
+
+#[test]
+fn test_positions() {
+  let positions = positions("let x =\n  1;");
+  assert_eq!(
+    positions,
+    vec![
+      Position { line: 0, column: 0 },
+      Position { line: 0, column: 4 },
+      Position { line: 0, column: 6 },
+      Position { line: 1, column: 2 },
+      Position { line: 1, column: 3 },
+      // The end of the input
+      Position { line: 1, column: 3 },
+    ]
+  );
+  assert_eq!(position_of(&positions, 99), Position { line: 1, column: 3 });
+}
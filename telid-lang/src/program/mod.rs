@@ -0,0 +1,7 @@
+#[allow(clippy::module_inception)]
+mod program;
+
+#[cfg(test)]
+mod tests;
+
+pub use program::*;
@@ -0,0 +1,166 @@
+use crate::{
+  checker::check,
+  error::{
+    parse_error_code, parse_error_message, parse_error_report, source_span, LexError, ParseError,
+    ResolveError, TypeError,
+  },
+  evaluator::scope::Scope,
+  lexer::{tokens::TokenKind, Lexer},
+  parser::{ast::Statement, parser},
+  resolver::{bind, Resolution},
+};
+use ariadne::Report;
+use chumsky::Parser;
+use std::ops::Range;
+
+pub type Tokens = Vec<(TokenKind, Range<usize>)>;
+
+// `resolve`, or `resolve_partial` for code that later input can add to
+pub type Resolve = fn(&[Statement], &Scope) -> Resolution;
+
+// Source that has been lexed and parsed, and possibly resolved. Where there
+// were errors, the AST is whatever the parser recovered
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+  // Without error tokens, so that indices match the AST's spans
+  pub tokens: Tokens,
+  pub ast: Vec<Statement>,
+  // Ordered by where they start in the source
+  pub errors: Vec<Diagnostic>,
+  // Type errors in code without annotations, which don't stop it from
+  // running, since the code might never run. Also ordered
+  pub warnings: Vec<Diagnostic>,
+  // Once it's been compiled, even if the parser had to recover the AST, so
+  // that tools can look names up in code that doesn't parse yet
+  pub resolution: Option<Resolution>,
+}
+
+// Something that stops a program from running, or a warning about it
+#[derive(Debug, Clone, PartialEq)]
+pub enum Diagnostic {
+  // The span is in characters, since the token was left out
+  Lex(LexError, Range<usize>),
+  Parse(ParseError),
+  Resolve(ResolveError),
+  Type(TypeError),
+}
+
+impl Diagnostic {
+  // Where it is in the source, in characters
+  pub fn span(&self, tokens: &[(TokenKind, Range<usize>)]) -> Range<usize> {
+    match self {
+      Diagnostic::Lex(_, span) => span.clone(),
+      Diagnostic::Parse(error) => source_span(tokens, error.span()),
+      Diagnostic::Resolve(error) => source_span(tokens, error.span.clone()),
+      Diagnostic::Type(error) => source_span(tokens, error.span.clone()),
+    }
+  }
+
+  // Its stable code, which `telid --explain` describes
+  pub fn code(&self) -> &'static str {
+    match self {
+      Diagnostic::Lex(error, _) => error.code(),
+      Diagnostic::Parse(error) => parse_error_code(error),
+      Diagnostic::Resolve(error) => error.kind.code(),
+      Diagnostic::Type(error) => error.kind.code(),
+    }
+  }
+
+  pub fn message(&self) -> String {
+    match self {
+      Diagnostic::Lex(error, _) => error.to_string(),
+      Diagnostic::Parse(error) => parse_error_message(error),
+      Diagnostic::Resolve(error) => error.kind.to_string(),
+      Diagnostic::Type(error) => error.kind.to_string(),
+    }
+  }
+
+  pub fn report<'a>(
+    &self,
+    src: &'a str,
+    tokens: &[(TokenKind, Range<usize>)],
+  ) -> Report<'a, (&'a str, Range<usize>)> {
+    match self {
+      Diagnostic::Lex(error, span) => error.report(src, span.clone()),
+      Diagnostic::Parse(error) => parse_error_report(error, src, tokens),
+      Diagnostic::Resolve(error) => error.report(src, tokens),
+      Diagnostic::Type(error) => error.report(src, tokens),
+    }
+  }
+}
+
+impl Program {
+  pub fn is_ok(&self) -> bool {
+    self.errors.is_empty()
+  }
+
+  fn add_errors(&mut self, errors: impl IntoIterator<Item = Diagnostic>) {
+    self.errors.extend(errors);
+    let tokens = &self.tokens;
+    self.errors.sort_by_key(|error| error.span(tokens).start);
+  }
+}
+
+// Lexes and parses `source`, collecting every lexical and syntax error
+pub fn parse(source: &str) -> Program {
+  let tokens = Lexer::new(source).lex(false);
+
+  let mut errors = Vec::new();
+  for (token, span) in &tokens {
+    if let TokenKind::Error(error) = token {
+      errors.push(Diagnostic::Lex(*error, span.clone()));
+    }
+  }
+
+  // The parser never sees the bad tokens, so spans have to be resolved
+  // against the filtered list
+  let tokens = tokens
+    .into_iter()
+    .filter(|token| !matches!(token.0, TokenKind::Error(_)))
+    .collect::<Vec<_>>();
+
+  let (ast, parse_errors) =
+    parser().parse_recovery(tokens.iter().map(|t| t.0.clone()).collect::<Vec<_>>());
+  errors.extend(parse_errors.into_iter().map(Diagnostic::Parse));
+
+  let mut program = Program {
+    tokens,
+    ast: ast.unwrap_or_default(),
+    errors: Vec::new(),
+    warnings: Vec::new(),
+    resolution: None,
+  };
+  program.add_errors(errors);
+  program
+}
+
+// Parses `source`, then resolves its names against `scope` and checks its
+// types, reporting the errors of only the first of those that finds any. Type errors
+// that don't involve an annotation are only warnings. Without any errors, the
+// AST is bound and ready to evaluate in `scope`
+pub fn compile(source: &str, scope: &Scope, resolve: Resolve) -> Program {
+  let mut program = parse(source);
+  let parsed = program.is_ok();
+  let resolution = resolve(&program.ast, scope);
+  let errors = resolution.errors.clone();
+  let resolution = program.resolution.insert(resolution);
+  if !parsed {
+    return program;
+  }
+  if !errors.is_empty() {
+    program.add_errors(errors.into_iter().map(Diagnostic::Resolve));
+    return program;
+  }
+
+  let (errors, warnings): (Vec<_>, Vec<_>) = check(&program.ast, resolution, scope)
+    .into_iter()
+    .partition(|error| error.annotated);
+  program.warnings = warnings.into_iter().map(Diagnostic::Type).collect();
+  if !errors.is_empty() {
+    program.add_errors(errors.into_iter().map(Diagnostic::Type));
+    return program;
+  }
+
+  bind(&mut program.ast, resolution);
+  program
+}
@@ -0,0 +1,57 @@
+use crate::{
+  error::LexError,
+  evaluator::{evaluate, scope, value::Value},
+  program::{compile, parse, Diagnostic},
+  resolver::{resolve, resolve_partial},
+};
+
+#[test]
+fn test_parse_errors() {
+  // Every error is reported, in the order they appear
+  let program = parse("let x = 1; let = 2; let y = #;");
+
+  assert_eq!(program.errors.len(), 3);
+  assert!(matches!(program.errors[0], Diagnostic::Parse(_)));
+  assert!(matches!(
+    program.errors[1],
+    Diagnostic::Lex(LexError::UnexpectedCharacter('#'), _)
+  ));
+  assert_eq!(program.errors[1].span(&program.tokens), 28..29);
+  // The statements around the errors are still parsed
+  assert!(!program.ast.is_empty());
+}
+
+#[test]
+fn test_compile() {
+  let scope = scope::default();
+
+  let program = compile("let x = 1; + x y", &scope, resolve);
+  assert!(matches!(program.errors[..], [Diagnostic::Resolve(_)]));
+
+  // Names are still looked up in what the parser recovered, but only the
+  // syntax error is reported
+  let program = compile("let x = 1; let = 2; + x y", &scope, resolve);
+  assert!(matches!(program.errors[..], [Diagnostic::Parse(_)]));
+  let resolution = program.resolution.unwrap();
+  assert!(resolution.declarations.iter().any(|d| d.name == "x"));
+
+  // Later input could still declare `y`
+  let program = compile("let fn f = y; 1", &scope, resolve_partial);
+  assert!(program.is_ok());
+
+  let program = compile("let x: Number = 'a'", &scope, resolve);
+  assert!(matches!(program.errors[..], [Diagnostic::Type(_)]));
+
+  // Without an annotation, a type error only matters if the code runs
+  let program = compile("if false + 1 true else 'ok'", &scope, resolve);
+  assert!(program.is_ok());
+  assert!(matches!(program.warnings[..], [Diagnostic::Type(_)]));
+  assert_eq!(
+    evaluate(program.ast, scope.clone()).unwrap().0,
+    Value::String(String::from("ok"))
+  );
+
+  let program = compile("let x = 1; + x 2", &scope, resolve);
+  assert!(program.is_ok());
+  assert_eq!(evaluate(program.ast, scope).unwrap().0, Value::Number(3.0));
+}
@@ -0,0 +1,7 @@
+#[allow(clippy::module_inception)]
+mod resolver;
+
+#[cfg(test)]
+mod tests;
+
+pub use resolver::*;
@@ -0,0 +1,739 @@
+use crate::{
+  error::{ResolveError, ResolveErrorKind},
+  evaluator::{scope::Scope, value::Value},
+  parser::ast::{Binding, Expression, ExpressionKind, Identifier, Statement, StatementKind},
+};
+use std::{
+  collections::{HashMap, HashSet},
+  ops::Range,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+  pub name: String,
+  pub kind: DeclarationKind,
+  // Span of the declaring identifier, in tokens. `None` for names that come
+  // from the scope the program is run in (builtins, earlier REPL lines)
+  pub span: Option<Range<usize>>,
+  // Span of the construct the declaration is visible in, in tokens
+  pub visible: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeclarationKind {
+  Variable {
+    constant: bool,
+  },
+  Function {
+    parameters: Vec<String>,
+  },
+  Parameter {
+    function: String,
+  },
+  LoopVariable,
+  Global {
+    constant: bool,
+    parameters: Option<usize>,
+  },
+}
+
+impl DeclarationKind {
+  pub fn is_constant(&self) -> bool {
+    match self {
+      DeclarationKind::Variable { constant } | DeclarationKind::Global { constant, .. } => {
+        *constant
+      }
+      DeclarationKind::Function { .. } => false,
+      DeclarationKind::Parameter { .. } | DeclarationKind::LoopVariable => true,
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+  pub name: String,
+  pub span: Range<usize>,
+  // Index into `Resolution::declarations`, or `None` if the name can only be
+  // found at runtime (Telid functions see their caller's variables)
+  pub declaration: Option<usize>,
+  pub assignment: bool,
+  // Where the evaluator will find the variable, if that's known
+  pub binding: Binding,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Resolution {
+  pub declarations: Vec<Declaration>,
+  pub references: Vec<Reference>,
+  pub errors: Vec<ResolveError>,
+}
+
+impl Resolution {
+  // The declaration a token belongs to, whether it's the declaring identifier
+  // or a use of it
+  pub fn declaration_at(&self, token: usize) -> Option<usize> {
+    self
+      .declarations
+      .iter()
+      .position(|declaration| {
+        declaration
+          .span
+          .as_ref()
+          .is_some_and(|span| span.contains(&token))
+      })
+      .or_else(|| {
+        self
+          .references
+          .iter()
+          .find(|reference| reference.span.contains(&token))
+          .and_then(|reference| reference.declaration)
+      })
+  }
+
+  pub fn references_to(&self, declaration: usize) -> impl Iterator<Item = &Reference> {
+    self
+      .references
+      .iter()
+      .filter(move |reference| reference.declaration == Some(declaration))
+  }
+}
+
+// Binds every identifier in `program` to the declaration it refers to, as far
+// as that can be known without running it. `scope` is the scope the program
+// will be evaluated in, and is where names that aren't declared in the
+// program itself are looked up
+pub fn resolve(program: &[Statement], scope: &Scope) -> Resolution {
+  resolve_program(program, scope, true)
+}
+
+// Like `resolve`, for code that more code will be run after in the same
+// scope, like a line in the REPL. Functions can use names nothing declares
+// yet, since a later line might
+pub fn resolve_partial(program: &[Statement], scope: &Scope) -> Resolution {
+  resolve_program(program, scope, false)
+}
+
+fn resolve_program(program: &[Statement], scope: &Scope, complete: bool) -> Resolution {
+  let mut resolver = Resolver {
+    scope,
+    scopes: vec![HashMap::new()],
+    visible: vec![Range {
+      start: 0,
+      end: usize::MAX,
+    }],
+    frames: vec![Frame {
+      slots: HashMap::new(),
+      len: scope.len(),
+      stable: true,
+      conditional: 0,
+    }],
+    locations: Vec::new(),
+    functions: Vec::new(),
+    conditional: 0,
+    pending: Vec::new(),
+    function_depth: 0,
+    declared: HashSet::new(),
+    complete,
+    resolution: Resolution::default(),
+  };
+
+  collect_names(program, &mut resolver.declared);
+
+  for statement in program {
+    resolver.statement(statement);
+  }
+  resolver.pop_scope();
+  resolver.finish();
+
+  resolver.resolution
+}
+
+// Stores the resolved bindings in `program`'s identifiers, so the evaluator
+// can find variables by position instead of by name
+pub fn bind(program: &mut [Statement], resolution: &Resolution) {
+  let bindings = resolution
+    .references
+    .iter()
+    .map(|reference| (reference.span.start, reference.binding))
+    .collect::<HashMap<_, _>>();
+
+  for statement in program {
+    bind_statement(statement, &bindings);
+  }
+}
+
+struct Resolver<'a> {
+  scope: &'a Scope,
+  // Name to declaration index, innermost scope last
+  scopes: Vec<HashMap<String, usize>>,
+  visible: Vec<Range<usize>>,
+  // The slots the evaluator will give variables, for each scope
+  frames: Vec<Frame>,
+  // Scope index and slot of each declaration, unless its slot depends on
+  // which branches run
+  locations: Vec<Option<(usize, usize)>>,
+  // Indices of the scopes function parameters are declared in. Bodies are
+  // run in their caller's scope, so only names declared inside the function
+  // are at a known position
+  functions: Vec<usize>,
+  // How many `if` branches without their own block we're in
+  conditional: usize,
+  // References inside function bodies that weren't declared yet when the body
+  // was resolved, with the number of scopes open at the time. Functions can
+  // call things declared after them, as long as they exist by the time the
+  // function runs
+  pending: Vec<(usize, usize)>,
+  function_depth: usize,
+  // Every name declared anywhere in the program
+  declared: HashSet<String>,
+  // Whether nothing will be run in the scope after the program
+  complete: bool,
+  resolution: Resolution,
+}
+
+struct Frame {
+  slots: HashMap<String, usize>,
+  len: usize,
+  // Cleared once a new name is declared conditionally, since every slot
+  // after it depends on whether that happened
+  stable: bool,
+  // The enclosing scope's `Resolver::conditional`
+  conditional: usize,
+}
+
+impl Resolver<'_> {
+  fn push_scope(&mut self, visible: Range<usize>) {
+    self.scopes.push(HashMap::new());
+    self.visible.push(visible);
+    self.frames.push(Frame {
+      slots: HashMap::new(),
+      len: 0,
+      stable: true,
+      conditional: self.conditional,
+    });
+    self.conditional = 0;
+  }
+
+  fn pop_scope(&mut self) {
+    let depth = self.scopes.len();
+    let scope = self.scopes.pop().unwrap();
+    self.visible.pop();
+    if let Some(frame) = self.frames.pop() {
+      self.conditional = frame.conditional;
+    }
+
+    self.pending.retain(|(reference, pending_depth)| {
+      if *pending_depth < depth {
+        return true;
+      }
+
+      let reference = &mut self.resolution.references[*reference];
+      match scope.get(&reference.name) {
+        Some(declaration) => {
+          reference.declaration = Some(*declaration);
+          false
+        }
+        None => true,
+      }
+    });
+
+    // Anything still pending now belongs to the enclosing scope
+    for (_, pending_depth) in self.pending.iter_mut() {
+      *pending_depth = (*pending_depth).min(depth - 1);
+    }
+  }
+
+  fn declare(&mut self, identifier: &Identifier, kind: DeclarationKind) -> usize {
+    let location = self
+      .slot(&identifier.0)
+      .map(|slot| (self.scopes.len() - 1, slot));
+    self.locations.push(location);
+
+    let index = self.resolution.declarations.len();
+    self.resolution.declarations.push(Declaration {
+      name: identifier.0.clone(),
+      kind,
+      span: Some(identifier.1.clone()),
+      visible: self.visible.last().unwrap().clone(),
+    });
+    self
+      .scopes
+      .last_mut()
+      .unwrap()
+      .insert(identifier.0.clone(), index);
+    index
+  }
+
+  // The slot the evaluator will declare `name` in, in the innermost scope
+  fn slot(&mut self, name: &str) -> Option<usize> {
+    let root = self.frames.len() == 1;
+    let frame = self.frames.last_mut().unwrap();
+
+    // Declaring a name again replaces the variable in its slot
+    let existing = frame.slots.get(name).copied().or_else(|| {
+      root
+        .then(|| self.scope.position(name))
+        .flatten()
+        .filter(|(depth, _)| *depth == 0)
+        .map(|(_, slot)| slot)
+    });
+    if let Some(slot) = existing {
+      return Some(slot);
+    }
+
+    if self.conditional > 0 {
+      frame.stable = false;
+    }
+    if !frame.stable {
+      return None;
+    }
+
+    let slot = frame.len;
+    frame.len += 1;
+    frame.slots.insert(name.to_string(), slot);
+    Some(slot)
+  }
+
+  fn binding(&self, declaration: usize) -> Binding {
+    let Some((scope, slot)) = self.locations[declaration] else {
+      return Binding::Dynamic;
+    };
+    if self
+      .functions
+      .last()
+      .is_some_and(|function| scope < *function)
+    {
+      return Binding::Dynamic;
+    }
+
+    Binding::Static {
+      depth: self.scopes.len() - 1 - scope,
+      slot,
+    }
+  }
+
+  fn lookup(&mut self, name: &str) -> Option<usize> {
+    for scope in self.scopes.iter().rev() {
+      if let Some(declaration) = scope.get(name) {
+        return Some(*declaration);
+      }
+    }
+
+    let variable = self.scope.get(name)?;
+    let index = self.resolution.declarations.len();
+    self.resolution.declarations.push(Declaration {
+      name: name.to_string(),
+      kind: DeclarationKind::Global {
+        constant: variable.constant,
+        parameters: match &variable.value {
+          Value::RustFunction {
+            parameter_count, ..
+          } => Some(*parameter_count),
+          Value::Function { parameters, .. } => Some(parameters.len()),
+          _ => None,
+        },
+      },
+      span: None,
+      visible: 0..usize::MAX,
+    });
+    // Only variables in the innermost frame are known to stay where they are
+    self.locations.push(
+      self
+        .scope
+        .position(name)
+        .filter(|(depth, _)| *depth == 0)
+        .map(|(_, slot)| (0, slot)),
+    );
+    // Later lookups of the same global should find this declaration
+    self.scopes[0].insert(name.to_string(), index);
+    Some(index)
+  }
+
+  fn reference(&mut self, identifier: &Identifier, assignment: bool) {
+    let declaration = self.lookup(&identifier.0);
+    let index = self.resolution.references.len();
+    self.resolution.references.push(Reference {
+      name: identifier.0.clone(),
+      span: identifier.1.clone(),
+      declaration,
+      assignment,
+      binding: declaration.map_or(Binding::Dynamic, |declaration| self.binding(declaration)),
+    });
+
+    match declaration {
+      Some(declaration) => {
+        if assignment && self.resolution.declarations[declaration].kind.is_constant() {
+          self.error(
+            ResolveErrorKind::ConstantReassignment(identifier.0.clone()),
+            identifier.1.clone(),
+          );
+        }
+      }
+      None if self.function_depth > 0 => self.pending.push((index, self.scopes.len())),
+      None => self.error(
+        ResolveErrorKind::UndefinedVariable(identifier.0.clone()),
+        identifier.1.clone(),
+      ),
+    }
+  }
+
+  fn finish(&mut self) {
+    for (reference, _) in std::mem::take(&mut self.pending) {
+      let reference = self.resolution.references[reference].clone();
+      // Declared somewhere we can't see from here, so it might still be in
+      // scope at runtime
+      if !self.complete || self.declared.contains(&reference.name) {
+        continue;
+      }
+
+      self.error(
+        ResolveErrorKind::UndefinedVariable(reference.name),
+        reference.span,
+      );
+    }
+
+    self
+      .resolution
+      .errors
+      .sort_by_key(|error| (error.span.start, error.span.end));
+  }
+
+  fn error(&mut self, kind: ResolveErrorKind, span: Range<usize>) {
+    self.resolution.errors.push(ResolveError { kind, span });
+  }
+
+  fn statement(&mut self, statement: &Statement) {
+    match &statement.kind {
+      StatementKind::Block(statements) => {
+        self.push_scope(statement.span.clone());
+        for statement in statements {
+          self.statement(statement);
+        }
+        self.pop_scope();
+      }
+      StatementKind::Let {
+        name,
+        value,
+        constant,
+        ..
+      } => {
+        self.expression(value);
+        self.declare(
+          name,
+          DeclarationKind::Variable {
+            constant: *constant,
+          },
+        );
+      }
+      StatementKind::Expression(expression) => self.expression(expression),
+      StatementKind::FunctionDeclaration {
+        name,
+        parameters,
+        body,
+        ..
+      } => {
+        self.declare(
+          name,
+          DeclarationKind::Function {
+            parameters: parameters.iter().map(|p| p.0.clone()).collect(),
+          },
+        );
+
+        self.push_scope(statement.span.clone());
+        self.functions.push(self.scopes.len() - 1);
+        let mut seen = HashSet::new();
+        for parameter in parameters {
+          if !seen.insert(parameter.0.clone()) {
+            self.error(
+              ResolveErrorKind::DuplicateParameter(parameter.0.clone()),
+              parameter.1.clone(),
+            );
+          }
+          self.declare(
+            parameter,
+            DeclarationKind::Parameter {
+              function: name.0.clone(),
+            },
+          );
+        }
+
+        self.function_depth += 1;
+        self.statement(body);
+        self.function_depth -= 1;
+        self.functions.pop();
+        self.pop_scope();
+      }
+      StatementKind::Assignment { name, value } => {
+        self.expression(value);
+        self.reference(name, true);
+      }
+    }
+  }
+
+  // A branch that isn't a block declares things in the enclosing scope, but
+  // only if it runs
+  fn branch(&mut self, statement: &Statement) {
+    let block = matches!(statement.kind, StatementKind::Block(_));
+    if !block {
+      self.conditional += 1;
+    }
+    self.statement(statement);
+    if !block {
+      self.conditional -= 1;
+    }
+  }
+
+  fn expression(&mut self, expression: &Expression) {
+    match &expression.kind {
+      ExpressionKind::Void
+      | ExpressionKind::NumberLiteral(_)
+      | ExpressionKind::StringLiteral(_)
+      | ExpressionKind::BooleanLiteral(_)
+      | ExpressionKind::Error => {}
+      ExpressionKind::Identifier(identifier) => self.reference(identifier, false),
+      ExpressionKind::ArrayLiteral(expressions) => {
+        for expression in expressions {
+          self.expression(expression);
+        }
+      }
+      ExpressionKind::Index { iterable, index } => {
+        self.expression(iterable);
+        self.expression(index);
+      }
+      ExpressionKind::Slice {
+        iterable,
+        start,
+        end,
+      } => {
+        self.expression(iterable);
+        if let Some(start) = start.as_ref() {
+          self.expression(start);
+        }
+        if let Some(end) = end.as_ref() {
+          self.expression(end);
+        }
+      }
+      ExpressionKind::FunctionCall { name, arguments } => {
+        self.reference(name, false);
+        for argument in arguments {
+          self.expression(argument);
+        }
+      }
+      ExpressionKind::If {
+        condition,
+        consequence,
+        alternative,
+      } => {
+        self.expression(condition);
+        self.branch(consequence);
+        if let Some(alternative) = alternative.as_ref() {
+          self.branch(alternative);
+        }
+      }
+      ExpressionKind::For {
+        variable,
+        iterable,
+        body,
+      } => {
+        self.expression(iterable);
+        self.push_scope(expression.span.clone());
+        self.declare(variable, DeclarationKind::LoopVariable);
+        self.statement(body);
+        self.pop_scope();
+      }
+      ExpressionKind::While { condition, body } => {
+        self.expression(condition);
+        self.push_scope(expression.span.clone());
+        self.statement(body);
+        self.pop_scope();
+      }
+      ExpressionKind::Binary { left, right, .. } => {
+        self.expression(left);
+        self.expression(right);
+      }
+      ExpressionKind::Unary { operand, .. } => self.expression(operand),
+      ExpressionKind::Yield(value) => {
+        if self.function_depth == 0 {
+          self.error(
+            ResolveErrorKind::YieldOutsideGenerator,
+            expression.span.clone(),
+          );
+        }
+        self.expression(value);
+      }
+    }
+  }
+}
+
+fn bind_identifier(identifier: &mut Identifier, bindings: &HashMap<usize, Binding>) {
+  if let Some(binding) = bindings.get(&identifier.1.start) {
+    identifier.2 = *binding;
+  }
+}
+
+fn bind_statement(statement: &mut Statement, bindings: &HashMap<usize, Binding>) {
+  match &mut statement.kind {
+    StatementKind::Block(statements) => {
+      for statement in statements {
+        bind_statement(statement, bindings);
+      }
+    }
+    StatementKind::Let { value, .. } | StatementKind::Expression(value) => {
+      bind_expression(value, bindings)
+    }
+    StatementKind::Assignment { name, value } => {
+      bind_identifier(name, bindings);
+      bind_expression(value, bindings);
+    }
+    StatementKind::FunctionDeclaration { body, .. } => bind_statement(body, bindings),
+  }
+}
+
+fn bind_expression(expression: &mut Expression, bindings: &HashMap<usize, Binding>) {
+  match &mut expression.kind {
+    ExpressionKind::Identifier(identifier) => bind_identifier(identifier, bindings),
+    ExpressionKind::ArrayLiteral(expressions) => {
+      for expression in expressions {
+        bind_expression(expression, bindings);
+      }
+    }
+    ExpressionKind::Index { iterable, index } => {
+      bind_expression(iterable, bindings);
+      bind_expression(index, bindings);
+    }
+    ExpressionKind::Slice {
+      iterable,
+      start,
+      end,
+    } => {
+      bind_expression(iterable, bindings);
+      for bound in [start.as_mut(), end.as_mut()].into_iter().flatten() {
+        bind_expression(bound, bindings);
+      }
+    }
+    ExpressionKind::FunctionCall { name, arguments } => {
+      bind_identifier(name, bindings);
+      for argument in arguments {
+        bind_expression(argument, bindings);
+      }
+    }
+    ExpressionKind::If {
+      condition,
+      consequence,
+      alternative,
+    } => {
+      bind_expression(condition, bindings);
+      bind_statement(consequence, bindings);
+      if let Some(alternative) = alternative.as_mut() {
+        bind_statement(alternative, bindings);
+      }
+    }
+    ExpressionKind::For { iterable, body, .. } => {
+      bind_expression(iterable, bindings);
+      bind_statement(body, bindings);
+    }
+    ExpressionKind::While { condition, body } => {
+      bind_expression(condition, bindings);
+      bind_statement(body, bindings);
+    }
+    ExpressionKind::Binary { left, right, .. } => {
+      bind_expression(left, bindings);
+      bind_expression(right, bindings);
+    }
+    ExpressionKind::Unary { operand, .. } | ExpressionKind::Yield(operand) => {
+      bind_expression(operand, bindings)
+    }
+    _ => {}
+  }
+}
+
+fn collect_names(statements: &[Statement], names: &mut HashSet<String>) {
+  for statement in statements {
+    collect_statement_names(statement, names);
+  }
+}
+
+fn collect_statement_names(statement: &Statement, names: &mut HashSet<String>) {
+  match &statement.kind {
+    StatementKind::Block(statements) => collect_names(statements, names),
+    StatementKind::Let { name, value, .. } => {
+      names.insert(name.0.clone());
+      collect_expression_names(value, names);
+    }
+    StatementKind::Expression(expression)
+    | StatementKind::Assignment {
+      value: expression, ..
+    } => collect_expression_names(expression, names),
+    StatementKind::FunctionDeclaration {
+      name,
+      parameters,
+      body,
+      ..
+    } => {
+      names.insert(name.0.clone());
+      names.extend(parameters.iter().map(|p| p.0.clone()));
+      collect_statement_names(body, names);
+    }
+  }
+}
+
+fn collect_expression_names(expression: &Expression, names: &mut HashSet<String>) {
+  match &expression.kind {
+    ExpressionKind::ArrayLiteral(expressions) => {
+      for expression in expressions {
+        collect_expression_names(expression, names);
+      }
+    }
+    ExpressionKind::Index { iterable, index } => {
+      collect_expression_names(iterable, names);
+      collect_expression_names(index, names);
+    }
+    ExpressionKind::Slice {
+      iterable,
+      start,
+      end,
+    } => {
+      collect_expression_names(iterable, names);
+      for bound in [start.as_ref(), end.as_ref()].into_iter().flatten() {
+        collect_expression_names(bound, names);
+      }
+    }
+    ExpressionKind::FunctionCall { arguments, .. } => {
+      for argument in arguments {
+        collect_expression_names(argument, names);
+      }
+    }
+    ExpressionKind::If {
+      condition,
+      consequence,
+      alternative,
+    } => {
+      collect_expression_names(condition, names);
+      collect_statement_names(consequence, names);
+      if let Some(alternative) = alternative.as_ref() {
+        collect_statement_names(alternative, names);
+      }
+    }
+    ExpressionKind::For {
+      variable,
+      iterable,
+      body,
+    } => {
+      names.insert(variable.0.clone());
+      collect_expression_names(iterable, names);
+      collect_statement_names(body, names);
+    }
+    ExpressionKind::While { condition, body } => {
+      collect_expression_names(condition, names);
+      collect_statement_names(body, names);
+    }
+    ExpressionKind::Binary { left, right, .. } => {
+      collect_expression_names(left, names);
+      collect_expression_names(right, names);
+    }
+    ExpressionKind::Unary { operand, .. } | ExpressionKind::Yield(operand) => {
+      collect_expression_names(operand, names)
+    }
+    _ => {}
+  }
+}
@@ -0,0 +1,162 @@
+use crate::{
+  error::ResolveErrorKind,
+  evaluator::{evaluate, scope, value::Value},
+  parser::ast::Binding,
+  resolver::{bind, resolve, resolve_partial, DeclarationKind, Resolution},
+  test_util::{examples, parse},
+};
+
+fn resolve_source(source: &str) -> Resolution {
+  resolve(&parse(source), &scope::default())
+}
+
+#[test]
+fn test_bindings() {
+  // Tokens: let x = 1 ; (0-4) let fn f x = + x y ; (5-13) let y = x ; (14-18) f ( y ) ; (19-23)
+  let resolution = resolve_source("let x = 1; let fn f x = + x y; let y = x; f(y);");
+
+  assert!(resolution.errors.is_empty());
+
+  let declaration = |token| {
+    let index = resolution.declaration_at(token).unwrap();
+    &resolution.declarations[index]
+  };
+
+  // The parameter shadows the outer x
+  assert_eq!(declaration(11).span, Some(8..9));
+  assert_eq!(
+    declaration(11).kind,
+    DeclarationKind::Parameter {
+      function: String::from("f")
+    }
+  );
+  // y is declared after f, but before f is called
+  assert_eq!(declaration(12).span, Some(15..16));
+  assert_eq!(declaration(19).span, Some(7..8));
+  assert_eq!(declaration(17).span, Some(1..2));
+
+  let f = resolution.declaration_at(7).unwrap();
+  assert_eq!(
+    resolution
+      .references_to(f)
+      .map(|reference| reference.span.clone())
+      .collect::<Vec<_>>(),
+    vec![19..20]
+  );
+
+  let println = resolve_source("println(1)");
+  assert!(matches!(
+    println.declarations[println.declaration_at(0).unwrap()].kind,
+    DeclarationKind::Global {
+      constant: true,
+      parameters: Some(1)
+    }
+  ));
+}
+
+#[test]
+fn test_errors() {
+  let resolution = resolve_source(
+    "
+    let const a = 1;
+    a = 2;
+    println(b);
+    let fn f x x = { x = 1; g() };
+    for i in [1] { i = 2 };
+    let fn h = dynamic;
+    let fn set = { let dynamic = 1; h() };
+    yield 1;
+    ",
+  );
+
+  assert_eq!(
+    resolution
+      .errors
+      .iter()
+      .map(|error| error.kind.clone())
+      .collect::<Vec<_>>(),
+    vec![
+      ResolveErrorKind::ConstantReassignment(String::from("a")),
+      ResolveErrorKind::UndefinedVariable(String::from("b")),
+      ResolveErrorKind::DuplicateParameter(String::from("x")),
+      ResolveErrorKind::ConstantReassignment(String::from("x")),
+      ResolveErrorKind::UndefinedVariable(String::from("g")),
+      ResolveErrorKind::ConstantReassignment(String::from("i")),
+      ResolveErrorKind::YieldOutsideGenerator,
+    ]
+  );
+}
+
+#[test]
+fn test_slots() {
+  // Tokens: let x = 1 ; (0-4) { let y = x ; let x = 2 ; y } ; (5-18)
+  // let fn f a = + a x ; (19-27) if true let z = 1 ; (28-34) let w = 2 ; w (35-40)
+  let resolution = resolve_source(
+    "let x = 1; { let y = x; let x = 2; y }; let fn f a = + a x; if true let z = 1; let w = 2; w",
+  );
+  assert!(resolution.errors.is_empty());
+
+  let binding = |token: usize| {
+    resolution
+      .references
+      .iter()
+      .find(|reference| reference.span.start == token)
+      .unwrap()
+      .binding
+  };
+  let builtins = scope::builtins().len();
+
+  assert_eq!(
+    binding(9),
+    Binding::Static {
+      depth: 1,
+      slot: builtins
+    }
+  );
+  assert_eq!(binding(16), Binding::Static { depth: 0, slot: 0 });
+  assert_eq!(binding(25), Binding::Static { depth: 0, slot: 0 });
+  // Functions see their caller's variables
+  assert_eq!(binding(26), Binding::Dynamic);
+  // Whether `z` gets a slot depends on the condition
+  assert_eq!(binding(40), Binding::Dynamic);
+
+  let println = resolve_source("println(1)");
+  assert_eq!(
+    println.references[0].binding,
+    Binding::Static { depth: 0, slot: 0 }
+  );
+}
+
+#[test]
+fn test_bind() {
+  let source = "
+    let x = 1;
+    let fn f a b = - a b;
+    let fn g = { let x = 2; let z = [x, x]; for i in z { let j = + i x; j } };
+    let y = g();
+    let a = 5;
+    f([0]y, a)
+  ";
+  let mut ast = parse(source);
+  let resolution = resolve(&ast, &scope::default());
+  bind(&mut ast, &resolution);
+
+  let bound = evaluate(ast, scope::default()).unwrap().0;
+  let unbound = evaluate(parse(source), scope::default()).unwrap().0;
+
+  // Arguments are evaluated before `f`'s own `a` exists
+  assert_eq!(bound, Value::Number(-1.0));
+  assert_eq!(bound, unbound);
+
+  // A later REPL line could still declare `later`
+  let ast = parse("let fn f = later()");
+  assert!(!resolve(&ast, &scope::default()).errors.is_empty());
+  assert!(resolve_partial(&ast, &scope::default()).errors.is_empty());
+}
+
+#[test]
+fn test_examples() {
+  examples(|path, source| {
+    assert_eq!(resolve_source(source).errors, vec![], "{}", path.display());
+  });
+}
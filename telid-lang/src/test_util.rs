@@ -0,0 +1,29 @@
+use crate::{
+  lexer::Lexer,
+  parser::{ast::Statement, parser},
+};
+use chumsky::Parser;
+use std::path::Path;
+
+// Lexes and parses `source`, which the test expects to be free of errors
+pub fn parse(source: &str) -> Vec<Statement> {
+  let tokens = Lexer::new(source)
+    .lex(false)
+    .into_iter()
+    .map(|t| t.0)
+    .collect::<Vec<_>>();
+
+  parser().parse(tokens).unwrap()
+}
+
+// Calls `test` with the path and source of every file in `examples/`
+pub fn examples(mut test: impl FnMut(&Path, &str)) {
+  let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/../examples");
+
+  for entry in std::fs::read_dir(examples).unwrap() {
+    let path = entry.unwrap().path();
+    let source = std::fs::read_to_string(&path).unwrap();
+
+    test(&path, &source);
+  }
+}
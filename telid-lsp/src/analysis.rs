@@ -0,0 +1,364 @@
+use crate::position::{offset, range};
+use lsp_types::{
+  CompletionItem, CompletionItemKind, Diagnostic, DiagnosticSeverity, DocumentSymbol, Hover,
+  HoverContents, MarkupContent, MarkupKind, Position, Range as LspRange, SymbolKind,
+};
+use std::{collections::HashSet, ops::Range};
+use telid_lang::{
+  error::source_span,
+  evaluator::{scope, value::Value},
+  lexer::tokens::TokenKind,
+  linter::lint,
+  parser::ast::{Expression, ExpressionKind, Statement, StatementKind},
+  position::LineIndex,
+  program::compile,
+  resolver::{resolve, Declaration, DeclarationKind, Resolution},
+};
+
+// Everything we know about one version of a document
+pub struct Analysis {
+  // Without error tokens, so indices match the AST's spans
+  tokens: Vec<(TokenKind, Range<usize>)>,
+  ast: Vec<Statement>,
+  resolution: Resolution,
+  lines: LineIndex,
+  pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+  pub fn new(source: &str) -> Self {
+    let lines = LineIndex::new(source);
+    let scope = scope::default();
+    let program = compile(source, &scope, resolve);
+
+    let mut diagnostics = Vec::new();
+    for error in &program.errors {
+      diagnostics.push(diagnostic(
+        range(&lines, error.span(&program.tokens)),
+        error.message(),
+      ));
+    }
+    for warning in &program.warnings {
+      diagnostics.push(Diagnostic {
+        severity: Some(DiagnosticSeverity::WARNING),
+        ..diagnostic(
+          range(&lines, warning.span(&program.tokens)),
+          warning.message(),
+        )
+      });
+    }
+
+    // The linter needs a complete program, and would repeat the resolver's
+    // errors as warnings
+    if program.is_ok() {
+      for warning in lint(source, &program.ast, &scope) {
+        diagnostics.push(Diagnostic {
+          severity: Some(DiagnosticSeverity::WARNING),
+          ..diagnostic(
+            range(&lines, source_span(&program.tokens, warning.span)),
+            warning.kind.to_string(),
+          )
+        });
+      }
+    }
+
+    Analysis {
+      resolution: program.resolution.unwrap_or_default(),
+      tokens: program.tokens,
+      ast: program.ast,
+      lines,
+      diagnostics,
+    }
+  }
+
+  fn range(&self, span: Range<usize>) -> LspRange {
+    range(&self.lines, source_span(&self.tokens, span))
+  }
+
+  // The token under the cursor. A cursor just after an identifier still
+  // counts as being on it
+  fn token_at(&self, position: Position) -> Option<usize> {
+    let offset = offset(&self.lines, position);
+    self
+      .tokens
+      .iter()
+      .position(|(_, span)| span.contains(&offset))
+      .or_else(|| {
+        self
+          .tokens
+          .iter()
+          .position(|(token, span)| span.end == offset && matches!(token, TokenKind::Identifier(_)))
+      })
+  }
+
+  fn declaration_at(&self, position: Position) -> Option<&Declaration> {
+    let declaration = self.resolution.declaration_at(self.token_at(position)?)?;
+    Some(&self.resolution.declarations[declaration])
+  }
+
+  pub fn definition(&self, position: Position) -> Option<LspRange> {
+    let span = self.declaration_at(position)?.span.clone()?;
+    Some(self.range(span))
+  }
+
+  pub fn references(&self, position: Position, include_declaration: bool) -> Vec<LspRange> {
+    let declaration = match self
+      .token_at(position)
+      .and_then(|token| self.resolution.declaration_at(token))
+    {
+      Some(declaration) => declaration,
+      None => return Vec::new(),
+    };
+
+    let mut ranges = Vec::new();
+    if include_declaration {
+      if let Some(span) = &self.resolution.declarations[declaration].span {
+        ranges.push(self.range(span.clone()));
+      }
+    }
+    for reference in self.resolution.references_to(declaration) {
+      ranges.push(self.range(reference.span.clone()));
+    }
+
+    ranges
+  }
+
+  pub fn hover(&self, position: Position) -> Option<Hover> {
+    let declaration = self.declaration_at(position)?;
+    let signature = match &declaration.kind {
+      DeclarationKind::Variable { constant: true } => format!("let const {}", declaration.name),
+      DeclarationKind::Variable { constant: false } => format!("let {}", declaration.name),
+      DeclarationKind::Function { parameters } => {
+        format!("let fn {} {}", declaration.name, parameters.join(" "))
+      }
+      DeclarationKind::Parameter { function } => {
+        format!("{} // parameter of {}", declaration.name, function)
+      }
+      DeclarationKind::LoopVariable => format!("for {} in", declaration.name),
+      DeclarationKind::Global {
+        parameters: Some(count),
+        ..
+      } => format!(
+        "{} // builtin, {}",
+        declaration.name,
+        parameter_count(*count)
+      ),
+      DeclarationKind::Global { .. } => format!("{} // global", declaration.name),
+    };
+
+    Some(Hover {
+      contents: HoverContents::Markup(MarkupContent {
+        kind: MarkupKind::Markdown,
+        value: format!("```telid\n{}\n```", signature.trim_end()),
+      }),
+      range: None,
+    })
+  }
+
+  pub fn completions(&self, position: Position) -> Vec<CompletionItem> {
+    let offset = offset(&self.lines, position);
+    let cursor = self
+      .tokens
+      .iter()
+      .position(|(_, span)| span.end >= offset)
+      .unwrap_or(self.tokens.len());
+
+    let mut seen = HashSet::new();
+    let mut items = Vec::new();
+
+    // Innermost declarations first, so shadowed names are skipped
+    let mut declarations = self
+      .resolution
+      .declarations
+      .iter()
+      .filter(|declaration| match &declaration.span {
+        Some(span) => {
+          declaration.visible.contains(&cursor)
+            && (span.end <= cursor || matches!(declaration.kind, DeclarationKind::Function { .. }))
+        }
+        None => false,
+      })
+      .collect::<Vec<_>>();
+    declarations.sort_by_key(|declaration| declaration.visible.len());
+
+    for declaration in declarations {
+      if !seen.insert(declaration.name.clone()) {
+        continue;
+      }
+
+      let (kind, detail) = match &declaration.kind {
+        DeclarationKind::Function { parameters } => (
+          CompletionItemKind::FUNCTION,
+          format!("fn {}", parameters.join(" ")),
+        ),
+        DeclarationKind::Variable { constant: true } => {
+          (CompletionItemKind::CONSTANT, String::from("const"))
+        }
+        DeclarationKind::Parameter { function } => (
+          CompletionItemKind::VARIABLE,
+          format!("parameter of {}", function),
+        ),
+        _ => (CompletionItemKind::VARIABLE, String::from("let")),
+      };
+
+      items.push(CompletionItem {
+        label: declaration.name.clone(),
+        kind: Some(kind),
+        detail: Some(detail),
+        ..Default::default()
+      });
+    }
+
+    for (name, variable) in scope::builtins() {
+      if !seen.insert(name.clone()) {
+        continue;
+      }
+
+      let detail = match variable.value {
+        Value::RustFunction {
+          parameter_count, ..
+        } => format!("builtin, {}", self::parameter_count(parameter_count)),
+        _ => String::from("builtin"),
+      };
+
+      items.push(CompletionItem {
+        label: name,
+        kind: Some(CompletionItemKind::FUNCTION),
+        detail: Some(detail),
+        ..Default::default()
+      });
+    }
+
+    items
+  }
+
+  pub fn symbols(&self) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+    for statement in &self.ast {
+      self.statement_symbols(statement, &mut symbols);
+    }
+    symbols
+  }
+
+  fn statement_symbols(&self, statement: &Statement, symbols: &mut Vec<DocumentSymbol>) {
+    match &statement.kind {
+      StatementKind::Block(statements) => {
+        for statement in statements {
+          self.statement_symbols(statement, symbols);
+        }
+      }
+      StatementKind::Let {
+        name,
+        value,
+        constant,
+        ..
+      } => {
+        self.expression_symbols(value, symbols);
+        symbols.push(self.symbol(
+          &name.0,
+          if *constant {
+            SymbolKind::CONSTANT
+          } else {
+            SymbolKind::VARIABLE
+          },
+          statement.span.clone(),
+          name.1.clone(),
+          None,
+          Vec::new(),
+        ));
+      }
+      StatementKind::Expression(expression)
+      | StatementKind::Assignment {
+        value: expression, ..
+      } => self.expression_symbols(expression, symbols),
+      StatementKind::FunctionDeclaration {
+        name,
+        parameters,
+        body,
+        ..
+      } => {
+        let mut children = Vec::new();
+        self.statement_symbols(body, &mut children);
+        symbols.push(
+          self.symbol(
+            &name.0,
+            SymbolKind::FUNCTION,
+            statement.span.clone(),
+            name.1.clone(),
+            Some(
+              parameters
+                .iter()
+                .map(|p| p.0.clone())
+                .collect::<Vec<_>>()
+                .join(" "),
+            ),
+            children,
+          ),
+        );
+      }
+    }
+  }
+
+  // Declarations can hide in the branches of an `if` or the body of a loop
+  fn expression_symbols(&self, expression: &Expression, symbols: &mut Vec<DocumentSymbol>) {
+    match &expression.kind {
+      ExpressionKind::If {
+        consequence,
+        alternative,
+        ..
+      } => {
+        self.statement_symbols(consequence, symbols);
+        if let Some(alternative) = alternative.as_ref() {
+          self.statement_symbols(alternative, symbols);
+        }
+      }
+      ExpressionKind::For { body, .. } | ExpressionKind::While { body, .. } => {
+        self.statement_symbols(body, symbols)
+      }
+      _ => {}
+    }
+  }
+
+  fn symbol(
+    &self,
+    name: &str,
+    kind: SymbolKind,
+    span: Range<usize>,
+    selection: Range<usize>,
+    detail: Option<String>,
+    children: Vec<DocumentSymbol>,
+  ) -> DocumentSymbol {
+    #[allow(deprecated)]
+    DocumentSymbol {
+      name: name.to_string(),
+      detail,
+      kind,
+      tags: None,
+      deprecated: None,
+      range: self.range(span),
+      selection_range: self.range(selection),
+      children: if children.is_empty() {
+        None
+      } else {
+        Some(children)
+      },
+    }
+  }
+}
+
+fn diagnostic(range: LspRange, message: String) -> Diagnostic {
+  Diagnostic {
+    range,
+    severity: Some(DiagnosticSeverity::ERROR),
+    source: Some(String::from("telid")),
+    message,
+    ..Default::default()
+  }
+}
+
+fn parameter_count(count: usize) -> String {
+  match count {
+    1 => String::from("1 parameter"),
+    count => format!("{} parameters", count),
+  }
+}
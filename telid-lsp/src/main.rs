@@ -0,0 +1,200 @@
+use analysis::Analysis;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+  notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Notification as _,
+    PublishDiagnostics,
+  },
+  request::{
+    Completion, DocumentSymbolRequest, GotoDefinition, HoverRequest, References, Request as _,
+  },
+  CompletionOptions, CompletionResponse, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+  DidOpenTextDocumentParams, DocumentSymbolResponse, GotoDefinitionResponse, Location, OneOf,
+  PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+  Url,
+};
+use std::{collections::HashMap, error::Error};
+
+mod analysis;
+mod position;
+
+fn main() -> Result<(), Box<dyn Error + Sync + Send>> {
+  let (connection, io_threads) = Connection::stdio();
+
+  let capabilities = serde_json::to_value(ServerCapabilities {
+    text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+    definition_provider: Some(OneOf::Left(true)),
+    references_provider: Some(OneOf::Left(true)),
+    hover_provider: Some(true.into()),
+    completion_provider: Some(CompletionOptions::default()),
+    document_symbol_provider: Some(OneOf::Left(true)),
+    ..Default::default()
+  })?;
+  connection.initialize(capabilities)?;
+
+  Server::default().run(connection)?;
+  io_threads.join()?;
+
+  Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+  documents: HashMap<Url, Analysis>,
+}
+
+impl Server {
+  // Takes the connection so it is dropped on return, which lets the IO threads
+  // finish
+  fn run(&mut self, connection: Connection) -> Result<(), Box<dyn Error + Sync + Send>> {
+    for message in &connection.receiver {
+      match message {
+        Message::Request(request) => {
+          if connection.handle_shutdown(&request)? {
+            return Ok(());
+          }
+
+          let response = self.request(request);
+          connection.sender.send(Message::Response(response))?;
+        }
+        Message::Notification(notification) => {
+          if let Some(diagnostics) = self.notification(notification) {
+            connection
+              .sender
+              .send(Message::Notification(Notification::new(
+                PublishDiagnostics::METHOD.to_string(),
+                diagnostics,
+              )))?;
+          }
+        }
+        Message::Response(_) => {}
+      }
+    }
+
+    Ok(())
+  }
+
+  // Returns the diagnostics to publish, if the notification changed a document
+  fn notification(&mut self, notification: Notification) -> Option<PublishDiagnosticsParams> {
+    let (uri, version, text) = match notification.method.as_str() {
+      DidOpenTextDocument::METHOD => {
+        let params: DidOpenTextDocumentParams = parse(notification.params)?;
+        (
+          params.text_document.uri,
+          Some(params.text_document.version),
+          Some(params.text_document.text),
+        )
+      }
+      DidChangeTextDocument::METHOD => {
+        // We only ask for full syncs, so the last change is the whole document
+        let params: DidChangeTextDocumentParams = parse(notification.params)?;
+        (
+          params.text_document.uri,
+          Some(params.text_document.version),
+          Some(params.content_changes.into_iter().last()?.text),
+        )
+      }
+      DidCloseTextDocument::METHOD => {
+        let params: DidCloseTextDocumentParams = parse(notification.params)?;
+        (params.text_document.uri, None, None)
+      }
+      _ => return None,
+    };
+
+    let diagnostics = match text {
+      Some(text) => {
+        let analysis = Analysis::new(&text);
+        let diagnostics = analysis.diagnostics.clone();
+        self.documents.insert(uri.clone(), analysis);
+        diagnostics
+      }
+      None => {
+        self.documents.remove(&uri);
+        Vec::new()
+      }
+    };
+
+    Some(PublishDiagnosticsParams::new(uri, diagnostics, version))
+  }
+
+  fn request(&self, request: Request) -> Response {
+    let id = request.id.clone();
+    let result = match request.method.as_str() {
+      GotoDefinition::METHOD => self.handle::<GotoDefinition>(
+        request,
+        |params| &params.text_document_position_params.text_document.uri,
+        |analysis, params| {
+          let position = params.text_document_position_params;
+          analysis.definition(position.position).map(|range| {
+            GotoDefinitionResponse::Scalar(Location::new(position.text_document.uri, range))
+          })
+        },
+      ),
+      References::METHOD => self.handle::<References>(
+        request,
+        |params| &params.text_document_position.text_document.uri,
+        |analysis, params| {
+          let position = params.text_document_position;
+          Some(
+            analysis
+              .references(position.position, params.context.include_declaration)
+              .into_iter()
+              .map(|range| Location::new(position.text_document.uri.clone(), range))
+              .collect(),
+          )
+        },
+      ),
+      HoverRequest::METHOD => self.handle::<HoverRequest>(
+        request,
+        |params| &params.text_document_position_params.text_document.uri,
+        |analysis, params| analysis.hover(params.text_document_position_params.position),
+      ),
+      Completion::METHOD => self.handle::<Completion>(
+        request,
+        |params| &params.text_document_position.text_document.uri,
+        |analysis, params| {
+          Some(CompletionResponse::Array(
+            analysis.completions(params.text_document_position.position),
+          ))
+        },
+      ),
+      DocumentSymbolRequest::METHOD => self.handle::<DocumentSymbolRequest>(
+        request,
+        |params| &params.text_document.uri,
+        |analysis, _| Some(DocumentSymbolResponse::Nested(analysis.symbols())),
+      ),
+      method => Err((
+        ErrorCode::MethodNotFound,
+        format!("Unsupported request: {}", method),
+      )),
+    };
+
+    match result {
+      Ok(result) => Response::new_ok(id, result),
+      Err((code, message)) => Response::new_err(id, code as i32, message),
+    }
+  }
+
+  fn handle<R: lsp_types::request::Request>(
+    &self,
+    request: Request,
+    uri: impl Fn(&R::Params) -> &Url,
+    handler: impl Fn(&Analysis, R::Params) -> R::Result,
+  ) -> Result<serde_json::Value, (ErrorCode, String)> {
+    let params: R::Params = parse(request.params)
+      .ok_or_else(|| (ErrorCode::InvalidParams, String::from("Invalid parameters")))?;
+    let analysis = self.documents.get(uri(&params)).ok_or_else(|| {
+      (
+        ErrorCode::InvalidParams,
+        String::from("Document is not open"),
+      )
+    })?;
+
+    serde_json::to_value(handler(analysis, params))
+      .map_err(|error| (ErrorCode::InternalError, error.to_string()))
+  }
+}
+
+fn parse<T: serde::de::DeserializeOwned>(params: serde_json::Value) -> Option<T> {
+  serde_json::from_value(params).ok()
+}
@@ -0,0 +1,17 @@
+use lsp_types::{Position, Range as LspRange};
+use std::ops::Range;
+use telid_lang::position::LineIndex;
+
+// The lexer counts characters, LSP counts lines and UTF-16 code units within
+// a line. These convert between the two
+pub fn position(lines: &LineIndex, offset: usize) -> Position {
+  Position::new(lines.line(offset) as u32, lines.utf16_column(offset) as u32)
+}
+
+pub fn offset(lines: &LineIndex, position: Position) -> usize {
+  lines.utf16_offset(position.line as usize, position.character as usize)
+}
+
+pub fn range(lines: &LineIndex, span: Range<usize>) -> LspRange {
+  LspRange::new(position(lines, span.start), position(lines, span.end))
+}
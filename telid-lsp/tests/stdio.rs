@@ -0,0 +1,201 @@
+use serde_json::{json, Value};
+use std::{
+  io::{BufRead, BufReader, Read, Write},
+  process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+const SOURCE: &str = "let x = 1;
+let fn add a b = + a b;
+println(add(x, 2));
+let y = ;
+";
+
+const URI: &str = "file:///test.tl";
+
+// A minimal client speaking Content-Length framed JSON-RPC to the server
+struct Client {
+  child: Child,
+  stdin: ChildStdin,
+  stdout: BufReader<ChildStdout>,
+  next_id: i64,
+}
+
+impl Client {
+  fn spawn() -> Self {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_telid-lsp"))
+      .stdin(Stdio::piped())
+      .stdout(Stdio::piped())
+      .spawn()
+      .unwrap();
+
+    Client {
+      stdin: child.stdin.take().unwrap(),
+      stdout: BufReader::new(child.stdout.take().unwrap()),
+      child,
+      next_id: 0,
+    }
+  }
+
+  fn send(&mut self, message: Value) {
+    let body = message.to_string();
+    write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    self.stdin.flush().unwrap();
+  }
+
+  fn receive(&mut self) -> Value {
+    let mut length = None;
+    loop {
+      let mut line = String::new();
+      self.stdout.read_line(&mut line).unwrap();
+      let line = line.trim_end();
+      if line.is_empty() {
+        break;
+      }
+      if let Some(value) = line.strip_prefix("Content-Length: ") {
+        length = Some(value.parse::<usize>().unwrap());
+      }
+    }
+
+    let mut body = vec![0; length.unwrap()];
+    self.stdout.read_exact(&mut body).unwrap();
+    serde_json::from_slice(&body).unwrap()
+  }
+
+  fn notify(&mut self, method: &str, params: Value) {
+    self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+  }
+
+  fn request(&mut self, method: &str, params: Value) -> Value {
+    self.next_id += 1;
+    let id = self.next_id;
+    self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+
+    loop {
+      let message = self.receive();
+      if message["id"] == json!(id) {
+        assert!(message.get("error").is_none(), "{}", message);
+        return message["result"].clone();
+      }
+    }
+  }
+
+  fn wait_for(&mut self, method: &str) -> Value {
+    loop {
+      let message = self.receive();
+      if message["method"] == json!(method) {
+        return message["params"].clone();
+      }
+    }
+  }
+}
+
+fn position(line: u32, character: u32) -> Value {
+  json!({
+    "textDocument": { "uri": URI },
+    "position": { "line": line, "character": character },
+  })
+}
+
+fn range(start: (u32, u32), end: (u32, u32)) -> Value {
+  json!({
+    "start": { "line": start.0, "character": start.1 },
+    "end": { "line": end.0, "character": end.1 },
+  })
+}
+
+#[test]
+fn test_session() {
+  let mut client = Client::spawn();
+
+  let initialize = client.request(
+    "initialize",
+    json!({ "processId": null, "rootUri": null, "capabilities": {} }),
+  );
+  assert_eq!(
+    initialize["capabilities"]["definitionProvider"],
+    json!(true)
+  );
+  client.notify("initialized", json!({}));
+
+  client.notify(
+    "textDocument/didOpen",
+    json!({
+      "textDocument": { "uri": URI, "languageId": "telid", "version": 1, "text": SOURCE },
+    }),
+  );
+  let diagnostics = client.wait_for("textDocument/publishDiagnostics");
+  assert_eq!(diagnostics["uri"], json!(URI));
+  let diagnostics = diagnostics["diagnostics"].as_array().unwrap();
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0]["range"]["start"]["line"], json!(3));
+
+  // `add` in the call goes to its declaration
+  let definition = client.request("textDocument/definition", position(2, 9));
+  assert_eq!(definition["range"], range((1, 7), (1, 10)));
+
+  let mut references = client.request(
+    "textDocument/references",
+    json!({
+      "textDocument": { "uri": URI },
+      "position": { "line": 0, "character": 4 },
+      "context": { "includeDeclaration": true },
+    }),
+  );
+  let references = references
+    .as_array_mut()
+    .unwrap()
+    .iter()
+    .map(|location| location["range"].clone())
+    .collect::<Vec<_>>();
+  assert_eq!(
+    references,
+    vec![range((0, 4), (0, 5)), range((2, 12), (2, 13))]
+  );
+
+  let hover = client.request("textDocument/hover", position(2, 9));
+  assert!(hover["contents"]["value"]
+    .as_str()
+    .unwrap()
+    .contains("let fn add a b"));
+
+  let completion = client.request("textDocument/completion", position(3, 8));
+  let labels = completion
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|item| item["label"].as_str().unwrap().to_string())
+    .collect::<Vec<_>>();
+  for label in ["x", "add", "println"] {
+    assert!(labels.iter().any(|l| l == label), "missing {}", label);
+  }
+
+  let symbols = client.request(
+    "textDocument/documentSymbol",
+    json!({ "textDocument": { "uri": URI } }),
+  );
+  let names = symbols
+    .as_array()
+    .unwrap()
+    .iter()
+    .map(|symbol| symbol["name"].as_str().unwrap())
+    .collect::<Vec<_>>();
+  assert_eq!(&names[..2], ["x", "add"]);
+
+  // Lint warnings show up once the document parses
+  client.notify(
+    "textDocument/didChange",
+    json!({
+      "textDocument": { "uri": URI, "version": 2 },
+      "contentChanges": [{ "text": "let unused = 1;" }],
+    }),
+  );
+  let diagnostics = client.wait_for("textDocument/publishDiagnostics");
+  let diagnostics = diagnostics["diagnostics"].as_array().unwrap();
+  assert_eq!(diagnostics.len(), 1);
+  assert_eq!(diagnostics[0]["severity"], json!(2));
+  assert_eq!(diagnostics[0]["message"], json!("UnusedVariable: unused"));
+
+  client.request("shutdown", Value::Null);
+  client.notify("exit", Value::Null);
+  assert!(client.child.wait().unwrap().success());
+}
@@ -0,0 +1,147 @@
+use std::io::{stdin, stdout, Write};
+use telid_lang::{
+  debugger::{Frontend, Paused, Reason, Step},
+  evaluator::value::Value,
+};
+
+const HELP: &str = "Commands:
+  break <line>, b      Pause before <line> runs
+  delete <line>, d     Remove the breakpoint on <line>
+  breakpoints          List breakpoints
+  continue, c          Run until the next breakpoint
+  step, s              Run the next statement, stepping into calls
+  next, n              Run the next statement, stepping over calls
+  out, o               Run until the current function returns
+  vars, v              Show the variables in every frame
+  stack, bt            Show the call stack
+  print <expr>, p      Evaluate <expr> where the program is paused
+  list, l              Show the code around the current line
+  quit, q              Stop the program
+  help, h              Show this message";
+
+// Takes commands from stdin while the program is paused. Lines are shown
+// counting from 1
+#[derive(Debug)]
+pub struct Console {
+  path: String,
+  lines: Vec<String>,
+}
+
+impl Console {
+  pub fn new(path: &str, source: &str) -> Self {
+    Console {
+      path: path.to_string(),
+      lines: source.lines().map(String::from).collect(),
+    }
+  }
+
+  fn show_line(&self, line: usize) {
+    println!(
+      "{}:{}: {}",
+      self.path,
+      line + 1,
+      self
+        .lines
+        .get(line)
+        .map(String::as_str)
+        .unwrap_or("")
+        .trim()
+    );
+  }
+
+  fn list(&self, current: usize) {
+    let start = current.saturating_sub(3);
+    let end = (current + 4).min(self.lines.len());
+    for line in start..end {
+      let marker = if line == current { ">" } else { " " };
+      println!("{} {:>4} {}", marker, line + 1, self.lines[line]);
+    }
+  }
+
+  fn variables(&self, paused: &Paused) {
+    for (depth, frame) in paused.frames().enumerate() {
+      let variables = frame
+        .iter()
+        .filter(|(_, variable)| !matches!(variable.value, Value::RustFunction { .. }))
+        .collect::<Vec<_>>();
+      if variables.is_empty() {
+        continue;
+      }
+
+      println!("frame {}:", depth);
+      for (name, variable) in variables {
+        println!("  {} = {}", name, describe(&variable.value));
+      }
+    }
+  }
+}
+
+impl Frontend for Console {
+  fn paused(&mut self, paused: &mut Paused) -> Step {
+    if paused.reason == Reason::Breakpoint {
+      print!("Breakpoint at ");
+    }
+    self.show_line(paused.position.line);
+
+    loop {
+      print!("(debug) ");
+      stdout().flush().unwrap();
+
+      let mut input = String::new();
+      if stdin().read_line(&mut input).unwrap() == 0 {
+        // Nobody left to ask, so let the program finish
+        println!();
+        return Step::Continue;
+      }
+
+      let input = input.trim();
+      let (command, argument) = input.split_once(' ').unwrap_or((input, ""));
+      let argument = argument.trim();
+
+      match command {
+        "" => {}
+        "break" | "b" | "delete" | "d" => match argument.parse::<usize>() {
+          Ok(line) if line > 0 => {
+            if matches!(command, "break" | "b") {
+              paused.breakpoints.insert(line - 1);
+            } else if !paused.breakpoints.remove(&(line - 1)) {
+              println!("No breakpoint on line {}", line);
+            }
+          }
+          _ => println!("Expected a line number"),
+        },
+        "breakpoints" => {
+          for line in paused.breakpoints.iter() {
+            self.show_line(*line);
+          }
+        }
+        "continue" | "c" => return Step::Continue,
+        "step" | "s" => return Step::Into,
+        "next" | "n" => return Step::Over,
+        "out" | "o" => return Step::Out,
+        "vars" | "v" => self.variables(paused),
+        "stack" | "bt" => {
+          for (name, position) in paused.call_stack() {
+            println!("{} called from line {}", name, position.line + 1);
+          }
+        }
+        "print" | "p" => match paused.evaluate(argument) {
+          Ok(value) => println!("{}", describe(&value)),
+          Err(error) => println!("{}", error),
+        },
+        "list" | "l" => self.list(paused.position.line),
+        "quit" | "q" => std::process::exit(0),
+        "help" | "h" => println!("{}", HELP),
+        _ => println!("Unknown command {:?}, type help for a list", command),
+      }
+    }
+  }
+}
+
+// Strings are quoted so they can be told apart from other values
+fn describe(value: &Value) -> String {
+  match value {
+    Value::String(string) => format!("{:?}", string),
+    value => value.to_string(),
+  }
+}
@@ -0,0 +1,147 @@
+use ariadne::Source;
+use serde_json::json;
+use std::{ops::Range, sync::Mutex};
+use telid_lang::{
+  error::{source_span, EvaluationError, Warning},
+  position::LineIndex,
+  program::{Diagnostic, Program, Tokens},
+};
+
+// How errors and warnings are written to stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorFormat {
+  // Ariadne's reports
+  #[default]
+  Human,
+  // One JSON object per line
+  Json,
+}
+
+// The last one given wins
+static ERROR_FORMAT: Mutex<ErrorFormat> = Mutex::new(ErrorFormat::Human);
+
+pub fn set_error_format(format: ErrorFormat) {
+  *ERROR_FORMAT.lock().unwrap() = format;
+}
+
+fn error_format() -> ErrorFormat {
+  *ERROR_FORMAT.lock().unwrap()
+}
+
+// Everything a JSON diagnostic says. Spans are in characters, like the
+// lexer's
+struct Entry {
+  severity: &'static str,
+  code: String,
+  message: String,
+  span: Range<usize>,
+  // Name of each function being called, and the span of the call
+  stack: Vec<(String, Range<usize>)>,
+}
+
+impl Entry {
+  fn print(&self, source: &str, id: &str) {
+    let lines = LineIndex::new(source);
+    let stack = self
+      .stack
+      .iter()
+      .map(|(name, span)| json!({ "name": name, "range": range(&lines, span) }))
+      .collect::<Vec<_>>();
+
+    eprintln!(
+      "{}",
+      json!({
+        "severity": self.severity,
+        "code": self.code,
+        "message": self.message,
+        "file": id,
+        "range": range(&lines, &self.span),
+        "stack": stack,
+      })
+    );
+  }
+}
+
+// The byte offset and 1-based line and column of each end of `span`, which
+// is in characters
+fn range(lines: &LineIndex, span: &Range<usize>) -> serde_json::Value {
+  json!({ "start": position(lines, span.start), "end": position(lines, span.end) })
+}
+
+fn position(lines: &LineIndex, offset: usize) -> serde_json::Value {
+  let position = lines.position(offset);
+  json!({ "byte": lines.byte(offset), "line": position.line + 1, "column": position.column + 1 })
+}
+
+// Reports the errors that kept `program` from running
+pub fn report_program(source: &str, id: &str, program: &Program) {
+  report_diagnostics(source, id, &program.errors, "error", &program.tokens);
+}
+
+// Reports the type errors that didn't keep `program` from running
+pub fn report_program_warnings(source: &str, id: &str, program: &Program) {
+  report_diagnostics(source, id, &program.warnings, "warning", &program.tokens);
+}
+
+fn report_diagnostics(
+  source: &str,
+  id: &str,
+  diagnostics: &[Diagnostic],
+  severity: &'static str,
+  tokens: &Tokens,
+) {
+  for diagnostic in diagnostics {
+    match error_format() {
+      ErrorFormat::Human => diagnostic
+        .report(id, tokens)
+        .eprint((id, Source::from(source)))
+        .unwrap(),
+      ErrorFormat::Json => Entry {
+        severity,
+        code: diagnostic.code().to_string(),
+        message: diagnostic.message(),
+        span: diagnostic.span(tokens),
+        stack: Vec::new(),
+      }
+      .print(source, id),
+    }
+  }
+}
+
+pub fn report_evaluation_error(source: &str, id: &str, error: &EvaluationError, tokens: &Tokens) {
+  match error_format() {
+    ErrorFormat::Human => error
+      .report(id, error.span.clone(), tokens.clone())
+      .eprint((id, Source::from(source)))
+      .unwrap(),
+    ErrorFormat::Json => Entry {
+      severity: "error",
+      code: error.kind.code().to_string(),
+      message: error.kind.to_string(),
+      span: source_span(tokens, error.span.clone()),
+      stack: error
+        .stack
+        .iter()
+        .map(|frame| (frame.name.clone(), source_span(tokens, frame.span.clone())))
+        .collect(),
+    }
+    .print(source, id),
+  }
+}
+
+pub fn report_warning(source: &str, id: &str, warning: &Warning, tokens: &Tokens) {
+  match error_format() {
+    ErrorFormat::Human => warning
+      .report(id, tokens)
+      .eprint((id, Source::from(source)))
+      .unwrap(),
+    ErrorFormat::Json => Entry {
+      severity: "warning",
+      code: warning.kind.as_ref().to_string(),
+      message: warning.kind.to_string(),
+      span: source_span(tokens, warning.span.clone()),
+      stack: Vec::new(),
+    }
+    .print(source, id),
+  }
+}
@@ -0,0 +1,266 @@
+use crate::{parse, read_source, reject_options};
+use serde_json::{json, Value as Json};
+use std::ops::Range;
+use telid_lang::{
+  error::source_span,
+  lexer::{tokens::TokenKind, Lexer},
+  parser::ast::{Expression, ExpressionKind, Identifier, Statement, StatementKind, Type},
+  program::Tokens,
+};
+
+// Shows what a file lexes to. Error tokens are included
+pub fn run_tokens(args: Vec<String>) -> Result<(), ()> {
+  let (path, json) = arguments(args, "tokens")?;
+  let source = read_source(&path)?;
+  let tokens = Lexer::new(&source).lex(false);
+
+  if json {
+    let tokens = tokens
+      .iter()
+      .map(|(token, span)| {
+        json!({
+          "kind": token.as_ref(),
+          "value": token_value(token),
+          "span": [span.start, span.end],
+        })
+      })
+      .collect::<Vec<_>>();
+    println!("{}", Json::Array(tokens));
+  } else {
+    print_tokens(&tokens);
+  }
+
+  Ok(())
+}
+
+// Shows what a file parses to. Spans in the JSON are in characters, but
+// those in the plain output are in tokens, like the AST's own
+pub fn run_ast(args: Vec<String>) -> Result<(), ()> {
+  let (path, json) = arguments(args, "ast")?;
+  let source = read_source(&path)?;
+  let (ast, tokens) = parse(&source, &path)?;
+
+  if json {
+    let dump = Dump { tokens: &tokens };
+    let ast = ast.iter().map(|statement| dump.statement(statement));
+    println!("{}", Json::Array(ast.collect()));
+  } else {
+    print_ast(&ast);
+  }
+
+  Ok(())
+}
+
+pub fn print_tokens(tokens: &[(TokenKind, Range<usize>)]) {
+  for (token, span) in tokens {
+    println!("{:>4}..{:<4} {:?}", span.start, span.end, token);
+  }
+}
+
+pub fn print_ast(ast: &[Statement]) {
+  for statement in ast {
+    println!("{:#?}", statement);
+  }
+}
+
+// The file, and whether `--json` was given
+fn arguments(args: Vec<String>, subcommand: &str) -> Result<(String, bool), ()> {
+  let usage = format!("Usage: telid {} [--json] <file>", subcommand);
+  let json = args.iter().any(|arg| arg == "--json");
+  let paths = args
+    .into_iter()
+    .filter(|arg| arg != "--json")
+    .collect::<Vec<_>>();
+  reject_options(&paths, &usage)?;
+
+  match <[String; 1]>::try_from(paths) {
+    Ok([path]) => Ok((path, json)),
+    Err(_) => {
+      eprintln!("{}", usage);
+      Err(())
+    }
+  }
+}
+
+fn token_value(token: &TokenKind) -> Json {
+  match token {
+    TokenKind::NumberLiteral(number) => json!(number.0),
+    TokenKind::BooleanLiteral(boolean) => json!(boolean),
+    TokenKind::StringLiteral(string) | TokenKind::Identifier(string) => json!(string),
+    TokenKind::Error(error) => json!(error.to_string()),
+    _ => Json::Null,
+  }
+}
+
+// Converts the AST to JSON. Every node has a `kind` and a `span`, and the
+// fields of its variant
+struct Dump<'a> {
+  tokens: &'a Tokens,
+}
+
+impl Dump<'_> {
+  fn span(&self, span: &Range<usize>) -> Json {
+    let span = source_span(self.tokens, span.clone());
+    json!([span.start, span.end])
+  }
+
+  fn identifier(&self, identifier: &Identifier) -> Json {
+    json!({ "name": identifier.0, "span": self.span(&identifier.1) })
+  }
+
+  fn annotation(annotation: &Option<Type>) -> Json {
+    match annotation {
+      Some(annotation) => json!(annotation.to_string()),
+      None => Json::Null,
+    }
+  }
+
+  fn statement(&self, statement: &Statement) -> Json {
+    let mut node = match &statement.kind {
+      StatementKind::Block(statements) => json!({
+        "kind": "Block",
+        "statements": statements.iter().map(|s| self.statement(s)).collect::<Vec<_>>(),
+      }),
+      StatementKind::Let {
+        name,
+        value,
+        constant,
+        annotation,
+      } => json!({
+        "kind": "Let",
+        "name": self.identifier(name),
+        "value": self.expression(value),
+        "constant": constant,
+        "annotation": Self::annotation(annotation),
+      }),
+      StatementKind::Expression(expression) => json!({
+        "kind": "Expression",
+        "expression": self.expression(expression),
+      }),
+      StatementKind::FunctionDeclaration {
+        name,
+        parameters,
+        parameter_types,
+        return_type,
+        body,
+      } => json!({
+        "kind": "FunctionDeclaration",
+        "name": self.identifier(name),
+        "parameters": parameters
+          .iter()
+          .zip(parameter_types)
+          .map(|(parameter, annotation)| json!({
+            "name": parameter.0,
+            "span": self.span(&parameter.1),
+            "annotation": Self::annotation(annotation),
+          }))
+          .collect::<Vec<_>>(),
+        "return_type": Self::annotation(return_type),
+        "body": self.statement(body),
+      }),
+      StatementKind::Assignment { name, value } => json!({
+        "kind": "Assignment",
+        "name": self.identifier(name),
+        "value": self.expression(value),
+      }),
+    };
+
+    node["span"] = self.span(&statement.span);
+    node
+  }
+
+  fn expression(&self, expression: &Expression) -> Json {
+    let optional = |expression: &Option<Expression>| match expression {
+      Some(expression) => self.expression(expression),
+      None => Json::Null,
+    };
+
+    let mut node = match &expression.kind {
+      ExpressionKind::Void => json!({ "kind": "Void" }),
+      ExpressionKind::Error => json!({ "kind": "Error" }),
+      ExpressionKind::Identifier(identifier) => json!({
+        "kind": "Identifier",
+        "name": identifier.0,
+      }),
+      ExpressionKind::NumberLiteral(number) => json!({ "kind": "NumberLiteral", "value": number }),
+      ExpressionKind::StringLiteral(string) => json!({ "kind": "StringLiteral", "value": string }),
+      ExpressionKind::BooleanLiteral(boolean) => {
+        json!({ "kind": "BooleanLiteral", "value": boolean })
+      }
+      ExpressionKind::ArrayLiteral(elements) => json!({
+        "kind": "ArrayLiteral",
+        "elements": elements.iter().map(|e| self.expression(e)).collect::<Vec<_>>(),
+      }),
+      ExpressionKind::Index { iterable, index } => json!({
+        "kind": "Index",
+        "iterable": self.expression(iterable),
+        "index": self.expression(index),
+      }),
+      ExpressionKind::Slice {
+        iterable,
+        start,
+        end,
+      } => json!({
+        "kind": "Slice",
+        "iterable": self.expression(iterable),
+        "start": optional(start),
+        "end": optional(end),
+      }),
+      ExpressionKind::FunctionCall { name, arguments } => json!({
+        "kind": "FunctionCall",
+        "name": self.identifier(name),
+        "arguments": arguments.iter().map(|a| self.expression(a)).collect::<Vec<_>>(),
+      }),
+      ExpressionKind::If {
+        condition,
+        consequence,
+        alternative,
+      } => json!({
+        "kind": "If",
+        "condition": self.expression(condition),
+        "consequence": self.statement(consequence),
+        "alternative": match alternative.as_ref() {
+          Some(alternative) => self.statement(alternative),
+          None => Json::Null,
+        },
+      }),
+      ExpressionKind::For {
+        variable,
+        iterable,
+        body,
+      } => json!({
+        "kind": "For",
+        "variable": self.identifier(variable),
+        "iterable": self.expression(iterable),
+        "body": self.statement(body),
+      }),
+      ExpressionKind::While { condition, body } => json!({
+        "kind": "While",
+        "condition": self.expression(condition),
+        "body": self.statement(body),
+      }),
+      ExpressionKind::Binary {
+        operator,
+        left,
+        right,
+      } => json!({
+        "kind": "Binary",
+        "operator": operator.to_string(),
+        "left": self.expression(left),
+        "right": self.expression(right),
+      }),
+      ExpressionKind::Unary { operator, operand } => json!({
+        "kind": "Unary",
+        "operator": operator.to_string(),
+        "operand": self.expression(operand),
+      }),
+      ExpressionKind::Yield(value) => json!({
+        "kind": "Yield",
+        "value": self.expression(value),
+      }),
+    };
+
+    node["span"] = self.span(&expression.span);
+    node
+  }
+}
@@ -1,121 +1,558 @@
-use ariadne::Source;
-use chumsky::Parser;
-use inquire::{
-  set_global_render_config,
-  ui::{RenderConfig, StyleSheet, Styled},
-  InquireError, Text,
+mod debug;
+mod diagnostics;
+mod dump;
+mod repl;
+mod test_runner;
+
+use diagnostics::{
+  report_evaluation_error, report_program, report_program_warnings, report_warning,
+  set_error_format, ErrorFormat,
 };
+use std::{str::FromStr, time::Duration};
 use telid_lang::{
+  debugger::Debugger,
   evaluator::scope::Scope,
-  evaluator::{evaluate, scope, value::Value},
-  lexer::tokens::TokenKind,
-  lexer::Lexer,
-  parser::parser,
+  evaluator::{
+    context::Context, coverage::Coverage, evaluate_with_context, limits::Limits,
+    profiler::Profiler, scope, value::Value,
+  },
+  explain::explain,
+  formatter::format,
+  linter::lint,
+  parser::ast::Statement,
+  position::{position_of, positions},
+  program::{self, compile, Resolve, Tokens},
+  resolver::resolve,
 };
-use util::simple_error_to_report;
 
-mod util;
+// Takes the arguments after the subcommand's name
+type Subcommand = fn(Vec<String>) -> Result<(), ()>;
+
+const ERROR_FORMAT: &str = "--error-format=";
 
 fn main() {
-  set_global_render_config(get_repl_render_config());
+  let mut args = std::env::args().skip(1).peekable();
+  while let Some(arg) = args.next_if(|arg| arg.starts_with(ERROR_FORMAT)) {
+    read_error_format(&arg);
+  }
+
+  let subcommand: Option<Subcommand> = match args.peek().map(String::as_str) {
+    Some("run") => Some(run_program),
+    Some("check") => Some(run_check),
+    Some("tokens") => Some(dump::run_tokens),
+    Some("ast") => Some(dump::run_ast),
+    Some("fmt") => Some(run_fmt),
+    Some("lint") => Some(run_lint),
+    Some("debug") => Some(run_debug),
+    Some("test") => Some(test_runner::run_tests),
+    Some("--explain") => Some(run_explain),
+    _ => None,
+  };
+  let runs = subcommand.is_none() || args.peek().is_some_and(|arg| arg == "run");
+  if subcommand.is_some() {
+    args.next();
+  }
 
-  let args = std::env::args().collect::<Vec<_>>();
-  match args.get(1) {
-    Some(path) => {
-      if let Err(_) = run_file(path) {
-        std::process::exit(1);
+  // The error format applies to every subcommand, so it can go anywhere,
+  // except after the file `run` runs, since what comes after that is the
+  // program's. `run` reads it with its other options
+  let args = args
+    .filter(|arg| {
+      let format = !runs && arg.starts_with(ERROR_FORMAT);
+      if format {
+        read_error_format(arg);
       }
+      !format
+    })
+    .collect();
+
+  // Without a subcommand, `telid` works like `telid run`
+  if subcommand.unwrap_or(run_program)(args).is_err() {
+    std::process::exit(1);
+  }
+}
+
+fn read_error_format(arg: &str) {
+  match &arg[ERROR_FORMAT.len()..] {
+    "json" => set_error_format(ErrorFormat::Json),
+    "human" => set_error_format(ErrorFormat::Human),
+    format => {
+      eprintln!("Unknown error format {:?}, expected human or json", format);
+      std::process::exit(1);
     }
-    None => run_repl(),
   }
 }
 
-fn get_repl_render_config() -> RenderConfig {
-  let prefix = Styled::new(">");
+// Code given with `--eval` is named this in reports
+const EVAL: &str = "<eval>";
+
+const RUN_USAGE: &str = "Usage: telid [run] [options] <file> [args]
+       telid [options] -e <code> [args]
+Options: --seed <n>, --deterministic, --profile, --folded <file>, --coverage,
+         --lcov <file>, --sandbox, --fuel <n>, --time-limit <ms>, --max-depth <n>";
+
+// Runs a file, or code given with `--eval`, or starts the REPL if there's
+// neither. Arguments after the file (or the code) are passed to the program
+fn run_program(args: Vec<String>) -> Result<(), ()> {
+  let mut args = args.into_iter();
+  let mut path = None;
+  let mut eval = None;
+  let mut seed = None;
+  let mut deterministic = false;
+  let mut instruments = Instruments::default();
+  let mut limits = Limits::default();
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--seed" => seed = Some(number_argument(&mut args, "--seed")?),
+      // Seeded randomness (0 unless --seed is given) and a clock frozen at the epoch
+      "--deterministic" => deterministic = true,
+      "--profile" => instruments.profile = true,
+      // Also profiles, and writes the stacks for flame graph tools
+      "--folded" => instruments.folded = Some(file_argument(&mut args, "--folded")),
+      "--coverage" => instruments.coverage = true,
+      // Also measures coverage, and writes it for lcov tools
+      "--lcov" => instruments.lcov = Some(file_argument(&mut args, "--lcov")),
+      // Limits for code that isn't trusted. The others override its defaults
+      "--sandbox" => limits = Limits::sandbox(),
+      "--fuel" => limits.fuel = Some(number_argument(&mut args, "--fuel")?),
+      "--time-limit" => {
+        let milliseconds = number_argument(&mut args, "--time-limit")?;
+        limits.time = Some(Duration::from_millis(milliseconds));
+      }
+      "--max-depth" => limits.call_depth = Some(number_argument(&mut args, "--max-depth")?),
+      _ if arg.starts_with(ERROR_FORMAT) => read_error_format(&arg),
+      "-e" | "--eval" => match args.next() {
+        Some(code) => {
+          eval = Some(code);
+          break;
+        }
+        None => {
+          eprintln!("{} expects code to run", arg);
+          return Err(());
+        }
+      },
+      _ if arg.starts_with("--") => {
+        eprintln!("Unknown option {}", arg);
+        eprintln!("{}", RUN_USAGE);
+        return Err(());
+      }
+      _ => {
+        path = Some(arg);
+        break;
+      }
+    }
+  }
+
+  let mut context = match (deterministic, seed) {
+    (true, seed) => Context::deterministic(seed.unwrap_or(0), 0.0),
+    (false, Some(seed)) => Context::with_seed(seed),
+    (false, None) => Context::new(),
+  };
+  context.limits = limits;
+  context.args = args.collect();
 
-  let mut render_config = RenderConfig::default();
-  render_config.prompt_prefix = prefix.clone();
-  render_config.answer = StyleSheet::new();
-  render_config.answered_prompt_prefix = prefix.clone();
+  let (source, id) = match (eval, path) {
+    (Some(code), _) => (code, EVAL.to_string()),
+    (None, Some(path)) => (read_source(&path)?, path),
+    (None, None) => {
+      repl::run_repl(&mut context);
+      return Ok(());
+    }
+  };
 
-  render_config
+  let (output, _) = if instruments.any() {
+    run_instrumented(&source, &id, &mut context, &instruments)?
+  } else {
+    run(&source, &id, resolve, scope::default(), &mut context)?
+  };
+
+  // Like in the REPL, what the code evaluates to is the point of `--eval`
+  if id == EVAL && output != Value::Void {
+    println!("{}", output);
+  }
+
+  Ok(())
+}
+
+fn number_argument<T: FromStr>(
+  args: &mut impl Iterator<Item = String>,
+  option: &str,
+) -> Result<T, ()> {
+  match args.next().map(|number| number.parse()) {
+    Some(Ok(number)) => Ok(number),
+    _ => {
+      eprintln!("{} expects a non-negative integer", option);
+      Err(())
+    }
+  }
 }
 
-fn run_file(path: &str) -> Result<(Value, Scope), ()> {
-  let source = std::fs::read_to_string(path).expect("Failed to read source file");
-  run(&source, path, scope::default())
+fn file_argument(args: &mut impl Iterator<Item = String>, option: &str) -> String {
+  args.next().unwrap_or_else(|| {
+    eprintln!("{} expects a file to write to", option);
+    std::process::exit(1);
+  })
 }
 
-fn run_repl() {
-  let mut scope = scope::default();
+// `-` reads the program from stdin. Failures are reported
+fn read_source(path: &str) -> Result<String, ()> {
+  if path == "-" {
+    std::io::read_to_string(std::io::stdin())
+      .map_err(|error| eprintln!("Failed to read stdin: {}", error))
+  } else {
+    read_file(path)
+  }
+}
 
-  loop {
-    let input = Text::new("").prompt();
+fn read_file(path: &str) -> Result<String, ()> {
+  std::fs::read_to_string(path).map_err(|error| eprintln!("Failed to read {}: {}", path, error))
+}
 
-    match input {
-      Ok(input) => {
-        if let Ok((output, scope_)) = run(&input, "repl", scope.clone()) {
-          if output != Value::Void {
-            println!("{}", output.to_string());
-          }
+fn write_file(path: &str, contents: String) -> Result<(), ()> {
+  std::fs::write(path, contents).map_err(|error| eprintln!("Failed to write {}: {}", path, error))
+}
 
-          scope = scope_;
-        }
-      }
-      Err(error) => match error {
-        InquireError::OperationCanceled | InquireError::OperationInterrupted => {
-          println!("Type exit(0) to exit")
-        }
-        _ => panic!("Unexpected error: {:?}", error),
-      },
+// Subcommands that take files fail on anything else that looks like an
+// option, rather than trying to read it
+fn reject_options(args: &[String], usage: &str) -> Result<(), ()> {
+  match args.iter().find(|arg| arg.starts_with("--")) {
+    Some(option) => {
+      eprintln!("Unknown option {}", option);
+      eprintln!("{}", usage);
+      Err(())
     }
+    None => Ok(()),
   }
 }
 
-fn run(source: &str, id: &str, scope: Scope) -> Result<(Value, Scope), ()> {
-  let mut lexer = Lexer::new(source.clone());
-  let tokens = lexer.lex(false);
+// Lexes, parses, resolves and checks files without running them, reporting
+// every error found
+fn run_check(paths: Vec<String>) -> Result<(), ()> {
+  const USAGE: &str = "Usage: telid check <files>";
+  reject_options(&paths, USAGE)?;
+  if paths.is_empty() {
+    eprintln!("{}", USAGE);
+    return Err(());
+  }
 
-  for token in tokens.clone() {
-    match token.0 {
-      TokenKind::Error(error) => {
-        error
-          .report(id, token.1)
-          .eprint((id, Source::from(source.clone())))
-          .unwrap();
+  let mut result = Ok(());
 
-        return Err(());
-      }
-      _ => {}
+  for path in paths {
+    let Ok(source) = read_source(&path) else {
+      result = Err(());
+      continue;
+    };
+    let program = compile(&source, &scope::default(), resolve);
+    report_program_warnings(&source, &path, &program);
+    if !program.is_ok() {
+      report_program(&source, &path, &program);
+      result = Err(());
     }
   }
 
-  let parser = parser();
-  let ast = parser.parse(
-    tokens
-      .clone()
-      .into_iter()
-      .map(|token| token.0)
-      .collect::<Vec<_>>(),
+  result
+}
+
+// Number of statements shown in a profile
+const HOT_STATEMENTS: usize = 10;
+
+// What to measure while a file runs, and where to write the results
+#[derive(Default)]
+struct Instruments {
+  profile: bool,
+  folded: Option<String>,
+  coverage: bool,
+  lcov: Option<String>,
+}
+
+impl Instruments {
+  fn any(&self) -> bool {
+    self.profile || self.folded.is_some() || self.coverage || self.lcov.is_some()
+  }
+}
+
+// Runs a file while measuring it, then prints what was measured to stderr,
+// even if the program failed. Fails if what was measured can't be written
+fn run_instrumented(
+  source: &str,
+  path: &str,
+  context: &mut Context,
+  instruments: &Instruments,
+) -> Result<(Value, Scope), ()> {
+  if instruments.profile || instruments.folded.is_some() {
+    context.profiler = Some(Profiler::new());
+  }
+  if instruments.coverage || instruments.lcov.is_some() {
+    // Coverage has to know about the statements that never run too
+    let (ast, _) = parse(source, path)?;
+    context.coverage = Some(Coverage::new(&ast));
+  }
+
+  let result = run(source, path, resolve, scope::default(), context);
+
+  let mut written = Ok(());
+  if let Some(mut profiler) = context.profiler.take() {
+    profiler.finish();
+    written = report_profile(path, source, &profiler, instruments.folded.as_deref());
+  }
+  if let Some(coverage) = context.coverage.take() {
+    let lcov = report_coverage(path, source, &coverage, instruments.lcov.as_deref());
+    written = written.and(lcov);
+  }
+
+  result.and_then(|result| written.map(|()| result))
+}
+
+fn report_profile(
+  path: &str,
+  source: &str,
+  profiler: &Profiler,
+  folded: Option<&str>,
+) -> Result<(), ()> {
+  eprintln!();
+  eprintln!("Functions, by exclusive time:");
+  eprintln!(
+    "{:>8} {:>12} {:>12}  name",
+    "calls", "inclusive", "exclusive"
   );
+  for (name, function) in profiler.functions() {
+    eprintln!(
+      "{:>8} {:>12} {:>12}  {}",
+      function.calls,
+      milliseconds(function.inclusive),
+      milliseconds(function.exclusive),
+      name
+    );
+  }
+
+  let positions = positions(source);
+  eprintln!();
+  eprintln!("Slowest statements:");
+  eprintln!("{:>8} {:>12}  location", "count", "time");
+  for (span, statement) in profiler.statements().into_iter().take(HOT_STATEMENTS) {
+    let position = position_of(&positions, span.start);
+    eprintln!(
+      "{:>8} {:>12}  {}:{}:{}",
+      statement.count,
+      milliseconds(statement.time),
+      path,
+      position.line + 1,
+      position.column + 1
+    );
+  }
+
+  match folded {
+    Some(folded) => write_file(folded, profiler.folded()),
+    None => Ok(()),
+  }
+}
+
+fn report_coverage(
+  path: &str,
+  source: &str,
+  coverage: &Coverage,
+  lcov: Option<&str>,
+) -> Result<(), ()> {
+  let lines = coverage.lines(source);
+  let percentage = |(found, hit): (usize, usize)| {
+    let percentage = if found == 0 {
+      100.0
+    } else {
+      hit as f64 * 100.0 / found as f64
+    };
+    format!("{}/{} ({:.1}%)", hit, found, percentage)
+  };
+
+  eprintln!();
+  eprintln!("Coverage of {}:", path);
+  eprintln!("  lines     {}", percentage(lines.line_totals()));
+  eprintln!("  branches  {}", percentage(lines.branch_totals()));
+
+  let missed = lines
+    .missed_lines()
+    .into_iter()
+    .map(|range| match range.len() {
+      1 => (range.start + 1).to_string(),
+      _ => format!("{}-{}", range.start + 1, range.end),
+    })
+    .collect::<Vec<_>>();
+  if !missed.is_empty() {
+    eprintln!("  lines not run: {}", missed.join(", "));
+  }
+
+  let untaken = lines
+    .branches
+    .iter()
+    .flat_map(|(line, counts)| {
+      [(counts[0], "then"), (counts[1], "else")]
+        .into_iter()
+        .filter(|(count, _)| *count == 0)
+        .map(move |(_, side)| format!("{} ({})", line + 1, side))
+    })
+    .collect::<Vec<_>>();
+  if !untaken.is_empty() {
+    eprintln!("  branches not taken: {}", untaken.join(", "));
+  }
+
+  match lcov {
+    Some(lcov) => write_file(lcov, lines.lcov(path)),
+    None => Ok(()),
+  }
+}
+
+fn milliseconds(duration: std::time::Duration) -> String {
+  format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+// Describes an error code, with an example of the error and how to fix it
+fn run_explain(args: Vec<String>) -> Result<(), ()> {
+  let [code] = args.as_slice() else {
+    eprintln!("Usage: telid --explain <code>");
+    return Err(());
+  };
+
+  match explain(code) {
+    Some(explanation) => {
+      println!("{}", explanation);
+      Ok(())
+    }
+    None => {
+      eprintln!("{} is not an error code", code);
+      Err(())
+    }
+  }
+}
+
+// Formats files in place, or with `--check`, lists the ones that aren't
+// formatted without touching them
+fn run_fmt(args: Vec<String>) -> Result<(), ()> {
+  const USAGE: &str = "Usage: telid fmt [--check] <files>";
+  let mut check = false;
+  let mut paths = Vec::new();
+
+  for arg in args {
+    match arg.as_str() {
+      "--check" => check = true,
+      _ => paths.push(arg),
+    }
+  }
 
-  if let Err(error) = ast {
-    for error in error {
-      simple_error_to_report(error, id, tokens.clone())
-        .eprint((id, Source::from(source.clone())))
-        .unwrap();
+  reject_options(&paths, USAGE)?;
+  if paths.is_empty() {
+    eprintln!("{}", USAGE);
+    return Err(());
+  }
+
+  let mut result = Ok(());
+
+  for path in paths {
+    let Ok(source) = read_file(&path) else {
+      result = Err(());
+      continue;
+    };
+    let Ok((ast, _)) = parse(&source, &path) else {
+      result = Err(());
+      continue;
+    };
+
+    let formatted = format(&source, &ast);
+    if formatted == source {
+      continue;
+    }
+
+    if check {
+      println!("{} is not formatted", path);
+      result = Err(());
+    } else if let Err(error) = std::fs::write(&path, formatted) {
+      eprintln!("Failed to write {}: {}", path, error);
+      result = Err(());
+    }
+  }
+
+  result
+}
+
+// Prints warnings for likely mistakes, and fails if there are any
+fn run_lint(paths: Vec<String>) -> Result<(), ()> {
+  const USAGE: &str = "Usage: telid lint <files>";
+  reject_options(&paths, USAGE)?;
+  if paths.is_empty() {
+    eprintln!("{}", USAGE);
+    return Err(());
+  }
+
+  let mut result = Ok(());
+
+  for path in paths {
+    let Ok(source) = read_source(&path) else {
+      result = Err(());
+      continue;
+    };
+    let Ok((ast, tokens)) = parse(&source, &path) else {
+      result = Err(());
+      continue;
+    };
+
+    for warning in lint(&source, &ast, &scope::default()) {
+      report_warning(&source, &path, &warning, &tokens);
+      result = Err(());
     }
+  }
+
+  result
+}
+
+// Runs a file under the debugger, which pauses before the first statement
+fn run_debug(args: Vec<String>) -> Result<(), ()> {
+  const USAGE: &str = "Usage: telid debug <file>";
+  reject_options(&args, USAGE)?;
+  let [path] = args.as_slice() else {
+    eprintln!("{}", USAGE);
+    return Err(());
+  };
+
+  let source = read_file(path)?;
+  let mut context = Context::new();
+  context.hook = Some(Box::new(Debugger::new(
+    &source,
+    debug::Console::new(path, &source),
+  )));
+
+  run(&source, path, resolve, scope::default(), &mut context).map(|_| ())
+}
+
+// Lexes and parses `source`, reporting every error it finds. The tokens are the
+// ones the AST's spans refer to
+fn parse(source: &str, id: &str) -> Result<(Vec<Statement>, Tokens), ()> {
+  let program = program::parse(source);
+  if !program.is_ok() {
+    report_program(source, id, &program);
+    return Err(());
+  }
+
+  Ok((program.ast, program.tokens))
+}
 
+fn run(
+  source: &str,
+  id: &str,
+  resolve: Resolve,
+  scope: Scope,
+  context: &mut Context,
+) -> Result<(Value, Scope), ()> {
+  // Mistakes the resolver and the type checker can see are reported before
+  // anything runs
+  let program = compile(source, &scope, resolve);
+  if !program.is_ok() {
+    report_program(source, id, &program);
     return Err(());
   }
 
-  match evaluate(ast.unwrap(), scope.clone()) {
+  match evaluate_with_context(program.ast, scope.clone(), context) {
     Ok(scope) => Ok(scope),
     Err(error) => {
-      error
-        .report(id, error.span.clone(), tokens)
-        .eprint((id, Source::from(source.clone())))
-        .unwrap();
+      report_evaluation_error(source, id, &error, &program.tokens);
       Err(())
     }
   }
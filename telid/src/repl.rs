@@ -0,0 +1,282 @@
+use crate::{
+  dump::{print_ast, print_tokens},
+  milliseconds, parse, run,
+};
+use rustyline::{
+  completion::{Completer, FilenameCompleter, Pair},
+  error::ReadlineError,
+  highlight::Highlighter,
+  hint::Hinter,
+  history::DefaultHistory,
+  validate::{ValidationContext, ValidationResult, Validator},
+  Editor, Helper,
+};
+use std::{ops::ControlFlow, path::PathBuf, time::Instant};
+use telid_lang::{
+  checker::type_of,
+  evaluator::{context::Context, scope, scope::Scope, value::Value},
+  lexer::{
+    tokens::{TokenKind, KEYWORDS},
+    Lexer,
+  },
+  parser::incomplete,
+  resolver::resolve_partial,
+};
+
+const PROMPT: &str = "> ";
+
+const HELP: &str = "Commands:
+  :tokens <code>   Show the tokens <code> lexes to
+  :ast <code>      Show the statements <code> parses to
+  :type <expr>     Show the type of <expr>'s value
+  :time <code>     Run <code> and show how long it took
+  :load <file>     Run <file> in the session
+  :env             Show the variables declared in the session
+  :reset           Forget every variable declared in the session
+  :quit, :q        Leave the REPL
+  :help, :h        Show this message";
+
+const COMMANDS: [&str; 11] = [
+  ":tokens", ":ast", ":type", ":time", ":load", ":env", ":reset", ":quit", ":q", ":help", ":h",
+];
+
+pub fn run_repl(context: &mut Context) {
+  let mut editor = Editor::<Session, DefaultHistory>::new().expect("Failed to start the REPL");
+  let mut scope = scope::default();
+  editor.set_helper(Some(Session::new(&scope)));
+
+  let history = history_path();
+  if let Some(history) = &history {
+    // There's no history the first time
+    let _ = editor.load_history(history);
+  }
+
+  loop {
+    match editor.readline(PROMPT) {
+      Ok(input) => {
+        if input.trim().is_empty() {
+          continue;
+        }
+        let _ = editor.add_history_entry(input.as_str());
+        if let Some(history) = &history {
+          // Saved every time, since `exit` doesn't give us a chance later
+          let _ = editor.save_history(history);
+        }
+
+        if let Some(command) = input.trim().strip_prefix(':') {
+          if run_command(command, &mut scope, context).is_break() {
+            return;
+          }
+          editor.set_helper(Some(Session::new(&scope)));
+          continue;
+        }
+
+        if let Ok((output, scope_)) = run(&input, "repl", resolve_partial, scope.clone(), context) {
+          if output != Value::Void {
+            println!("{}", output);
+          }
+
+          scope = scope_;
+          editor.set_helper(Some(Session::new(&scope)));
+        }
+      }
+      Err(ReadlineError::Interrupted) => println!("Type :quit to exit"),
+      Err(ReadlineError::Eof) => return,
+      Err(error) => panic!("Unexpected error: {:?}", error),
+    }
+  }
+}
+
+// Runs a line that started with `:`, and breaks if the REPL should stop
+fn run_command(input: &str, scope: &mut Scope, context: &mut Context) -> ControlFlow<()> {
+  let (command, argument) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+  let argument = argument.trim();
+
+  let usage = |argument_name: &str| println!("Usage: :{} <{}>", command, argument_name);
+
+  match command {
+    "tokens" if argument.is_empty() => usage("code"),
+    "tokens" => {
+      print_tokens(&Lexer::new(argument).lex(false));
+    }
+    "ast" if argument.is_empty() => usage("code"),
+    "ast" => {
+      if let Ok((ast, _)) = parse(argument, "repl") {
+        print_ast(&ast);
+      }
+    }
+    "type" if argument.is_empty() => usage("expr"),
+    // The expression's side effects on variables are thrown away
+    "type" => {
+      if let Ok((value, _)) = run(argument, "repl", resolve_partial, scope.clone(), context) {
+        println!("{}", type_of(&value));
+      }
+    }
+    "time" if argument.is_empty() => usage("code"),
+    "time" => {
+      let start = Instant::now();
+      let result = run(argument, "repl", resolve_partial, scope.clone(), context);
+      let elapsed = start.elapsed();
+
+      if let Ok((output, scope_)) = result {
+        if output != Value::Void {
+          println!("{}", output);
+        }
+        *scope = scope_;
+      }
+      println!("Took {}", milliseconds(elapsed));
+    }
+    "load" if argument.is_empty() => usage("file"),
+    "load" => match std::fs::read_to_string(argument) {
+      Ok(source) => {
+        if let Ok((_, scope_)) = run(&source, argument, resolve_partial, scope.clone(), context) {
+          *scope = scope_;
+        }
+      }
+      Err(error) => println!("Failed to read {}: {}", argument, error),
+    },
+    "env" => {
+      // Builtins are left out, unless they've been replaced
+      let builtins = scope::default();
+      for frame in scope.frames() {
+        for (name, variable) in frame {
+          if builtins.get(name) == Some(variable) {
+            continue;
+          }
+
+          let annotation = match &variable.annotation {
+            Some(annotation) => annotation.clone(),
+            None => type_of(&variable.value),
+          };
+          let value = match &variable.value {
+            Value::String(string) => format!("{:?}", string),
+            value => value.to_string(),
+          };
+          let constant = if variable.constant { "const " } else { "" };
+
+          println!("{}{}: {} = {}", constant, name, annotation, value);
+        }
+      }
+    }
+    "reset" => *scope = scope::default(),
+    "quit" | "q" => return ControlFlow::Break(()),
+    "help" | "h" => println!("{}", HELP),
+    _ => println!("Unknown command :{}, type :help for a list", command),
+  }
+
+  ControlFlow::Continue(())
+}
+
+// `TELID_HISTORY`, or `.telid_history` in the home directory
+fn history_path() -> Option<PathBuf> {
+  match std::env::var_os("TELID_HISTORY") {
+    Some(path) => Some(PathBuf::from(path)),
+    None => std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".telid_history")),
+  }
+}
+
+// Completes names and keywords, and keeps reading lines until the input is
+// complete
+struct Session {
+  // Every name declared in the session, and the keywords
+  names: Vec<String>,
+  files: FilenameCompleter,
+}
+
+impl Session {
+  fn new(scope: &Scope) -> Self {
+    let mut names = scope
+      .frames()
+      .flatten()
+      .map(|(name, _)| name.clone())
+      .chain(KEYWORDS.iter().map(|keyword| keyword.to_string()))
+      .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+
+    Session {
+      names,
+      files: FilenameCompleter::new(),
+    }
+  }
+}
+
+impl Completer for Session {
+  type Candidate = Pair;
+
+  fn complete(
+    &self,
+    line: &str,
+    position: usize,
+    context: &rustyline::Context<'_>,
+  ) -> rustyline::Result<(usize, Vec<Pair>)> {
+    if line.starts_with(":load ") {
+      return self.files.complete(line, position, context);
+    }
+    if line.starts_with(':') && !line[..position].contains(char::is_whitespace) {
+      let candidates = COMMANDS
+        .iter()
+        .filter(|command| command.starts_with(&line[..position]))
+        .map(|command| Pair {
+          display: command.to_string(),
+          replacement: command.to_string(),
+        })
+        .collect();
+      return Ok((0, candidates));
+    }
+
+    let start = line[..position]
+      .char_indices()
+      .rev()
+      .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+      .last()
+      .map_or(position, |(index, _)| index);
+    let prefix = &line[start..position];
+    if prefix.is_empty() {
+      return Ok((position, Vec::new()));
+    }
+
+    let candidates = self
+      .names
+      .iter()
+      .filter(|name| name.starts_with(prefix))
+      .map(|name| Pair {
+        display: name.clone(),
+        replacement: name.clone(),
+      })
+      .collect();
+    Ok((start, candidates))
+  }
+}
+
+impl Validator for Session {
+  fn validate(&self, context: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+    let input = context.input();
+
+    // An empty line runs whatever was entered, so mistakes don't trap you
+    // in an unfinished input. Commands only take one line
+    if input.ends_with('\n') || input.trim_start().starts_with(':') {
+      return Ok(ValidationResult::Valid(None));
+    }
+
+    let tokens = Lexer::new(input)
+      .lex(false)
+      .into_iter()
+      .map(|token| token.0)
+      .filter(|token| !matches!(token, TokenKind::Error(_)))
+      .collect::<Vec<_>>();
+    if incomplete(&tokens) {
+      Ok(ValidationResult::Incomplete)
+    } else {
+      Ok(ValidationResult::Valid(None))
+    }
+  }
+}
+
+impl Hinter for Session {
+  type Hint = String;
+}
+
+impl Highlighter for Session {}
+
+impl Helper for Session {}
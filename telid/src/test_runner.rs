@@ -0,0 +1,147 @@
+use crate::{diagnostics::report_evaluation_error, parse, reject_options, run};
+use std::path::{Path, PathBuf};
+use telid_lang::{
+  evaluator::{
+    context::Context,
+    evaluate_with_context,
+    limits::{Capabilities, Limits},
+    scope,
+  },
+  parser::ast::{Binding, Expression, ExpressionKind, Identifier, Statement, StatementKind},
+  resolver::resolve,
+};
+
+// Test functions are top-level functions with this prefix and no parameters
+const PREFIX: &str = "test_";
+
+// Runs the tests in every `.tl` file under the given directory (or the
+// current one), or in the given files. Each file's top level runs once, and
+// then each test runs in its own copy of the scope it left behind
+pub fn run_tests(args: Vec<String>) -> Result<(), ()> {
+  reject_options(&args, "Usage: telid test [<files or directories>]")?;
+  let paths = match args.as_slice() {
+    [] => vec![String::from(".")],
+    _ => args,
+  };
+
+  let mut files = Vec::new();
+  for path in paths {
+    collect(Path::new(&path), &mut files);
+  }
+  files.sort();
+
+  let mut passed = 0;
+  let mut failed = 0;
+
+  for file in files {
+    let path = file.to_string_lossy().to_string();
+    let source = match std::fs::read_to_string(&file) {
+      Ok(source) => source,
+      Err(error) => {
+        println!("{} ... FAILED to load: {}", path, error);
+        failed += 1;
+        continue;
+      }
+    };
+
+    let Ok((ast, tokens)) = parse(&source, &path) else {
+      println!("{} ... FAILED to load", path);
+      failed += 1;
+      continue;
+    };
+    let tests = ast
+      .iter()
+      .filter_map(|statement| match &statement.kind {
+        StatementKind::FunctionDeclaration {
+          name, parameters, ..
+        } if name.0.starts_with(PREFIX) && parameters.is_empty() => Some(name.clone()),
+        _ => None,
+      })
+      .collect::<Vec<_>>();
+    if tests.is_empty() {
+      continue;
+    }
+
+    let Ok((_, scope)) = run(&source, &path, resolve, scope::default(), &mut context()) else {
+      println!("{} ... FAILED to load", path);
+      failed += 1;
+      continue;
+    };
+
+    for test in tests {
+      let call = call(&test);
+      match evaluate_with_context(vec![call], scope.clone(), &mut context()) {
+        Ok(_) => {
+          println!("{}::{} ... ok", path, test.0);
+          passed += 1;
+        }
+        Err(error) => {
+          println!("{}::{} ... FAILED", path, test.0);
+          report_evaluation_error(&source, &path, &error, &tokens);
+          failed += 1;
+        }
+      }
+    }
+  }
+
+  println!();
+  println!("{} passed, {} failed", passed, failed);
+
+  if failed > 0 {
+    Err(())
+  } else {
+    Ok(())
+  }
+}
+
+// Code under test can't end `telid test` or wait for input, so `exit` and
+// `readln` fail the test that calls them instead
+fn context() -> Context {
+  let mut context = Context::new();
+  context.limits = Limits {
+    capabilities: Capabilities {
+      exit: false,
+      stdin: false,
+      ..Capabilities::default()
+    },
+    ..Limits::default()
+  };
+  context
+}
+
+// Adds `path` if it's a file, or every `.tl` file under it if it's a
+// directory
+fn collect(path: &Path, files: &mut Vec<PathBuf>) {
+  if !path.is_dir() {
+    files.push(path.to_path_buf());
+    return;
+  }
+
+  let Ok(entries) = std::fs::read_dir(path) else {
+    return;
+  };
+  for entry in entries.flatten() {
+    let path = entry.path();
+    if path.is_dir() {
+      collect(&path, files);
+    } else if path.extension().is_some_and(|extension| extension == "tl") {
+      files.push(path);
+    }
+  }
+}
+
+// A call to the test, with the span of its name so that errors in the call
+// itself point at the declaration
+fn call(test: &Identifier) -> Statement {
+  let span = test.1.clone();
+  Statement {
+    kind: StatementKind::Expression(Expression {
+      kind: ExpressionKind::FunctionCall {
+        name: Identifier(test.0.clone(), span.clone(), Binding::Dynamic),
+        arguments: Vec::new(),
+      },
+      span: span.clone(),
+    }),
+    span,
+  }
+}
@@ -0,0 +1,203 @@
+use std::{
+  path::PathBuf,
+  process::{Command, Output},
+};
+
+fn telid(args: &[&str]) -> Output {
+  Command::new(env!("CARGO_BIN_EXE_telid"))
+    .args(args)
+    .output()
+    .unwrap()
+}
+
+// A fresh directory for a test's files
+fn directory(name: &str) -> PathBuf {
+  let directory = std::env::temp_dir().join(format!("telid-{}-{}", name, std::process::id()));
+  let _ = std::fs::remove_dir_all(&directory);
+  std::fs::create_dir_all(&directory).unwrap();
+  directory
+}
+
+#[test]
+fn test_test_command() {
+  let directory = directory("test");
+  std::fs::create_dir(directory.join("nested")).unwrap();
+  std::fs::write(
+    directory.join("math.tl"),
+    "let counter = 0;
+let fn add a b = + a b;
+
+let fn test_add = assert_eq(add(1, 2), 3);
+let fn test_mutates = {
+  counter = + counter 1;
+  assert_eq(counter, 1);
+}
+// Sees the counter as the top level left it
+let fn test_isolated = assert_eq(counter, 0);
+let fn test_throws = assert_throws(add);
+let fn helper x = x;
+",
+  )
+  .unwrap();
+  std::fs::write(
+    directory.join("nested").join("strings.tl"),
+    "let fn test_wrong = assert_eq(+ 'a' 'b', 'ac');",
+  )
+  .unwrap();
+  std::fs::write(
+    directory.join("notes.txt"),
+    "let fn test_ignored = exit(1);",
+  )
+  .unwrap();
+
+  let output = telid(&["test", directory.to_str().unwrap()]);
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  let stderr = String::from_utf8(output.stderr).unwrap();
+
+  assert_eq!(output.status.code(), Some(1));
+  for test in ["test_add", "test_mutates", "test_isolated", "test_throws"] {
+    assert!(
+      stdout.contains(&format!("math.tl::{} ... ok", test)),
+      "{}",
+      stdout
+    );
+  }
+  assert!(stdout.contains("strings.tl::test_wrong ... FAILED"));
+  assert!(!stdout.contains("helper"));
+  assert!(stdout.ends_with("4 passed, 1 failed\n"));
+  assert!(stderr.contains("left:  'ab'"));
+
+  // A path that can't be read fails like a file that can't be loaded
+  let missing = directory.join("missing.tl");
+  let output = telid(&["test", missing.to_str().unwrap()]);
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  assert!(
+    stdout.contains("missing.tl ... FAILED to load"),
+    "{}",
+    stdout
+  );
+  assert!(stdout.ends_with("0 passed, 1 failed\n"));
+
+  // A test that exits fails instead of ending the run
+  let exits = directory.join("exits.tl");
+  std::fs::write(
+    &exits,
+    "let fn test_exit = exit(3);\nlet fn test_later = assert(true);",
+  )
+  .unwrap();
+  let output = telid(&["test", exits.to_str().unwrap()]);
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  assert_eq!(output.status.code(), Some(1));
+  assert!(
+    stdout.contains("exits.tl::test_exit ... FAILED"),
+    "{}",
+    stdout
+  );
+  assert!(stdout.contains("exits.tl::test_later ... ok"), "{}", stdout);
+  assert!(stdout.ends_with("1 passed, 1 failed\n"));
+
+  std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_unannotated_type_errors() {
+  let directory = directory("types");
+  let path = directory.join("branch.tl");
+  std::fs::write(&path, "if false println(+ 1 true) else println('ok');").unwrap();
+
+  // The branch that can't work never runs
+  let output = telid(&["run", path.to_str().unwrap()]);
+  assert_eq!(output.status.code(), Some(0));
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), "ok\n");
+  assert!(output.stderr.is_empty());
+
+  // `check` still points it out, but only as a warning
+  let output = telid(&["--error-format=json", "check", path.to_str().unwrap()]);
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert_eq!(output.status.code(), Some(0));
+  assert!(stderr.contains("\"severity\":\"warning\""), "{}", stderr);
+  assert!(stderr.contains("T0009"), "{}", stderr);
+
+  std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_error_format() {
+  let directory = directory("format");
+  let path = directory.join("args.tl");
+  std::fs::write(&path, "println(args()); + 1 true;").unwrap();
+
+  // Before the file it's telid's, after it it's the program's
+  let output = telid(&[
+    "--error-format=json",
+    path.to_str().unwrap(),
+    "foo",
+    "--error-format=human",
+  ]);
+  let stdout = String::from_utf8(output.stdout).unwrap();
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert_eq!(stdout, "[foo, --error-format=human]\n");
+  assert!(stderr.starts_with('{'), "{}", stderr);
+
+  // Also among `run`'s other options
+  let output = telid(&[
+    "run",
+    "--seed",
+    "1",
+    "--error-format=json",
+    path.to_str().unwrap(),
+  ]);
+  let stderr = String::from_utf8(output.stderr).unwrap();
+  assert_eq!(String::from_utf8(output.stdout).unwrap(), "[]\n");
+  assert!(stderr.starts_with('{'), "{}", stderr);
+
+  std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[test]
+fn test_bad_arguments() {
+  // Unknown options are rejected with the usage, not read as files
+  for args in [
+    &["--help"][..],
+    &["fmt", "--help"],
+    &["ast", "--verbose", "a.tl"],
+  ] {
+    let output = telid(args);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{:?}", args);
+    assert!(stderr.contains("Unknown option --"), "{}", stderr);
+    assert!(stderr.contains("Usage: telid"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+  }
+
+  // Files that can't be read are reported
+  let directory = directory("arguments");
+  let missing = directory.join("missing.tl");
+  for subcommand in ["run", "check", "lint", "fmt", "tokens", "debug"] {
+    let output = telid(&[subcommand, missing.to_str().unwrap()]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", subcommand);
+    assert!(stderr.starts_with("Failed to read "), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+  }
+
+  // So are reports that can't be written, after the program has run
+  let program = directory.join("program.tl");
+  std::fs::write(&program, "println('ran');").unwrap();
+  let unwritable = directory.join("missing").join("report");
+  for option in ["--folded", "--lcov"] {
+    let output = telid(&[
+      option,
+      unwritable.to_str().unwrap(),
+      program.to_str().unwrap(),
+    ]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(output.status.code(), Some(1), "{}", option);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "ran\n");
+    assert!(stderr.contains("Failed to write "), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+  }
+
+  std::fs::remove_dir_all(directory).unwrap();
+}